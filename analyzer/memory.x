@@ -1,5 +1,24 @@
 MEMORY
 {
     FLASH : ORIGIN = 0x08000000, LENGTH = 2048K
-    RAM :   ORIGIN = 0x20000000, LENGTH = 192K
+
+    /* .data, .bss and the stack in the 64K CCM RAM, only the CPU reaches it */
+    RAM :   ORIGIN = 0x10000000, LENGTH = 64K
+
+    /* SRAM1-3, the DMA buffers of the sampler in .capture.dma and the
+     * capture buffers in the rest */
+    CAPTURE : ORIGIN = 0x20000000, LENGTH = 192K
 }
+
+SECTIONS
+{
+    .capture (NOLOAD) : ALIGN(8)
+    {
+        *(.capture .capture.*);
+        . = ALIGN(8);
+        _scapture = .;
+    } > CAPTURE
+}
+INSERT AFTER .bss;
+
+_ecapture = ORIGIN(CAPTURE) + LENGTH(CAPTURE);
@@ -0,0 +1,108 @@
+use core::mem::{align_of, size_of};
+use core::slice;
+use crate::sample::SampleBuffer;
+use crate::decoder::{Section, SectionBuffer};
+
+// Captured edges per decoder section, most decoders emit a section per bit
+const EDGES_PER_SECTION: usize = 2;
+
+// Slack for aligning the slices of the capture buffers
+const ALIGN_RESERVE: usize = 32;
+
+// Hands out slices of a memory region that is never freed,
+// used to place the capture buffers in their own linker section
+pub struct Arena
+{
+	next: usize,
+	end: usize
+}
+
+impl Arena
+{
+	// The region must be valid, used for nothing else and live forever
+	pub unsafe fn new(start: *mut u8, end: *mut u8) -> Arena
+	{
+		Arena
+		{
+			next: start as usize,
+			end: end as usize
+		}
+	}
+
+	#[cfg(any(test, feature = "std"))]
+	pub fn leak(size: usize) -> Arena
+	{
+		let mem: &'static mut [u64] = Box::leak(vec![0; size.div_ceil(8)].into_boxed_slice());
+		let start = mem.as_mut_ptr() as *mut u8;
+		unsafe { Arena::new(start, start.add(mem.len() * 8)) }
+	}
+
+	pub fn remaining(&self) -> usize
+	{
+		self.end - self.next
+	}
+
+	pub fn alloc<T: Copy>(&mut self, len: usize, init: T) -> Option<&'static mut [T]>
+	{
+		let start = self.next.next_multiple_of(align_of::<T>());
+		let end = start.checked_add(len.checked_mul(size_of::<T>())?)?;
+		if end > self.end
+		{
+			return None;
+		}
+
+		self.next = end;
+		unsafe
+		{
+			let ptr = start as *mut T;
+			for i in 0..len
+			{
+				ptr.add(i).write(init);
+			}
+
+			Some(slice::from_raw_parts_mut(ptr, len))
+		}
+	}
+}
+
+// Split the whole region between the sample and the section buffer
+pub fn capture_buffers(mut mem: Arena) -> (SampleBuffer, SectionBuffer)
+{
+	let avail = mem.remaining().saturating_sub(ALIGN_RESERVE);
+	let per_edge = SampleBuffer::ENTRY_SIZE + size_of::<Section>() / EDGES_PER_SECTION;
+	let edges = avail / per_edge;
+
+	let sec_buf = SectionBuffer::alloc(&mut mem, edges / EDGES_PER_SECTION).unwrap();
+	let buf = SampleBuffer::alloc(&mut mem, edges).unwrap();
+	(buf, sec_buf)
+}
+
+#[cfg(test)]
+mod tests
+{
+	use crate::arena::{capture_buffers, Arena};
+	use crate::timestamps::TimestampBuffer;
+
+	#[test]
+	fn test_alloc()
+	{
+		let mut mem = Arena::leak(64);
+		let a = mem.alloc::<u8>(3, 7).unwrap();
+		let b = mem.alloc::<u64>(4, 0).unwrap();
+		assert_eq!(a, &[7, 7, 7]);
+		assert_eq!(b.as_ptr() as usize % 8, 0);
+		assert!(mem.alloc::<u64>(4, 0).is_none());
+	}
+
+	#[test]
+	fn test_capture_buffers()
+	{
+		/* The SRAM of the board without the DMA buffers */
+		let (buf, sec_buf) = capture_buffers(Arena::leak(176 * 1024));
+		assert!(buf.capacity() > 6000 - TimestampBuffer::BLOCK);
+
+		/* The sample buffer is rounded down to whole blocks */
+		let sections = sec_buf.sections.len();
+		assert!(sections * 2 >= buf.capacity() && sections * 2 < buf.capacity() + 2 * TimestampBuffer::BLOCK);
+	}
+}
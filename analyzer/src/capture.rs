@@ -0,0 +1,305 @@
+use stm32f4xx_hal::pac::{RCC, TIM8};
+use core::ptr::{addr_of, read_volatile, write_volatile};
+use crate::hw::{timer_get, timer_prescale, timer_set};
+use crate::sample::{Sample, Timebase, Timestamp};
+
+/*
+ * TIM8 update events request DMA2 Stream 1 Channel 7, which copies the
+ * input register of port D (blue) into a circular buffer. The TIM8 compare
+ * event of channel 1 at the end of each period requests Stream 2 Channel 7
+ * for port E (yellow). The CPU only scans the buffers for level changes,
+ * so timestamps do not depend on loop latency.
+ * TIM2 is reset and divided down to the timebase when the capture starts,
+ * so sample n is taken at TIM2 tick n * sample period. Timestamps are derived
+ * from the 64 bit sample count, TIM2 itself is extended in software on every
+ * poll. The prescaler is restored when the capture stops.
+ * Disabled channels are masked out while scanning, so they never cause a change.
+ */
+
+const DMA_BUF_LEN: usize = 8192;
+
+// Samples the CPU may fall behind before data is lost
+const OVERRUN_LAG: i64 = (DMA_BUF_LEN - DMA_BUF_LEN / 8) as i64;
+
+const PERIPH_BASE      : u32 = 0x40000000;
+const AHB1PERIPH_BASE  : u32 = PERIPH_BASE + 0x20000;
+const GPIOD_IDR        : u32 = AHB1PERIPH_BASE + 0x0C00 + 0x10;
+const GPIOE_IDR        : u32 = AHB1PERIPH_BASE + 0x1000 + 0x10;
+const DMA2_BASE        : u32 = AHB1PERIPH_BASE + 0x6400;
+const DMA2_LIFCR       : u32 = DMA2_BASE + 0x08;
+
+/* Stream registers: 0x10 + 0x18 * Stream */
+const DMA2_S1CR        : u32 = DMA2_BASE + 0x28;
+const DMA2_S2CR        : u32 = DMA2_BASE + 0x40;
+const DMA_SXNDTR       : u32 = 0x04;
+const DMA_SXPAR        : u32 = 0x08;
+const DMA_SXM0AR       : u32 = 0x0C;
+const DMA_SXFCR        : u32 = 0x14;
+
+const DMA_SXCR_EN      : u32 = 1 << 0;
+const DMA_SXCR_CIRC    : u32 = 1 << 8;
+const DMA_SXCR_MINC    : u32 = 1 << 10;
+const DMA_SXCR_PL_HIGH : u32 = 3 << 16;
+const DMA_SXCR_CHSEL_7 : u32 = 7 << 25;
+
+/* FEIFx, DMEIFx, TEIFx, HTIFx, TCIFx of Stream 1 and 2 */
+const DMA_LIFCR_S1     : u32 = 0xF40;
+const DMA_LIFCR_S2     : u32 = 0x3D0000;
+
+const TIM_CR1_CEN      : u32 = 1 << 0;
+const TIM_DIER_UDE     : u32 = 1 << 8;
+const TIM_DIER_CC1DE   : u32 = 1 << 9;
+
+#[repr(align(4))]
+struct DmaBuf([u8; DMA_BUF_LEN]);
+
+/* In SRAM, DMA can not reach the CCM RAM with .bss. The section is not
+ * loaded, the buffers hold garbage until the first capture fills them. */
+#[link_section = ".capture.dma"]
+static mut DMA_BLUE: DmaBuf = DmaBuf([0; DMA_BUF_LEN]);
+#[link_section = ".capture.dma"]
+static mut DMA_YELLOW: DmaBuf = DmaBuf([0; DMA_BUF_LEN]);
+
+fn dma_blue() -> *const u8
+{
+	unsafe { addr_of!(DMA_BLUE.0) as *const u8 }
+}
+
+fn dma_yellow() -> *const u8
+{
+	unsafe { addr_of!(DMA_YELLOW.0) as *const u8 }
+}
+
+fn read_sample(idx: usize) -> Sample
+{
+	unsafe
+	{
+		(read_volatile(dma_blue().add(idx)) as Sample) |
+			((read_volatile(dma_yellow().add(idx)) as Sample) << 8)
+	}
+}
+
+// Four samples of a port at once
+fn read_word(buf: *const u8, idx: usize) -> u32
+{
+	unsafe { read_volatile(buf.add(idx) as *const u32) }
+}
+
+fn stream_pos(cr: u32) -> usize
+{
+	let ndtr = unsafe { read_volatile((cr + DMA_SXNDTR) as *const u32) } as usize;
+	(DMA_BUF_LEN - ndtr) % DMA_BUF_LEN
+}
+
+// Both streams are requested in the same timer period,
+// the one behind determines how far both buffers are valid
+fn write_pos() -> usize
+{
+	let blue = stream_pos(DMA2_S1CR);
+	let yellow = stream_pos(DMA2_S2CR);
+	if (blue + DMA_BUF_LEN - yellow) % DMA_BUF_LEN < DMA_BUF_LEN / 2 { yellow } else { blue }
+}
+
+unsafe fn stream_start(cr: u32, par: u32, buf: *const u8)
+{
+	write_volatile((cr + DMA_SXPAR) as *mut u32, par);
+	write_volatile((cr + DMA_SXM0AR) as *mut u32, buf as u32);
+	write_volatile((cr + DMA_SXNDTR) as *mut u32, DMA_BUF_LEN as u32);
+	write_volatile((cr + DMA_SXFCR) as *mut u32, 0);
+	write_volatile(cr as *mut u32,
+		DMA_SXCR_CHSEL_7 | DMA_SXCR_PL_HIGH | DMA_SXCR_MINC | DMA_SXCR_CIRC | DMA_SXCR_EN);
+}
+
+unsafe fn stream_stop(cr: u32)
+{
+	write_volatile(cr as *mut u32, 0);
+	while read_volatile(cr as *const u32) & DMA_SXCR_EN != 0 {}
+}
+
+// Sample in every byte
+fn spread(port: u8) -> u32
+{
+	(port as u32) * 0x01010101
+}
+
+pub struct Capture
+{
+	// Read position in the DMA buffer
+	rd: usize,
+
+	// Number of the sample at DMA buffer index 0 in the current lap
+	base: u64,
+
+	// Ticks between two samples
+	period: u32,
+
+	// Last TIM2 value and number of its overflows
+	timer_last: u32,
+	timer_wraps: u32,
+
+	prev: Sample,
+
+	// Ports of prev in every byte, to skip four unchanged samples at once
+	prev_blue: u32,
+	prev_yellow: u32,
+
+	// Enabled channels, also in every byte of each port
+	mask: Sample,
+	mask_blue: u32,
+	mask_yellow: u32,
+
+	pub overrun: bool
+}
+
+impl Capture
+{
+	pub fn start(timebase: Timebase, channels: Sample) -> Capture
+	{
+		let period = timebase.sample_period();
+		unsafe
+		{
+			(*RCC::ptr()).ahb1enr().modify(|_, w| w.dma2en().enabled());
+			(*RCC::ptr()).apb2enr().modify(|_, w| w.tim8en().enabled());
+
+			/* Stop Timer and DMA */
+			(*TIM8::ptr()).cr1().write(|w| w.bits(0));
+			stream_stop(DMA2_S1CR);
+			stream_stop(DMA2_S2CR);
+			write_volatile(DMA2_LIFCR as *mut u32, DMA_LIFCR_S1 | DMA_LIFCR_S2);
+
+			/* Port -> Buffer, Byte to Byte, Circular */
+			stream_start(DMA2_S1CR, GPIOD_IDR, dma_blue());
+			stream_start(DMA2_S2CR, GPIOE_IDR, dma_yellow());
+
+			/* Timer clock is 180 MHz, twice the TIM2 clock,
+			 * compare 1 matches one cycle before the update */
+			let cycles = 2 * timebase.divider() * period;
+			(*TIM8::ptr()).psc().write(|w| w.bits(0));
+			(*TIM8::ptr()).arr().write(|w| w.bits(cycles - 1));
+			(*TIM8::ptr()).ccr1().write(|w| w.bits(cycles - 1));
+			(*TIM8::ptr()).cnt().write(|w| w.bits(0));
+			(*TIM8::ptr()).dier().write(|w| w.bits(TIM_DIER_UDE | TIM_DIER_CC1DE));
+
+			timer_prescale(timebase.divider() - 1);
+			timer_set(0);
+			(*TIM8::ptr()).cr1().write(|w| w.bits(TIM_CR1_CEN));
+		}
+
+		/* Wait for the first sample */
+		while write_pos() == 0 {}
+
+		let prev = read_sample(0) & channels;
+		Capture
+		{
+			rd: 1,
+			base: 0,
+			period,
+			timer_last: 0,
+			timer_wraps: 0,
+			prev,
+			prev_blue: spread(prev as u8),
+			prev_yellow: spread((prev >> 8) as u8),
+			mask: channels,
+			mask_blue: spread(channels as u8),
+			mask_yellow: spread((channels >> 8) as u8),
+			overrun: false
+		}
+	}
+
+	pub fn stop(&mut self)
+	{
+		unsafe
+		{
+			(*TIM8::ptr()).cr1().write(|w| w.bits(0));
+			(*TIM8::ptr()).dier().write(|w| w.bits(0));
+			stream_stop(DMA2_S1CR);
+			stream_stop(DMA2_S2CR);
+		}
+
+		timer_prescale(0);
+	}
+
+	// Level of the last read sample
+	pub fn level(&self) -> Sample
+	{
+		self.prev
+	}
+
+	// Timestamp of the newest sample, after an overrun of the last valid one
+	pub fn now(&mut self) -> Timestamp
+	{
+		if self.overrun { self.ts(self.rd) } else { self.timer() }
+	}
+
+	// TIM2 extended to 64 bit, must be called at least once per timer lap (~47 s at 90 MHz)
+	fn timer(&mut self) -> Timestamp
+	{
+		let t = timer_get();
+		if t < self.timer_last
+		{
+			self.timer_wraps += 1;
+		}
+
+		self.timer_last = t;
+		((self.timer_wraps as Timestamp) << 32) | t as Timestamp
+	}
+
+	fn ts(&self, idx: usize) -> Timestamp
+	{
+		(self.base + idx as u64) * self.period as Timestamp
+	}
+
+	fn advance(&mut self, n: usize)
+	{
+		self.rd += n;
+		if self.rd == DMA_BUF_LEN
+		{
+			self.rd = 0;
+			self.base += DMA_BUF_LEN as u64;
+		}
+	}
+
+	// Next level change with its timestamp,
+	// None if all samples written so far are read or data was lost
+	pub fn poll(&mut self) -> Option<(Sample, Timestamp)>
+	{
+		if self.overrun
+		{
+			return None;
+		}
+
+		/* Signed, the next sample may be a few ticks in the future */
+		let lag = self.timer() as i64 - self.ts(self.rd) as i64;
+		if lag > OVERRUN_LAG * self.period as i64
+		{
+			self.overrun = true;
+			return None;
+		}
+
+		let wr = write_pos();
+		while self.rd != wr
+		{
+			let avail = (wr + DMA_BUF_LEN - self.rd) % DMA_BUF_LEN;
+			if self.rd & 3 == 0 && avail >= 4
+				&& read_word(dma_blue(), self.rd) & self.mask_blue == self.prev_blue
+				&& read_word(dma_yellow(), self.rd) & self.mask_yellow == self.prev_yellow
+			{
+				self.advance(4);
+				continue;
+			}
+
+			let port = read_sample(self.rd) & self.mask;
+			let ts = self.ts(self.rd);
+			self.advance(1);
+			if port != self.prev
+			{
+				self.prev = port;
+				self.prev_blue = spread(port as u8);
+				self.prev_yellow = spread((port >> 8) as u8);
+				return Some((port, ts));
+			}
+		}
+
+		None
+	}
+}
@@ -0,0 +1,64 @@
+use crate::sample::{Sample, SampleBuffer, Timestamp};
+use crate::vcd::{self, VcdWriter, CHANNEL_NAMES};
+
+/*
+ * Capture files on the host: the CSV written by the dumper and VCD.
+ * The simulator, decode and the tests read their samples with it.
+ */
+
+// Entries as written by the dumper: timestamp and the level of all channels
+pub fn read_csv(filename: &str, out: &mut impl FnMut(Timestamp, Sample)) -> Result<(), csv::Error>
+{
+	let mut rdr = csv::ReaderBuilder::new().has_headers(true).from_path(filename)?;
+
+	for result in rdr.records()
+	{
+		let (timestamp, data): (Timestamp, Sample) = result?.deserialize(None)?;
+		out(timestamp, data);
+	}
+
+	Ok(())
+}
+
+pub fn load_csv(filename: &str, buf: &mut SampleBuffer) -> Result<(), csv::Error>
+{
+	read_csv(filename, &mut |ts, data| buf.push(data, ts))
+}
+
+// Timestamps in the timebase of the buffer
+pub fn load_vcd(filename: &str, buf: &mut SampleBuffer) -> std::io::Result<()>
+{
+	let text = std::fs::read_to_string(filename)?;
+	let clock_rate = buf.timebase.clock_rate();
+	vcd::read(&text, clock_rate, &mut |ts, data| buf.push(data, ts))
+		.map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidData, format!("{e:?}")))?;
+
+	Ok(())
+}
+
+// VCD or CSV by the file extension
+pub fn load(filename: &str, buf: &mut SampleBuffer) -> Result<(), Box<dyn std::error::Error>>
+{
+	if filename.ends_with(".vcd")
+	{
+		load_vcd(filename, buf)?;
+	}
+	else
+	{
+		load_csv(filename, buf)?;
+	}
+
+	Ok(())
+}
+
+pub fn write_vcd(buf: &SampleBuffer, out: &mut impl std::fmt::Write) -> std::fmt::Result
+{
+	let mut w = VcdWriter::new(buf.timebase.clock_rate(), buf.channels);
+	w.header(out, &CHANNEL_NAMES)?;
+	for (ts, data) in buf
+	{
+		w.sample(out, ts, data)?;
+	}
+
+	Ok(())
+}
@@ -2,7 +2,7 @@ use crate::durationindicator::DurationIndicator;
 use crate::lcd::{lcd_color, lcd_vline};
 use crate::gui::Action;
 use crate::waveform::{WaveformBuffer, WAVEFORM_SPACING, CHANNEL_LABEL_WIDTH, WAVEFORMS_Y, WAVEFORM_W};
-use crate::sample::SampleBuffer;
+use crate::sample::{SampleBuffer, Timestamp};
 
 const H: u32 = 8 * WAVEFORM_SPACING;
 const COLOR_DEF: u16 = lcd_color(192, 192, 192);
@@ -14,10 +14,11 @@ pub struct Cursors
 {
 	durationindicator: DurationIndicator,
 	x: [u32; 2],
-	ts: [u32; 2],
+	ts: [Timestamp; 2],
 	pub en: bool,
-	t_start: u32,
-	t_end: u32
+	t_start: Timestamp,
+	t_end: Timestamp,
+	ticks_per_us: u32
 }
 
 impl Cursors
@@ -31,7 +32,8 @@ impl Cursors
 			ts: [ 0, 0 ],
 			en: false,
 			t_start: 0,
-			t_end: 0
+			t_end: 0,
+			ticks_per_us: 1
 		}
 	}
 
@@ -44,9 +46,9 @@ impl Cursors
 	{
 		let t = self.t_end - self.t_start;
 		let t0 =
-			if self.ts[0] == u32::MAX
+			if self.ts[0] == Timestamp::MAX
 			{
-				((self.x[0] as f64) / (WAVEFORM_W as f64) * (t as f64)) as u32
+				((self.x[0] as f64) / (WAVEFORM_W as f64) * (t as f64)) as Timestamp
 			}
 			else
 			{
@@ -55,9 +57,9 @@ impl Cursors
 
 
 		let t1 =
-			if self.ts[1] == u32::MAX
+			if self.ts[1] == Timestamp::MAX
 			{
-				((self.x[1] as f64) / (WAVEFORM_W as f64) * (t as f64)) as u32
+				((self.x[1] as f64) / (WAVEFORM_W as f64) * (t as f64)) as Timestamp
 			}
 			else
 			{
@@ -65,7 +67,7 @@ impl Cursors
 			};
 
 		let dt = t1.abs_diff(t0);
-		self.durationindicator.show(dt);
+		self.durationindicator.show(dt, self.ticks_per_us);
 	}
 
 	fn render(&mut self, x: [u32; 2], wf: &WaveformBuffer)
@@ -111,9 +113,9 @@ impl Cursors
 		self.render_duration();
 	}
 
-	fn get_t(&self) -> u32
+	fn get_t(&self) -> Timestamp
 	{
-		if self.ts[0] != u32::MAX
+		if self.ts[0] != Timestamp::MAX
 		{
 			return self.ts[0];
 		}
@@ -121,10 +123,10 @@ impl Cursors
 		let x0 = self.x[0];
 		let t = self.t_end - self.t_start;
 		let t0 = (x0 as f64) / (WAVEFORM_W as f64) * (t as f64);
-		(t0 as u32) + self.t_start
+		(t0 as Timestamp) + self.t_start
 	}
 
-	fn to_x(&self, t: u32) -> u32
+	fn to_x(&self, t: Timestamp) -> u32
 	{
 		if t <= self.t_start
 		{
@@ -147,11 +149,11 @@ impl Cursors
 		((t_off / (t_whole as f64)) * ((WAVEFORM_W - 1) as f64)) as u32
 	}
 
-	fn set_t(&mut self, nt: u32, new_x: &mut [u32; 2])
+	fn set_t(&mut self, nt: Timestamp, new_x: &mut [u32; 2])
 	{
 		if nt < self.t_start || nt > self.t_end
 		{
-			self.ts[0] = u32::MAX;
+			self.ts[0] = Timestamp::MAX;
 			return;
 		}
 
@@ -168,35 +170,35 @@ impl Cursors
 			{
 				let t = self.get_t();
 				let idx = buf.find_prev(t);
-				let nt = buf.timestamps[idx];
+				let nt = buf.timestamp(idx);
 				self.set_t(nt, &mut new_x);
 			}
 			Action::NextEdge =>
 			{
 				let t = self.get_t();
 				let idx = buf.find_next(t);
-				let nt = buf.timestamps[idx];
+				let nt = buf.timestamp(idx);
 				self.set_t(nt, &mut new_x);
 			}
 			Action::LeftFast =>
 			{
 				limit_dec_by!(new_x[0], LARGE_MOVE);
-				self.ts[0] = u32::MAX;
+				self.ts[0] = Timestamp::MAX;
 			}
 			Action::RightFast =>
 			{
 				limit_inc_by!(new_x[0], LARGE_MOVE, WAVEFORM_W - 1);
-				self.ts[0] = u32::MAX;
+				self.ts[0] = Timestamp::MAX;
 			}
 			Action::Left =>
 			{
 				limit_dec_by!(new_x[0], 1);
-				self.ts[0] = u32::MAX;
+				self.ts[0] = Timestamp::MAX;
 			}
 			Action::Right =>
 			{
 				limit_inc_by!(new_x[0], 1, WAVEFORM_W - 1);
-				self.ts[0] = u32::MAX;
+				self.ts[0] = Timestamp::MAX;
 			}
 			Action::Escape =>
 			{
@@ -226,7 +228,7 @@ impl Cursors
 		}
 	}
 
-	pub fn show(&mut self, t_start: u32, t_end: u32)
+	pub fn show(&mut self, t_start: Timestamp, t_end: Timestamp, ticks_per_us: u32)
 	{
 		if !self.en
 		{
@@ -235,9 +237,10 @@ impl Cursors
 
 		self.t_start = t_start;
 		self.t_end = t_end;
+		self.ticks_per_us = ticks_per_us;
 
-		self.ts[0] = u32::MAX;
-		self.ts[1] = u32::MAX;
+		self.ts[0] = Timestamp::MAX;
+		self.ts[1] = Timestamp::MAX;
 
 		self.x[0] = WAVEFORM_W / 4;
 		self.x[1] = WAVEFORM_W / 4 * 3;
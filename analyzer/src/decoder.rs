@@ -1,13 +1,18 @@
-use crate::sample::SampleBuffer;
+use core::fmt;
+use crate::arena::Arena;
+use crate::sample::{SampleBuffer, Timestamp, CHANNELS};
 use crate::decoder_onewire::rom_cmd::ROMCmd;
 
 pub type DecoderPin = u32;
 
+// Capacity of the section buffer on the host
+#[cfg(any(test, feature = "std"))]
 pub const SECBUF_SIZE: usize = 1000;
+// Clock of TIM2 without prescaler, timestamps use the Timebase of the buffer
 pub const TIMER_CLOCK_RATE: u32 = 90_000_000;
-pub const TIMER_TICKS_PER_US: u32 = TIMER_CLOCK_RATE / 1_000_000;
 
-// GUI is responsible for choosing representation, colors, etc.
+// GUI is responsible for choosing representation, colors, etc.,
+// Display gives the label text shared by the screen and the exports
 #[derive(Copy, Clone, Default, Debug, PartialEq)]
 pub enum SectionContent
 {
@@ -37,16 +42,95 @@ pub enum SectionContent
 	FamilyCode(u8),
 	SensorID(u64),
 	ROMCmd(ROMCmd),
+	ChipSelect(bool),
+	TxWord(u32),
+	RxWord(u32),
+	Transaction(u16),
+	WordGap,
+}
+
+impl fmt::Display for SectionContent
+{
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result
+	{
+		match *self
+		{
+			SectionContent::Byte(v) | SectionContent::TxByte(v) | SectionContent::RxByte(v) =>
+				write_byte(f, v.into()),
+			SectionContent::Data(v) => write_byte(f, v),
+			SectionContent::Empty => write!(f, "Empty"),
+			SectionContent::Bit(v) => write!(f, "{}", if v { 1 } else { 0 }),
+			SectionContent::StartBit => write!(f, "Start"),
+			SectionContent::StopBit => write!(f, "Stop"),
+			SectionContent::I2cAddress(v) => write!(f, "Addr: {:X}", v),
+			SectionContent::Err(v) => write!(f, "{}", v),
+			SectionContent::ParityBit(v) => write!(f, "{}", v),
+			SectionContent::RepeatedStart => write!(f, "RS"),
+			SectionContent::Ack => write!(f, "ACK"),
+			SectionContent::Nak => write!(f, "NAK"),
+			SectionContent::I2cWrite => write!(f, "W"),
+			SectionContent::I2cRead => write!(f, "R"),
+			SectionContent::Reset => write!(f, "Reset"),
+			SectionContent::ResetResponse(v) => write!(f, "{}", if v { "Response" } else { "No Response" }),
+			SectionContent::ResetRecovery => write!(f, "Reset Recovery"),
+			SectionContent::ROMCmd(v) => write!(f, "{}", v.to_string()),
+			SectionContent::FamilyCode(v) | SectionContent::CRC(v) | SectionContent::FunctionCmd(v) =>
+				write!(f, "${:02X}", v),
+			SectionContent::SensorID(v) => write!(f, "${:02X}", v),
+			SectionContent::ChipSelect(v) => write!(f, "{}", if v { "CS" } else { "CS end" }),
+			SectionContent::TxWord(v) | SectionContent::RxWord(v) => write!(f, "${:X}", v),
+			SectionContent::Transaction(v) => write!(f, "{} words", v),
+			SectionContent::WordGap => write!(f, "Gap")
+		}
+	}
+}
+
+// Hex value, printable characters also as text
+fn write_byte(f: &mut fmt::Formatter, byte: u64) -> fmt::Result
+{
+	if (32..=126).contains(&byte)
+	{
+		write!(f, "${:02X} {}", byte, (byte as u8) as char)
+	}
+	else
+	{
+		write!(f, "${:02X}", byte)
+	}
+}
+
+impl SectionContent
+{
+	// Annotation row like the two lines on the screen, transmitted bytes on their own
+	pub fn row(&self) -> &'static str
+	{
+		match self
+		{
+			SectionContent::Bit(_) => "bits",
+			SectionContent::TxByte(_) | SectionContent::TxWord(_) => "tx",
+			SectionContent::Transaction(_) => "transactions",
+			SectionContent::WordGap => "gaps",
+			_ => "data"
+		}
+	}
+
+	// A decoded data word, counted by the frame stop condition,
+	// SPI gives an Rx and a Tx section for each word
+	pub fn is_frame(&self) -> bool
+	{
+		matches!(self,
+			SectionContent::Byte(_) | SectionContent::TxByte(_) | SectionContent::RxByte(_) |
+			SectionContent::TxWord(_) | SectionContent::RxWord(_) | SectionContent::Data(_) | SectionContent::FunctionCmd(_) | SectionContent::ROMCmd(_))
+	}
 }
 
 #[derive(Default, Clone, Copy, Debug)]
 pub struct Section
 {
 	// Which time the section starts on
-	pub start: u32,
+	pub start: Timestamp,
 
 	// Which time the section ends on
-	pub end: u32,
+	pub end: Timestamp,
 
 	// Arbitrary Content
 	pub content: SectionContent
@@ -54,12 +138,28 @@ pub struct Section
 
 pub struct SectionBuffer
 {
-	pub sections: [Section; SECBUF_SIZE],
+	pub sections: &'static mut [Section],
 	pub len: usize
 }
 
 impl SectionBuffer
 {
+	pub fn alloc(mem: &mut Arena, len: usize) -> Option<Self>
+	{
+		Some(SectionBuffer
+		{
+			sections: mem.alloc(len, Section::default())?,
+			len: 0
+		})
+	}
+
+	#[cfg(any(test, feature = "std"))]
+	pub fn new() -> Self
+	{
+		let mut mem = Arena::leak((SECBUF_SIZE + 1) * core::mem::size_of::<Section>());
+		Self::alloc(&mut mem, SECBUF_SIZE).unwrap()
+	}
+
 	pub fn clear(&mut self)
 	{
 		self.len = 0;
@@ -76,11 +176,31 @@ impl SectionBuffer
 		self.len += 1;
 	}
 
+	// Push a section that is filled in later, e.g. one that ends after the
+	// sections pushed next, gives its index or None if the buffer is full
+	pub fn reserve(&mut self, section: Section) -> Option<usize>
+	{
+		if self.is_full()
+		{
+			return None;
+		}
+
+		self.push(section);
+		Some(self.len - 1)
+	}
+
 	pub fn is_full(&self) -> bool
 	{
 		self.len >= self.sections.len()
 	}
 
+	// The decoder found invalid data
+	pub fn has_errors(&self) -> bool
+	{
+		self.sections[..self.len].iter()
+			.any(|s| matches!(s.content, SectionContent::Err(_)))
+	}
+
 	pub fn iter(&self) -> SectionBufferIter
 	{
 		SectionBufferIter {
@@ -91,7 +211,7 @@ impl SectionBuffer
 
 	// start: Timstamp of window start
 	// Returns sample index
-	pub fn find_view(&self, start: u32, end: u32) -> (usize, usize)
+	pub fn find_view(&self, start: Timestamp, end: Timestamp) -> (usize, usize)
 	{
 		let mut first = None;
 		let mut last = 0;
@@ -113,6 +233,7 @@ impl SectionBuffer
 	}
 }
 
+#[derive(Clone)]
 pub struct SectionBufferIter<'a>
 {
 	buffer: &'a SectionBuffer,
@@ -149,25 +270,54 @@ pub trait Decoder
 	fn get_pin(&self, idx: usize) -> Option<(&'static str, DecoderPin)>;
 }
 
-pub fn pin_duplicates(arr: &[DecoderPin]) -> bool
+// The configured decoder, as stored in the flash and read by the host tools
+#[derive(serde::Serialize, serde::Deserialize)]
+pub enum DecoderUnion
 {
-	let len = arr.len();
-	if len < 2
+	None,
+	Uart(crate::decoder_uart::DecoderUart),
+	SPI(crate::decoder_spi::DecoderSPI),
+	I2C(crate::decoder_i2c::DecoderI2C),
+	OneWire(crate::decoder_onewire::DecoderOneWire),
+	QSPI(crate::decoder_qspi::DecoderQSPI),
+}
+
+impl DecoderUnion
+{
+	pub fn decoder(&self) -> Option<&dyn Decoder>
 	{
-		return false;
+		match self
+		{
+			DecoderUnion::None => None,
+			DecoderUnion::Uart(dcd) => Some(dcd),
+			DecoderUnion::SPI(dcd) => Some(dcd),
+			DecoderUnion::I2C(dcd) => Some(dcd),
+			DecoderUnion::OneWire(dcd) => Some(dcd),
+			DecoderUnion::QSPI(dcd) => Some(dcd)
+		}
 	}
+}
 
-	let mut seen = [false; 16];
+// Every pin is one of the channels and used only once, settings from the
+// host or the flash may contain anything
+pub fn pins_valid(arr: &[DecoderPin]) -> bool
+{
+	let mut seen = [false; CHANNELS as usize];
 	for &item in arr.iter()
 	{
+		if item >= CHANNELS
+		{
+			return false;
+		}
+
 		let index = item as usize;
 		if seen[index]
 		{
-			return true;
+			return false;
 		}
 
 		seen[index] = true;
 	}
 
-	false
+	true
 }
@@ -5,6 +5,7 @@ use crate::{font::{Font, remap_char},
 		LCD_BLACK, LCD_WHITE, LCD_GREEN, LCD_RED, LCD_BLUE, LCD_YELLOW, LCD_ORANGE}};
 
 use crate::decoder::{SectionBuffer, SectionContent};
+use crate::sample::Timestamp;
 use crate::terminus16_bold::TERMINUS16_BOLD;
 use crate::bytewriter::ByteMutWriter;
 use crate::waveform::CHANNEL_LABEL_WIDTH;
@@ -194,18 +195,6 @@ pub struct DecoderFrameBuffer<const LEN: usize>
 	lines: [DecoderLine<LEN>; 2]
 }
 
-fn format_byte(buf: &mut ByteMutWriter, byte: u64)
-{
-	if (32..=126).contains(&byte)
-	{
-		write!(buf, " ${:02X} {}", byte, (byte as u8) as char).unwrap();
-	}
-	else
-	{
-		write!(buf, " ${:02X}", byte).unwrap();
-	}
-}
-
 impl<const LEN: usize> DecoderFrameBuffer<LEN>
 {
 	pub fn new() -> Self
@@ -234,7 +223,7 @@ impl<const LEN: usize> DecoderFrameBuffer<LEN>
 		self.lines[1].draw_buffer(CHANNEL_LABEL_WIDTH, 50);
 	}
 
-	pub fn render(&mut self, sec_buf: &SectionBuffer, t_start: u32, t_end: u32)
+	pub fn render(&mut self, sec_buf: &SectionBuffer, t_start: Timestamp, t_end: Timestamp)
 	{
 		self.clear_intenal();
 
@@ -244,9 +233,15 @@ impl<const LEN: usize> DecoderFrameBuffer<LEN>
 		{
 			let cur = sec_buf.sections[i];
 
+			/* Transactions and gaps would cover the words, they are only exported */
+			if matches!(cur.content, SectionContent::Transaction(_) | SectionContent::WordGap)
+			{
+				continue;
+			}
+
 			let x0 = t_to_x(cur.start, t_start, t_end);
 			let x1 = t_to_x(cur.end, t_start, t_end);
-			let w = x1 - x0;
+			let mut w = x1 - x0;
 
 			let mut text: [u8; 64] = [0; 64];
 			let mut buf = ByteMutWriter::new(&mut text);
@@ -257,116 +252,88 @@ impl<const LEN: usize> DecoderFrameBuffer<LEN>
 
 			match cur.content
 			{
-				SectionContent::TxByte(v) => {
+				SectionContent::TxByte(_) | SectionContent::TxWord(_) => {
 					fg = 1; /* Black */
 					bg = 3; /* Blue */
 					line = &mut self.lines[1];
-
-					format_byte(&mut buf, v.into());
 				},
-				SectionContent::RxByte(v) => { format_byte(&mut buf, v.into()) },
-				SectionContent::Byte(v) => { format_byte(&mut buf, v.into()) },
-				SectionContent::Empty     => write!(buf, " Empty").unwrap(),
-				SectionContent::Bit(v)    => {
+				SectionContent::Bit(_)    => {
 					line = &mut self.lines[1];
-					write!(buf, " {}", if v { 1 } else { 0 }).unwrap()
 				},
 				SectionContent::StartBit  => {
 					fg = 1; /* Black */
 					bg = 5; /* Green */
-					write!(buf, " Start").unwrap()
 				},
 				SectionContent::StopBit   => {
 					fg = 1; /* Black */
 					bg = 6; /* Orange */
-					write!(buf, " Stop").unwrap()
 				},
-				SectionContent::I2cAddress(v) => {
+				SectionContent::I2cAddress(_) => {
 					fg = 1; /* Black */
 					bg = 4; /* Yellow */
-					write!(buf, " Addr: {:X}", v).unwrap()
 				},
-				SectionContent::Err(v) => write!(buf, " {}", v).unwrap(),
-				SectionContent::ParityBit(v) => write!(buf, " {}", v).unwrap(),
-				SectionContent::Data(v) => format_byte(&mut buf, v.into()),
 				SectionContent::RepeatedStart => {
 					fg = 1; /* Black */
 					bg = 5; /* Green */
-					write!(buf, " RS").unwrap()
 				},
 				SectionContent::Ack => {
 					fg = 1; /* Black */
 					bg = 5; /* Green */
-					write!(buf, " ACK").unwrap()
 				},
 				SectionContent::Nak => {
 					fg = 1; /* Black */
 					bg = 2; /* Red */
-					write!(buf, " NAK").unwrap()
 				},
 				SectionContent::I2cWrite => {
 					fg = 1; /* Black */
 					bg = 1; /* White */
-					write!(buf, " W").unwrap()
 				},
 				SectionContent::I2cRead => {
 					fg = 1; /* Black */
 					bg = 1; /* White */
-					write!(buf, " R").unwrap()
 				},
 				SectionContent::Reset => {
 					fg = 0; /* Black */
 					bg = 1; /* White */
-					write!(buf, " Reset").unwrap()
 				}
 				SectionContent::ResetResponse(v) => {
-					fg = 5; /* Green */
+					fg = if v { 5 /* Green */ } else { 2 /* Red */ };
 					bg = 1; /* White */
-					let mut response = "Response";
-
-					if !v {
-						fg = 2; /* Red */
-						response = "No Response";
-					}
-					write!(buf, " {}", response).unwrap()
 				}
 				SectionContent::ResetRecovery => {
 					fg = 0; /* Black */
 					bg = 1; /* White */
-					write!(buf, " Reset Recovery").unwrap()
 				},
-				SectionContent::ROMCmd(v) => {
+				SectionContent::ROMCmd(_) => {
 					fg = 1; /* Black */
 					bg = 3; /* Blue */
-
-					write!(buf, " {}", v.to_string()).unwrap()
 				},
-				SectionContent::FamilyCode(v) => {
+				SectionContent::FamilyCode(_) => {
 					fg = 1; /* Black */
 					bg = 6; /* Orange */
-
-					write!(buf, " ${:02X}", v).unwrap();
 				},
-				SectionContent::SensorID(v ) => {
+				SectionContent::SensorID(_) => {
 					fg = 1; /* Black */
 					bg = 6; /* Orange */
-
-					write!(buf, " ${:02X}", v).unwrap();
 				}
-				SectionContent::CRC(v) => {
+				SectionContent::CRC(_) => {
 					fg = 1; /* Black */
 					bg = 5; /* Green */
-
-					write!(buf, " ${:02X}", v).unwrap();
 				}
-				SectionContent::FunctionCmd(v) => {
+				SectionContent::FunctionCmd(_) => {
 					fg = 1; /* Black */
 					bg = 3; /* Blue */
-
-					write!(buf, " ${:02X}", v).unwrap();
 				}
+				SectionContent::ChipSelect(_) => {
+					/* Only a point in time, keep it visible */
+					w = w.max(1);
+					bg = 4; /* Yellow */
+				}
+				_ => {}
 			};
 
+			write!(buf, " {}", cur.content).unwrap();
+
 			let font = &TERMINUS16_BOLD;
 			let font_width = font.width + 1;
 			let font_height = font.height;
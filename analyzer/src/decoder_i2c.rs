@@ -1,4 +1,4 @@
-use crate::decoder::{SectionContent, Section, Decoder, DecoderPin, SectionBuffer};
+use crate::decoder::{SectionContent, Section, Decoder, DecoderPin, SectionBuffer, pins_valid};
 use crate::sample::SampleBuffer;
 
 #[derive(serde::Serialize, serde::Deserialize)]
@@ -61,7 +61,7 @@ impl Decoder for DecoderI2C
 		{
 			let scl: bool = samples.samples[i] & (1 << self.get_pin(1).unwrap().1 as u8) > 0;
 			let sda: bool = samples.samples[i] & (1 << self.get_pin(0).unwrap().1 as u8) > 0;
-			let ts = samples.timestamps[i];
+			let ts = samples.timestamp(i);
 
 			let sda_edge: Edge =  Edge::get_edge(last_sda, sda);
 			let scl_edge: Edge =  Edge::get_edge(last_scl, scl);
@@ -148,7 +148,7 @@ impl Decoder for DecoderI2C
 
 	fn is_valid(&self) -> bool
 	{
-		self.sda_pin != self.scl_pin
+		pins_valid(&[self.sda_pin, self.scl_pin])
 	}
 
 	fn get_pin(&self, idx: usize) -> Option<(&'static str, DecoderPin)>
@@ -4,14 +4,13 @@ mod onewire_iter;
 mod onewire_output;
 pub mod rom_cmd;
 
-use timings::Timings;
 
 use crate::bit_reader::BitReader;
-use crate::decoder::{SectionBuffer, SectionContent, Section, Decoder, DecoderPin};
+use crate::decoder::{SectionBuffer, SectionContent, Section, Decoder, DecoderPin, pins_valid};
 use crate::decoder_onewire::onewire_output::OneWireOutput;
 use crate::decoder_onewire::onewire_iter::OnewireIter;
 use crate::decoder_onewire::rom_cmd::ROMCmd;
-use crate::sample::SampleBuffer;
+use crate::sample::{SampleBuffer, Timestamp};
 use onewire_error::OneWireError;
 
 const NO_SLAVE_ON_BRANCH: u8 = 0b11;
@@ -65,7 +64,7 @@ impl BitProcessor {
 		iter: &mut OnewireIter,
 		output: &mut OneWireOutput,
 		reader: &mut BitReader,
-	) -> (u32, Result<(), OneWireError>) 
+	) -> (Timestamp, Result<(), OneWireError>) 
 	{
 		let mut end_time = iter.current_time();
 
@@ -83,7 +82,7 @@ impl BitProcessor {
 		&self,
 		iter: &mut OnewireIter, 
 		output: &mut OneWireOutput
-	) -> Option<(u32, Result<bool, OneWireError>)>
+	) -> Option<(Timestamp, Result<bool, OneWireError>)>
 	{
 		let start = iter.current_time();
 		let (end, result) = iter.next_bit()?;
@@ -105,7 +104,7 @@ impl BitProcessor {
 		&self,
 		iter: &mut OnewireIter,
 		output: &mut OneWireOutput,
-	) -> Option<(u32, Result<bool, OneWireError>)>
+	) -> Option<(Timestamp, Result<bool, OneWireError>)>
 	{
 		let mut slave_reader = BitReader::lsb(2);
 		let (end, result) = BitProcessor::default().read_bits(iter, output, &mut slave_reader);
@@ -133,7 +132,7 @@ impl BitProcessor {
 		&self,
 		iter: &mut OnewireIter, 
 		output: &mut OneWireOutput
-	) -> Option<(u32, Result<bool, OneWireError>)> 
+	) -> Option<(Timestamp, Result<bool, OneWireError>)> 
 	{
 		match self.mode {
 			ProcessorMode::Default => self.read_bit_default(iter, output),
@@ -169,11 +168,11 @@ impl OneWireState {
 			ROMCmd::SearchROM => OneWireState::FamilyCode(FamilyCodeState { processor: BitProcessor { mode: ProcessorMode::Search } }),
 			ROMCmd::SkipROM => OneWireState::FunctionCmd(FunctionCmdState),
 			ROMCmd::OverdriveSkipROM => {
-				iter.set_timing(Timings::overdrive());
+				iter.set_overdrive();
 				OneWireState::FunctionCmd(FunctionCmdState)
 			},
 			ROMCmd::OverdriveMatchROM => {
-				iter.set_timing(Timings::overdrive());
+				iter.set_overdrive();
 				OneWireState::FamilyCode(FamilyCodeState::default())
 			},
 			_ => OneWireState::FamilyCode(FamilyCodeState::default()),
@@ -186,7 +185,7 @@ struct ResetState;
 
 impl ResetState {
 	pub fn process(&self, iter: &mut OnewireIter, output: &mut OneWireOutput) -> Option<OneWireState> {
-		iter.set_timing(Timings::standard());
+		iter.set_standard();
 
 		let start = iter.current_time();
 		let reset = iter.next_reset()?;
@@ -398,7 +397,7 @@ impl Decoder for DecoderOneWire {
 
 	fn is_valid(&self) -> bool
 	{
-		true
+		pins_valid(&[self.onewire_pin])
 	}
 
 	fn get_pin(&self, idx: usize) -> Option<(&'static str, DecoderPin)>
@@ -1,16 +1,16 @@
 use crate::decoder_onewire::onewire_error::OneWireError;
 use crate::decoder_onewire::timings::Timings;
-use crate::sample::{Edge, EdgeWiseIterator};
+use crate::sample::{Edge, EdgeWiseIterator, Timestamp};
 
 pub struct OnewireIter<'a> {
 	iter: EdgeWiseIterator<'a>,
 	last_idx: usize,
-	timing: Timings<u32>,
+	timing: Timings<Timestamp>,
 }
 
 impl <'a>OnewireIter<'a> {
 
-	fn check_bit_timings(&self, duration: u32) -> Result<(), OneWireError> {
+	fn check_bit_timings(&self, duration: Timestamp) -> Result<(), OneWireError> {
 		match duration {
 			d if d < self.timing.wr_init.min => Err(OneWireError::BitInitTooShort),
 			d if d > self.timing.wr_slot.max && d >= self.timing.reset.min => {
@@ -26,7 +26,7 @@ impl <'a>OnewireIter<'a> {
 		}
 	}
 
-	pub fn next_bit(&mut self) -> Option<(u32, Result<bool, OneWireError>)> {
+	pub fn next_bit(&mut self) -> Option<(Timestamp, Result<bool, OneWireError>)> {
 		self.last_idx = self.iter.current_index();
 
 		let start_time = self.current_time();
@@ -93,7 +93,7 @@ impl <'a>OnewireIter<'a> {
 		Some(Ok(()))
 	}
 
-	pub fn next_response(&mut self) -> Option<(u32, u32, Result<bool, OneWireError>)> {
+	pub fn next_response(&mut self) -> Option<(Timestamp, Timestamp, Result<bool, OneWireError>)> {
 		self.last_idx = self.iter.current_index();
 
 		let mut start_time = self.current_time();
@@ -132,7 +132,7 @@ impl <'a>OnewireIter<'a> {
 		Some((start_time, end_time, Ok(device_responded)))
 	}
 
-	pub fn next_reset_recovery(&mut self,  response_start: u32) -> Option<Result<u32, OneWireError>> {
+	pub fn next_reset_recovery(&mut self,  response_start: Timestamp) -> Option<Result<Timestamp, OneWireError>> {
 		if (self.current_time() - response_start) < self.timing.reset_recover_min {
 			return Some(Err(OneWireError::ResetRecoveryTooShort))
 		}
@@ -160,12 +160,16 @@ impl <'a>OnewireIter<'a> {
 		self.iter.set_index(self.last_idx).unwrap();
 	}
 
-	pub fn current_time(&self) -> u32 {
+	pub fn current_time(&self) -> Timestamp {
 		self.iter.current_time()
 	}
 
-	pub fn set_timing(&mut self, timing: Timings<u32>) {
-		self.timing = timing;
+	pub fn set_standard(&mut self) {
+		self.timing = Timings::standard(self.iter.timebase().ticks_per_us());
+	}
+
+	pub fn set_overdrive(&mut self) {
+		self.timing = Timings::overdrive(self.iter.timebase().ticks_per_us());
 	}
 }
 
@@ -173,7 +177,7 @@ impl <'a>From<EdgeWiseIterator<'a>> for OnewireIter<'a> {
 	fn from(iter: EdgeWiseIterator<'a>) -> Self {
 		OnewireIter {
 			last_idx: iter.current_index(),
-			timing: Timings::standard(),
+			timing: Timings::standard(iter.timebase().ticks_per_us()),
 			iter,
 		}
 	}
@@ -1,7 +1,7 @@
 use crate::decoder::{Section, SectionBuffer, SectionContent};
 use crate::decoder_onewire::onewire_error::OneWireError;
 use crate::decoder_onewire::onewire_iter::OnewireIter;
-use crate::decoder_onewire::timings::Timings;
+use crate::sample::Timestamp;
 
 pub struct OneWireOutput<'a> {
 	output: &'a mut SectionBuffer
@@ -14,8 +14,8 @@ impl <'a>OneWireOutput<'a> {
 		Some(())
 	}
 
-	pub fn push_err(&mut self, iter: &mut OnewireIter, start_time: u32, err: OneWireError) -> Option<()> {
-		iter.set_timing(Timings::standard());
+	pub fn push_err(&mut self, iter: &mut OnewireIter, start_time: Timestamp, err: OneWireError) -> Option<()> {
+		iter.set_standard();
 		iter.forward_to_reset()?;
 
 		self.push(Section {
@@ -27,6 +27,18 @@ impl TryFrom<u8> for ROMCmd {
 }
 
 impl ROMCmd {
+	// Command byte on the bus
+	pub fn code(self) -> u8 {
+		match self {
+			ROMCmd::ReadROM => 0x33,
+			ROMCmd::SkipROM => 0xCC,
+			ROMCmd::MatchROM => 0x55,
+			ROMCmd::SearchROM => 0xF0,
+			ROMCmd::OverdriveSkipROM => 0x3C,
+			ROMCmd::OverdriveMatchROM => 0x69,
+		}
+	}
+
 	pub fn to_string(&self) -> &'static str {
 		match self {
 			ROMCmd::ReadROM => "Read ROM",
@@ -1,7 +1,7 @@
 use core::ops::{Add, Mul};
 use libm::ceilf;
 
-use crate::decoder::TIMER_TICKS_PER_US;
+use crate::sample::Timestamp;
 
 #[derive(Copy, Clone)]
 pub struct Range<T>
@@ -27,10 +27,10 @@ where
 }
 
 impl Range<f32> {
-	fn as_u32(&self) -> Range<u32> {
+	fn as_ticks(&self) -> Range<Timestamp> {
 		Range {
-			min: self.min as u32,
-			max: ceilf(self.max) as u32,
+			min: self.min as Timestamp,
+			max: ceilf(self.max) as Timestamp,
 		}
 	}
 }
@@ -72,8 +72,8 @@ where
 	}
 }
 
-impl Timings<u32> {
-	pub fn standard() -> Self {
+impl Timings<Timestamp> {
+	pub fn standard(ticks_per_us: u32) -> Self {
 		Timings {
 			wr_init: Range { min: 5, max: 15 },
 			wr_slot: Range { min: 27, max: 120 },
@@ -82,10 +82,10 @@ impl Timings<u32> {
 			reset: Range { min: 480, max: 640 },
 			reset_recover_min: 473,
 		}
-		.scale(TIMER_TICKS_PER_US)
+		.scale(ticks_per_us as Timestamp)
 	}
 
-	pub fn overdrive() -> Self {
+	pub fn overdrive(ticks_per_us: u32) -> Self {
 		Timings {
 			wr_init: Range { min: 1.0, max: 1.85},
 			wr_slot: Range { min: 7.0, max: 14.0 },
@@ -94,20 +94,20 @@ impl Timings<u32> {
 			reset: Range { min: 68.0, max: 80.0 },
 			reset_recover_min: 46.7,
 		}
-		.scale(TIMER_TICKS_PER_US as f32)
-		.as_u32()
+		.scale(ticks_per_us as f32)
+		.as_ticks()
 	}
 }
 
 impl Timings<f32> {
-	fn as_u32(&self) -> Timings<u32> {
+	fn as_ticks(&self) -> Timings<Timestamp> {
 		Timings {
-			wr_init: self.wr_init.as_u32(),
-			wr_slot: self.wr_slot.as_u32(),
-			line_recover_min: self.line_recover_min as u32,
-			reset: self.reset.as_u32(),
-			response: self.response.as_u32(),
-			reset_recover_min: self.reset_recover_min as u32,
+			wr_init: self.wr_init.as_ticks(),
+			wr_slot: self.wr_slot.as_ticks(),
+			line_recover_min: self.line_recover_min as Timestamp,
+			reset: self.reset.as_ticks(),
+			response: self.response.as_ticks(),
+			reset_recover_min: self.reset_recover_min as Timestamp,
 		}
 	}
 }
\ No newline at end of file
@@ -0,0 +1,304 @@
+use crate::decoder::{DecoderPin, Section, SectionContent, SectionBuffer, Decoder, pins_valid};
+use crate::decoder_spi::{begin_transaction, end_transaction, sample_level, CsPolarity};
+use crate::sample::{Sample, SampleBuffer};
+
+/*
+ * SPI with 1, 2 or 4 bidirectional data lines, as used by flash chips
+ * (dual and quad I/O) and sensors or displays with 3-wire SPI. The master
+ * sends a number of command bytes (command, address, dummy cycles), then
+ * the transaction switches to the data phase, which may use more lines
+ * and is read or written. Bytes are MSB first, the highest IO line
+ * carries the highest bit of each clock.
+ */
+
+#[derive(serde::Serialize, serde::Deserialize, PartialEq, Clone, Copy)]
+pub enum Lanes
+{
+	Single,
+	Dual,
+	Quad
+}
+
+impl Lanes
+{
+	pub fn count(self) -> usize
+	{
+		match self
+		{
+			Lanes::Single => 1,
+			Lanes::Dual => 2,
+			Lanes::Quad => 4
+		}
+	}
+}
+
+// Direction of the bytes after the phase switch
+#[derive(serde::Serialize, serde::Deserialize, PartialEq, Clone, Copy)]
+pub enum DataPhase
+{
+	Read,
+	Write
+}
+
+#[derive(serde::Serialize, serde::Deserialize)]
+pub struct DecoderQSPI
+{
+	// IO0 to IO3, IO0 is the data line of 3-wire SPI and MOSI of dual and quad
+	pub io_pins: [DecoderPin; 4],
+	pub sck_pin: DecoderPin,
+	pub cs_pin: DecoderPin,
+	pub cs_polarity: CsPolarity,
+	// 0-3, CPOL in bit 1 and CPHA in bit 0
+	pub mode: u8,
+	// Bytes sent before the phase switch, 0 starts with the data phase
+	pub command_bytes: u8,
+	pub command_lanes: Lanes,
+	pub data_lanes: Lanes,
+	pub data_phase: DataPhase
+}
+
+fn extract(pins: Sample, pin: DecoderPin) -> u8
+{
+	((pins >> pin) & 1) as u8
+}
+
+impl DecoderQSPI
+{
+	fn cs_active(&self, pins: Sample) -> bool
+	{
+		self.cs_polarity.is_active(extract(pins, self.cs_pin))
+	}
+
+	// IO lines used by any of the phases
+	fn used_lanes(&self) -> usize
+	{
+		self.command_lanes.count().max(self.data_lanes.count())
+	}
+}
+
+impl Decoder for DecoderQSPI
+{
+	fn decode(&self, samples: &SampleBuffer, output: &mut SectionBuffer) -> Result<(), ()>
+	{
+		let sample_level = sample_level(self.mode);
+		let mut data: u8 = 0;
+		let mut bitcount = 0;
+		let mut start_sample = 0;
+
+		/* Levels at the start of the capture are no edges */
+		let first = if samples.len > 0 { samples.samples[0] } else { 0 };
+		let mut oldsck = extract(first, self.sck_pin);
+		let mut selected = self.cs_active(first);
+
+		/* A transaction that was already running when the capture started is not complete */
+		let mut transaction = None;
+		let mut words: u16 = 0;
+
+		for (ts, pins) in samples
+		{
+			/* Each assert and deassert of CS starts over with the command phase */
+			let active = self.cs_active(pins);
+			if active != selected
+			{
+				selected = active;
+				if let (false, Some(slot)) = (active, transaction)
+				{
+					end_transaction(output, slot, ts, words);
+				}
+
+				output.push(Section { start: ts, end: ts, content: SectionContent::ChipSelect(active) });
+				transaction = if active { begin_transaction(output, ts) } else { None };
+				words = 0;
+				data = 0;
+				bitcount = 0;
+			}
+
+			let sck = extract(pins, self.sck_pin);
+
+			// Need any edge on SCK
+			if sck == oldsck { continue; }
+			oldsck = sck;
+
+			// Need the sampling edge while selected
+			if sck != sample_level || !selected { continue; }
+
+			if bitcount == 0
+			{
+				start_sample = ts;
+			}
+
+			let command = words < self.command_bytes as u16;
+			let lanes = if command { self.command_lanes } else { self.data_lanes }.count();
+			for pin in self.io_pins[..lanes].iter().rev()
+			{
+				data = (data << 1) | extract(pins, *pin);
+			}
+
+			bitcount += lanes;
+			if bitcount < 8 { continue; }
+
+			let content = if command || self.data_phase == DataPhase::Write
+			{
+				SectionContent::TxByte(data)
+			}
+			else
+			{
+				SectionContent::RxByte(data)
+			};
+			output.push(Section { start: start_sample, end: ts, content });
+
+			words = words.saturating_add(1);
+			data = 0;
+			bitcount = 0;
+		}
+
+		/* The section is already reserved, a transaction still running ends with the capture */
+		if let Some(slot) = transaction
+		{
+			end_transaction(output, slot, samples.timestamp(samples.len - 1), words);
+		}
+
+		Ok(())
+	}
+
+	fn is_valid(&self) -> bool
+	{
+		/* Unused IO lines may share a pin with anything */
+		let used = self.used_lanes();
+		let mut arr: [DecoderPin; 6] = [self.sck_pin, self.cs_pin, 0, 0, 0, 0];
+		arr[2..2 + used].copy_from_slice(&self.io_pins[..used]);
+
+		self.mode < 4 && pins_valid(&arr[..2 + used])
+	}
+
+	fn get_pin(&self, idx: usize) -> Option<(&'static str, DecoderPin)>
+	{
+		const NAMES: [&str; 4] = ["IO0", "IO1", "IO2", "IO3"];
+		let used = self.used_lanes();
+		match idx
+		{
+			i if i < used => Some((NAMES[i], self.io_pins[i])),
+			i if i == used => Some(("SCK", self.sck_pin)),
+			i if i == used + 1 => Some(("CS", self.cs_pin)),
+			_ => None,
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests
+{
+	use crate::decoder_qspi::{DataPhase, DecoderQSPI, Lanes};
+	use crate::decoder_spi::{transaction_words, CsPolarity};
+	use crate::decoder::{Decoder, SectionBuffer, SectionContent};
+	use crate::test_utils::decode_sections;
+
+	// Pins of the sample captures: IO0-IO3 on channels 0-3, SCK on 4, CS on 5.
+	// The flash reads send command, address and a dummy byte on IO0.
+	fn decoder(data_lanes: Lanes) -> DecoderQSPI
+	{
+		DecoderQSPI
+		{
+			io_pins: [0, 1, 2, 3],
+			sck_pin: 4,
+			cs_pin: 5,
+			cs_polarity: CsPolarity::ActiveLow,
+			mode: 0,
+			command_bytes: 5,
+			command_lanes: Lanes::Single,
+			data_lanes,
+			data_phase: DataPhase::Read
+		}
+	}
+
+	fn words(sections: &SectionBuffer) -> Vec<SectionContent>
+	{
+		sections.iter().filter(|s| s.content.is_frame()).map(|s| s.content).collect()
+	}
+
+	fn rx(sections: &SectionBuffer) -> Vec<u8>
+	{
+		sections.iter().filter_map(|s| match s.content
+		{
+			SectionContent::RxByte(b) => Some(b),
+			_ => None
+		}).collect()
+	}
+
+	#[test]
+	fn test_quad()
+	{
+		let sections = decode_sections("SPI/QSPI_Quad_Read.csv", decoder(Lanes::Quad));
+		assert_eq!(words(&sections)[..5], [
+			SectionContent::TxByte(0x6B), SectionContent::TxByte(0x00), SectionContent::TxByte(0x10),
+			SectionContent::TxByte(0x00), SectionContent::TxByte(0x00)
+		]);
+		assert_eq!(rx(&sections), b"Hallo");
+
+		/* Two clocks per byte in the data phase */
+		let bytes: Vec<_> = sections.iter().filter(|s| s.content.is_frame()).collect();
+		assert!(bytes[5].end - bytes[5].start < (bytes[0].end - bytes[0].start) / 3);
+
+		let transaction = sections.iter()
+			.find(|s| matches!(s.content, SectionContent::Transaction(_)))
+			.unwrap();
+		assert_eq!(transaction.content, SectionContent::Transaction(10));
+		assert_eq!(transaction_words(&sections, transaction).filter(|&(mosi, _)| !mosi).count(), 5);
+	}
+
+	#[test]
+	fn test_dual()
+	{
+		let sections = decode_sections("SPI/QSPI_Dual_Read.csv", decoder(Lanes::Dual));
+		assert_eq!(words(&sections)[0], SectionContent::TxByte(0x3B));
+		assert_eq!(rx(&sections), b"Welt!");
+
+		/* Read with the wrong number of lines */
+		let sections = decode_sections("SPI/QSPI_Dual_Read.csv", decoder(Lanes::Quad));
+		assert_ne!(rx(&sections), b"Welt!");
+	}
+
+	#[test]
+	fn test_three_wire()
+	{
+		let mut spi = decoder(Lanes::Single);
+		spi.mode = 3;
+		spi.command_bytes = 1;
+		let sections = decode_sections("SPI/SPI_3Wire_Read.csv", spi);
+		assert_eq!(words(&sections), [
+			SectionContent::TxByte(0x8F), SectionContent::RxByte(0x33),
+			SectionContent::TxByte(0xA8), SectionContent::RxByte(0x12), SectionContent::RxByte(0x34)
+		]);
+
+		/* Without a phase switch everything is written */
+		let mut spi = decoder(Lanes::Single);
+		spi.mode = 3;
+		spi.command_bytes = 0;
+		spi.data_phase = DataPhase::Write;
+		let sections = decode_sections("SPI/SPI_3Wire_Read.csv", spi);
+		assert!(rx(&sections).is_empty());
+		assert_eq!(words(&sections).len(), 5);
+	}
+
+	#[test]
+	fn test_valid()
+	{
+		/* IO2 and IO3 are free in dual mode */
+		let mut spi = decoder(Lanes::Dual);
+		spi.io_pins = [0, 1, 4, 4];
+		assert!(spi.is_valid());
+		assert_eq!(spi.get_pin(2), Some(("SCK", 4)));
+		assert_eq!(spi.get_pin(4), None);
+
+		spi.data_lanes = Lanes::Quad;
+		assert!(!spi.is_valid());
+
+		let mut spi = decoder(Lanes::Quad);
+		spi.mode = 4;
+		assert!(!spi.is_valid());
+
+		let mut spi = decoder(Lanes::Single);
+		spi.io_pins[0] = 16;
+		assert!(!spi.is_valid());
+	}
+}
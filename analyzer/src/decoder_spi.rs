@@ -1,7 +1,8 @@
-use crate::decoder::{DecoderPin, Section, SectionContent, SectionBuffer, Decoder, pin_duplicates};
-use crate::sample::{Sample, SampleBuffer};
+use core::fmt;
+use crate::decoder::{DecoderPin, Section, SectionContent, SectionBuffer, Decoder, pins_valid};
+use crate::sample::{Sample, SampleBuffer, Timestamp};
 
-#[derive(serde::Serialize, serde::Deserialize, PartialEq)]
+#[derive(serde::Serialize, serde::Deserialize, PartialEq, Clone, Copy)]
 pub enum BitOrder
 {
 	MsbFirst,
@@ -9,6 +10,13 @@ pub enum BitOrder
 }
 
 
+#[derive(serde::Serialize, serde::Deserialize, PartialEq, Clone, Copy)]
+pub enum CsPolarity
+{
+	ActiveLow,
+	ActiveHigh
+}
+
 #[derive(serde::Serialize, serde::Deserialize)]
 pub struct DecoderSPI
 {
@@ -16,44 +24,157 @@ pub struct DecoderSPI
 	pub miso_pin: DecoderPin,
 	pub sck_pin: DecoderPin,
 	pub cs_pin: DecoderPin,
+	pub cs_polarity: CsPolarity,
+	// 0-3, CPOL in bit 1 and CPHA in bit 0
 	pub mode: u8,
-	pub bitorder: BitOrder
+	pub bitorder: BitOrder,
+	// Bits per word, 4-32
+	pub word_size: u8
 }
 
+pub const WORD_SIZE_MIN: u8 = 4;
+pub const WORD_SIZE_MAX: u8 = 32;
+
 fn extract(pins: Sample, pin: DecoderPin) -> u8
 {
-	(pins >> pin) & 1
+	((pins >> pin) & 1) as u8
+}
+
+impl CsPolarity
+{
+	// The level of CS selects the device
+	pub(crate) fn is_active(self, cs: u8) -> bool
+	{
+		cs == match self
+		{
+			CsPolarity::ActiveLow => 0,
+			CsPolarity::ActiveHigh => 1
+		}
+	}
+}
+
+// SCK level after the edge the data is sampled on: CPHA=0 samples on the
+// leading edge, CPHA=1 on the trailing one, CPOL=1 inverts both
+pub(crate) fn sample_level(mode: u8) -> u8
+{
+	let cpol = (mode >> 1) & 1;
+	let cpha = mode & 1;
+	(cpol == cpha) as u8
+}
+
+impl DecoderSPI
+{
+	fn cs_active(&self, pins: Sample) -> bool
+	{
+		self.cs_polarity.is_active(extract(pins, self.cs_pin))
+	}
+}
+
+// The words of a transaction section as (MOSI, value) in the order they were decoded
+pub fn transaction_words<'a>(sections: &'a SectionBuffer, transaction: &Section) -> impl Iterator<Item = (bool, u32)> + Clone + 'a
+{
+	let (start, end) = (transaction.start, transaction.end);
+	sections.iter()
+		.filter(move |s| s.start >= start && s.end <= end)
+		.filter_map(|s| match s.content
+		{
+			SectionContent::TxByte(v) => Some((true, v as u32)),
+			SectionContent::RxByte(v) => Some((false, v as u32)),
+			SectionContent::TxWord(v) => Some((true, v)),
+			SectionContent::RxWord(v) => Some((false, v)),
+			_ => None
+		})
+}
+
+// Label of a transaction with its words in hex, MOSI before MISO,
+// as the exports write it: "2 words, MOSI: AB CD, MISO: 0 FF"
+pub fn write_transaction(f: &mut impl fmt::Write, words: impl Iterator<Item = (bool, u32)> + Clone) -> fmt::Result
+{
+	write!(f, "{} words", words.clone().filter(|&(mosi, _)| mosi).count())?;
+	for (name, mosi) in [("MOSI", true), ("MISO", false)]
+	{
+		write!(f, ", {}:", name)?;
+		for (_, v) in words.clone().filter(|&(m, _)| m == mosi)
+		{
+			write!(f, " {:X}", v)?;
+		}
+	}
+
+	Ok(())
+}
+
+// The transaction section is reserved at CS assert so it starts before its words,
+// CS deassert fills in its end and the number of words
+pub(crate) fn begin_transaction(output: &mut SectionBuffer, ts: Timestamp) -> Option<usize>
+{
+	output.reserve(Section { start: ts, end: ts, content: SectionContent::Transaction(0) })
+}
+
+pub(crate) fn end_transaction(output: &mut SectionBuffer, slot: usize, ts: Timestamp, words: u16)
+{
+	let section = &mut output.sections[slot];
+	section.end = ts;
+	section.content = SectionContent::Transaction(words);
 }
 
 impl Decoder for DecoderSPI
 {
 	fn decode(&self, samples: &SampleBuffer, output: &mut SectionBuffer) -> Result<(), ()>
 	{
-		let word_size = 8;
-		let mut mosidata = 0;
-		let mut misodata = 0;
+		let word_size = self.word_size as u32;
+		let sample_level = sample_level(self.mode);
+		let mut mosidata: u32 = 0;
+		let mut misodata: u32 = 0;
 		let mut bitcount = 0;
 		let mut start_sample = 0;
-		let mut oldsck = 0;
+
+		/* Levels at the start of the capture are no edges */
+		let first = if samples.len > 0 { samples.samples[0] } else { 0 };
+		let mut oldsck = extract(first, self.sck_pin);
+		let mut selected = self.cs_active(first);
+
+		/* A transaction that was already running when the capture started is not complete */
+		let mut transaction = None;
+		let mut words = 0;
+		let mut last_end = None;
 
 		for (ts, pins) in samples
 		{
+			/* Each assert and deassert of CS starts a new word */
+			let active = self.cs_active(pins);
+			if active != selected
+			{
+				selected = active;
+				if let (false, Some(slot)) = (active, transaction)
+				{
+					end_transaction(output, slot, ts, words);
+				}
+
+				output.push(Section { start: ts, end: ts, content: SectionContent::ChipSelect(active) });
+				transaction = if active { begin_transaction(output, ts) } else { None };
+				words = 0;
+				last_end = None;
+				mosidata = 0;
+				misodata = 0;
+				bitcount = 0;
+			}
+
 			let sck = extract(pins, self.sck_pin);
 
 			// Need any edge on SCK
 			if sck == oldsck { continue; }
 			oldsck = sck;
 
-			// Need rising edge on SCK
-			if sck == 0 { continue; }
+			// Need the sampling edge while selected
+			if sck != sample_level || !selected { continue; }
 
 			if bitcount == 0
 			{
 				start_sample = ts;
 			}
 
-			let mosi = extract(pins, self.mosi_pin);
-			let miso = extract(pins, self.miso_pin);
+			let mosi = extract(pins, self.mosi_pin) as u32;
+			let miso = extract(pins, self.miso_pin) as u32;
 			if self.bitorder == BitOrder::MsbFirst
 			{
 				mosidata |= mosi << (word_size - 1 - bitcount);
@@ -68,14 +189,36 @@ impl Decoder for DecoderSPI
 			bitcount += 1;
 			if bitcount != word_size { continue; }
 
-			output.push(Section { start: start_sample, end: ts, content: SectionContent::RxByte(misodata) });
-			output.push(Section { start: start_sample, end: ts, content: SectionContent::TxByte(mosidata) });
+			/* Time from the last bit of the previous word to the first of this one */
+			if let Some(end) = last_end
+			{
+				output.push(Section { start: end, end: start_sample, content: SectionContent::WordGap });
+			}
+
+			let (rx, tx) = if word_size <= 8
+			{
+				(SectionContent::RxByte(misodata as u8), SectionContent::TxByte(mosidata as u8))
+			}
+			else
+			{
+				(SectionContent::RxWord(misodata), SectionContent::TxWord(mosidata))
+			};
+			output.push(Section { start: start_sample, end: ts, content: rx });
+			output.push(Section { start: start_sample, end: ts, content: tx });
 
+			words = words.saturating_add(1);
+			last_end = Some(ts);
 			mosidata = 0;
 			misodata = 0;
 			bitcount = 0;
 		}
 
+		/* The section is already reserved, a transaction still running ends with the capture */
+		if let Some(slot) = transaction
+		{
+			end_transaction(output, slot, samples.timestamp(samples.len - 1), words);
+		}
+
 		Ok(())
 	}
 
@@ -89,7 +232,8 @@ impl Decoder for DecoderSPI
 			self.cs_pin
 		];
 
-		!pin_duplicates(&arr)
+		self.mode < 4 && (WORD_SIZE_MIN..=WORD_SIZE_MAX).contains(&self.word_size) &&
+			pins_valid(&arr)
 	}
 
 	fn get_pin(&self, idx: usize) -> Option<(&'static str, DecoderPin)>
@@ -104,3 +248,181 @@ impl Decoder for DecoderSPI
 		}
 	}
 }
+
+#[cfg(test)]
+mod tests
+{
+	use crate::decoder_spi::{transaction_words, BitOrder, CsPolarity, DecoderSPI};
+	use crate::decoder::{Decoder, SectionBuffer, SectionContent};
+	use crate::test_utils::{decode_sections, load_sample_buffer};
+
+	// Pins of the sample captures: "Hallo" on MOSI and "Welt!" on MISO,
+	// an aborted word of 3 bits, then "?" and "#". Before that SCK runs
+	// while CS is inactive.
+	fn decoder(mode: u8) -> DecoderSPI
+	{
+		DecoderSPI
+		{
+			mosi_pin: 0,
+			miso_pin: 1,
+			sck_pin: 2,
+			cs_pin: 3,
+			cs_polarity: CsPolarity::ActiveLow,
+			mode,
+			bitorder: BitOrder::MsbFirst,
+			word_size: 8
+		}
+	}
+
+	fn bytes(sections: &SectionBuffer) -> (Vec<u8>, Vec<u8>)
+	{
+		let tx = sections.iter().filter_map(|s| match s.content
+		{
+			SectionContent::TxByte(b) => Some(b),
+			_ => None
+		}).collect();
+		let rx = sections.iter().filter_map(|s| match s.content
+		{
+			SectionContent::RxByte(b) => Some(b),
+			_ => None
+		}).collect();
+		(tx, rx)
+	}
+
+	#[test]
+	fn test_modes()
+	{
+		for mode in 0..4
+		{
+			let file = format!("SPI/SPI_Mode{}_Hallo.csv", mode);
+			let sections = decode_sections(&file, decoder(mode));
+			assert_eq!(bytes(&sections), (b"Hallo?".to_vec(), b"Welt!#".to_vec()), "mode {}", mode);
+
+			/* The other edge sees the data while it changes */
+			let wrong = decode_sections(&file, decoder(mode ^ 1));
+			assert_ne!(bytes(&wrong).0, b"Hallo?", "mode {}", mode);
+		}
+	}
+
+	#[test]
+	fn test_chip_select()
+	{
+		let sections = decode_sections("SPI/SPI_Mode0_Hallo.csv", decoder(0));
+		let cs: Vec<bool> = sections.iter().filter_map(|s| match s.content
+		{
+			SectionContent::ChipSelect(v) => Some(v),
+			_ => None
+		}).collect();
+		assert_eq!(cs, [true, false, true, false, true, false]);
+
+		/* Words lie between assert and deassert */
+		let first = sections.iter().next().unwrap();
+		assert_eq!(first.content, SectionContent::ChipSelect(true));
+		let word = sections.iter().find(|s| s.content.is_frame()).unwrap();
+		assert!(word.start > first.end);
+
+		/* Active high only sees the idle bus */
+		let mut spi = decoder(0);
+		spi.cs_polarity = CsPolarity::ActiveHigh;
+		let sections = decode_sections("SPI/SPI_Mode0_Hallo.csv", spi);
+		assert!(bytes(&sections).0.is_empty());
+	}
+
+	#[test]
+	fn test_cpol_start()
+	{
+		/* SCK idles high in mode 3, the capture starting high is no edge */
+		let buf = load_sample_buffer("SPI/SPI_Mode3_Hallo.csv");
+		let mut sections = SectionBuffer::new();
+		decoder(3).decode(&buf, &mut sections).unwrap();
+		assert_eq!(sections.iter().next().unwrap().content, SectionContent::ChipSelect(true));
+	}
+
+	#[test]
+	fn test_word_size()
+	{
+		/* 12 bit words, three in one transaction */
+		let mut spi = decoder(0);
+		spi.word_size = 12;
+		let sections = decode_sections("SPI/SPI_Mode0_Words12.csv", spi);
+		let words: Vec<SectionContent> = sections.iter()
+			.filter(|s| s.content.is_frame())
+			.map(|s| s.content)
+			.collect();
+		assert_eq!(words, [
+			SectionContent::RxWord(0x000), SectionContent::TxWord(0xABC),
+			SectionContent::RxWord(0x555), SectionContent::TxWord(0x123),
+			SectionContent::RxWord(0xAAA), SectionContent::TxWord(0xFFF)
+		]);
+
+		/* Short words still fit into bytes */
+		let mut spi = decoder(0);
+		spi.word_size = 4;
+		let sections = decode_sections("SPI/SPI_Mode0_Hallo.csv", spi);
+		assert_eq!(bytes(&sections).0, [0x4, 0x8, 0x6, 0x1, 0x6, 0xC, 0x6, 0xC, 0x6, 0xF, 0x3, 0xF]);
+	}
+
+	#[test]
+	fn test_transaction()
+	{
+		let mut spi = decoder(0);
+		spi.word_size = 12;
+		let sections = decode_sections("SPI/SPI_Mode0_Words12.csv", spi);
+		let transaction = sections.iter()
+			.find(|s| matches!(s.content, SectionContent::Transaction(_)))
+			.unwrap();
+		assert_eq!(transaction.content, SectionContent::Transaction(3));
+
+		/* It spans from assert to deassert of CS */
+		let cs: Vec<_> = sections.iter()
+			.filter(|s| matches!(s.content, SectionContent::ChipSelect(_)))
+			.map(|s| s.start)
+			.collect();
+		assert_eq!((transaction.start, transaction.end), (cs[0], cs[1]));
+
+		/* It comes right after the CS marker, before its words */
+		let order: Vec<_> = sections.iter().take(3).map(|s| s.content).collect();
+		assert_eq!(order[..2], [SectionContent::ChipSelect(true), SectionContent::Transaction(3)]);
+		assert!(order[2].is_frame());
+		assert!(sections.iter().zip(sections.iter().skip(1)).all(|(a, b)| a.start <= b.start));
+
+		let mosi: Vec<u32> = transaction_words(&sections, transaction)
+			.filter_map(|(mosi, v)| mosi.then_some(v))
+			.collect();
+		assert_eq!(mosi, [0xABC, 0x123, 0xFFF]);
+
+		/* The pause after each word is one bit time longer than the previous */
+		let gaps: Vec<_> = sections.iter()
+			.filter(|s| s.content == SectionContent::WordGap)
+			.map(|s| s.end - s.start)
+			.collect();
+		assert_eq!(gaps, [1800, 2700]);
+
+		/* The aborted word counts for nothing */
+		let sections = decode_sections("SPI/SPI_Mode0_Hallo.csv", decoder(0));
+		let counts: Vec<_> = sections.iter().filter_map(|s| match s.content
+		{
+			SectionContent::Transaction(n) => Some(n),
+			_ => None
+		}).collect();
+		assert_eq!(counts, [5, 0, 1]);
+	}
+
+	#[test]
+	fn test_valid()
+	{
+		assert!(decoder(3).is_valid());
+		assert!(!decoder(4).is_valid());
+
+		let mut spi = decoder(0);
+		spi.word_size = 32;
+		assert!(spi.is_valid());
+		spi.word_size = 3;
+		assert!(!spi.is_valid());
+
+		/* Pins beyond the channels, e.g. from a host config */
+		let mut spi = decoder(0);
+		spi.cs_pin = 20;
+		assert!(!spi.is_valid());
+	}
+}
@@ -1,14 +1,31 @@
 use crate::userflash::UserFlash;
+use crate::decoder::DecoderUnion;
+use crate::trigger::Trigger;
+use crate::glitch::GlitchFilter;
+use crate::stop::StopCondition;
+use crate::sample::{Sample, ALL_CHANNELS};
+use crate::gui::{decoder_to_items, items_valid};
 
-#[derive(serde::Serialize, serde::Deserialize)]
-pub enum DecoderUnion
-{
-	None,
-	Uart(crate::decoder_uart::DecoderUart),
-	SPI(crate::decoder_spi::DecoderSPI),
-	I2C(crate::decoder_i2c::DecoderI2C),
-	OneWire(crate::decoder_onewire::DecoderOneWire),
-}
+// Menu selections of the decoder with the most inputs
+pub const SELS_LEN: usize = 12;
+
+// Byte 0 holds the layout version, the settings follow it. Raise it whenever
+// one of the stored types changes, older settings are then dropped instead of
+// read shifted. Earlier layouts started with the decoder, whose variant index
+// stays below this version.
+const VERSION: u8 = 16;
+
+const DECODER_START: usize = 1;
+const DECODER_LEN: usize = size_of::<DecoderUnion>();
+const SELS_START: usize = DECODER_START + DECODER_LEN;
+const TRIGGER_START: usize = SELS_START + SELS_LEN;
+const TRIGGER_LEN: usize = size_of::<Trigger>();
+const FILTER_START: usize = TRIGGER_START + TRIGGER_LEN;
+const FILTER_LEN: usize = size_of::<GlitchFilter>();
+const STOP_START: usize = FILTER_START + FILTER_LEN;
+const STOP_LEN: usize = size_of::<StopCondition>();
+const CHANNELS_START: usize = STOP_START + STOP_LEN;
+const LEN: usize = CHANNELS_START + size_of::<Sample>();
 
 pub struct DecoderStorage
 {
@@ -16,35 +33,64 @@ pub struct DecoderStorage
 
 impl DecoderStorage
 {
-	pub fn load(flash: &UserFlash) -> (DecoderUnion, [u8; 8])
+	// The channel mask is stored as is, erased flash enables all channels.
+	// Settings of another layout version or that the menu would not accept
+	// are dropped, the GUI indexes its option lists with them. Dropped menu
+	// selections are taken from the decoder again.
+	pub fn load(flash: &UserFlash) -> (DecoderUnion, [u8; SELS_LEN], Trigger, GlitchFilter, StopCondition, Sample)
 	{
-		/* Get the Length of the Decoder */
-		const MAX_LEN: usize = size_of::<DecoderUnion>();
-		let mut bytes: [u8; MAX_LEN] = [0; MAX_LEN];
-		let mut sels: [u8; 8] = [0; 8];
+		let bytes = &flash.as_slice()[0..LEN];
+		if bytes[0] != VERSION
+		{
+			return (DecoderUnion::None, [0; SELS_LEN], Trigger::default(), GlitchFilter::default(),
+				StopCondition::default(), ALL_CHANNELS);
+		}
 
-		/* Read Data from flash and serialize */
-		bytes.copy_from_slice(&flash.as_slice()[0..MAX_LEN]);
-		sels.copy_from_slice(&flash.as_slice()[MAX_LEN..(MAX_LEN+8)]);
+		let decoder = match postcard::from_bytes::<DecoderUnion>(&bytes[DECODER_START..SELS_START])
+		{
+			Ok(x) if x.decoder().is_none_or(|d| d.is_valid()) => x,
+			_ => DecoderUnion::None,
+		};
 
-		(match postcard::from_bytes(&bytes)
+		let mut sels: [u8; SELS_LEN] = [0; SELS_LEN];
+		sels.copy_from_slice(&bytes[SELS_START..TRIGGER_START]);
+		if !items_valid(&decoder, &sels)
 		{
-			Ok(x) => x,
-			Err(_) => DecoderUnion::None,
-		}, sels)
+			decoder_to_items(&decoder, &mut sels);
+		}
+
+		let mut channel_bytes: [u8; size_of::<Sample>()] = [0; size_of::<Sample>()];
+		channel_bytes.copy_from_slice(&bytes[CHANNELS_START..LEN]);
+
+		(decoder, sels,
+		match postcard::from_bytes::<Trigger>(&bytes[TRIGGER_START..FILTER_START])
+		{
+			Ok(x) if x.is_valid() => x,
+			_ => Trigger::default(),
+		},
+		postcard::from_bytes::<GlitchFilter>(&bytes[FILTER_START..STOP_START]).unwrap_or_default(),
+		match postcard::from_bytes::<StopCondition>(&bytes[STOP_START..CHANNELS_START])
+		{
+			Ok(x) if x.is_valid() => x,
+			_ => StopCondition::default(),
+		},
+		Sample::from_le_bytes(channel_bytes))
 	}
 
 	/* POTENTIAL TODO: Save the rest of the sector before erasing and rewrite */
-	pub fn save(flash: &mut UserFlash, decoder: &DecoderUnion, sels: &[u8])
+	pub fn save(flash: &mut UserFlash, decoder: &DecoderUnion, sels: &[u8], trigger: &Trigger,
+		filter: &GlitchFilter, stop: &StopCondition, channels: Sample)
 	{
-		/* Get the Length of the Decoder */
-		const MAX_LEN: usize = size_of::<DecoderUnion>();
-		let mut bytes: [u8; MAX_LEN+8] = [0; MAX_LEN+8];
-
-		bytes[MAX_LEN..MAX_LEN+8].copy_from_slice(sels);
+		let mut bytes: [u8; LEN] = [0; LEN];
+		bytes[0] = VERSION;
+		bytes[SELS_START..TRIGGER_START].copy_from_slice(sels);
 
-		/* Convert Decoder to Bytes and write them into the Flash */
-		postcard::to_slice(&decoder, &mut bytes).unwrap();
+		/* Convert Decoder, Trigger, Filter and Stop Condition to Bytes and write them into the Flash */
+		postcard::to_slice(&decoder, &mut bytes[DECODER_START..SELS_START]).unwrap();
+		postcard::to_slice(&trigger, &mut bytes[TRIGGER_START..FILTER_START]).unwrap();
+		postcard::to_slice(&filter, &mut bytes[FILTER_START..STOP_START]).unwrap();
+		postcard::to_slice(&stop, &mut bytes[STOP_START..CHANNELS_START]).unwrap();
+		bytes[CHANNELS_START..LEN].copy_from_slice(&channels.to_le_bytes());
 		flash.erase();
 		flash.write(bytes.iter());
 	}
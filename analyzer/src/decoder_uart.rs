@@ -1,6 +1,6 @@
 use crate::bit_reader::BitReader;
-use crate::decoder::{SectionBuffer, Section, SectionContent, Decoder, DecoderPin, TIMER_CLOCK_RATE};
-use crate::sample::{SampleBuffer, BitSignal, Pulse, PulsewiseIterator};
+use crate::decoder::{SectionBuffer, Section, SectionContent, Decoder, DecoderPin, pins_valid};
+use crate::sample::{SampleBuffer, BitSignal, Pulse, PulsewiseIterator, Timestamp};
 use libm::roundf;
 
 // TODO: use .peekable() in rust core
@@ -11,7 +11,7 @@ pub enum Parity {
 	Odd
 }
 
-#[derive(Clone, Copy, serde::Serialize, serde::Deserialize)]
+#[derive(Clone, Copy, PartialEq, serde::Serialize, serde::Deserialize)]
 pub enum DataBits {
 	Five = 5,
 	Six = 6,
@@ -20,7 +20,7 @@ pub enum DataBits {
 	Nine = 9
 }
 
-#[derive(Clone, Copy, serde::Serialize, serde::Deserialize)]
+#[derive(Clone, Copy, PartialEq, serde::Serialize, serde::Deserialize)]
 pub enum StopBits {
 	One,
 	OneAndHalf,
@@ -50,7 +50,7 @@ pub struct BitwiseIterator<'a> {
 	buffer: PulsewiseIterator<'a>,
 	expected_bit_time: f32,
 	current_pulse: Pulse,
-	bit_time: u32,
+	bit_time: Timestamp,
 }
 
 impl<'a> BitwiseIterator<'a> {
@@ -75,7 +75,7 @@ impl<'a> BitwiseIterator<'a> {
 		})
 	}
 
-	fn current_time(&self) -> u32 {
+	fn current_time(&self) -> Timestamp {
 		self.current_pulse.start
 	}
 
@@ -135,7 +135,7 @@ impl<'a> BitwiseIterator<'a> {
 	fn calculate_pulse(&mut self, mut pulse: Pulse) -> Pulse {
 		// Calc bit timings for the current pulse
 		let duration = pulse.duration();
-		let bit_count = roundf(duration as f32 / self.expected_bit_time) as u32;
+		let bit_count = roundf(duration as f32 / self.expected_bit_time) as Timestamp;
 		// .max, as pulse must describe at least one bit
 		let bit_time = duration / bit_count.max(1);
 
@@ -288,8 +288,9 @@ pub struct DecoderUart {
 impl Decoder for DecoderUart {
 
 	fn decode(&self, samples: &SampleBuffer, output: &mut SectionBuffer) -> Result<(), ()> {
-		if self.baudrate > TIMER_CLOCK_RATE {return Err(())}
-		let bit_time = TIMER_CLOCK_RATE as f32 / self.baudrate as f32;
+		let clock_rate = samples.timebase.clock_rate();
+		if self.baudrate > clock_rate {return Err(())}
+		let bit_time = clock_rate as f32 / self.baudrate as f32;
 
 		let mut bits = BitwiseIterator::from(samples.edge_iter(self.rx_pin).into(), bit_time);
 		let mut output = UartOutput {output};
@@ -307,7 +308,7 @@ impl Decoder for DecoderUart {
 
 	fn is_valid(&self) -> bool
 	{
-		self.tx_pin != self.rx_pin
+		pins_valid(&[self.rx_pin, self.tx_pin])
 	}
 
 	fn get_pin(&self, idx: usize) -> Option<(&'static str, DecoderPin)> {
@@ -323,7 +324,9 @@ impl Decoder for DecoderUart {
 #[cfg(test)]
 mod tests {
 	use crate::decoder_uart::{StopBits, Parity, DataBits, DecoderUart, SectionContent};
-	use crate::test_utils::{decode_sections, assert_bits_lsb_eq, assert_top_layer_eq,
+	use crate::decoder::{Decoder, SectionBuffer};
+	use crate::sample::{SampleBuffer, Timebase};
+	use crate::test_utils::{decode_sections, load_sample_buffer, assert_bits_lsb_eq, assert_top_layer_eq,
 		assert_bit_layer_no_time_overlap, assert_top_layer_no_time_overlap};
 
 	fn decoder_8n1_300() -> DecoderUart {
@@ -373,6 +376,39 @@ mod tests {
 		assert_bits_lsb_eq(8, &mut section_iter, 'o' as u64);
 	}
 
+	#[test]
+	fn test_has_errors() {
+		let sections = decode_sections("UART/UART_8N1_300_Hallo.csv", decoder_8n1_300());
+		assert!(!sections.has_errors());
+
+		let mut uart = decoder_8n1_300();
+		uart.parity = Parity::Even;
+		uart.stopbits = StopBits::Two;
+		let sections = decode_sections("UART/UART_8N1_300_Hallo.csv", uart);
+		assert!(sections.has_errors());
+	}
+
+	#[test]
+	fn test_timebase() {
+		// Same recording with 1 MHz timestamps
+		let buf = load_sample_buffer("UART/UART_8N1_300_Hallo.csv");
+		let mut slow = SampleBuffer::new();
+		for (ts, port) in &buf {
+			slow.push(port, Timebase::Mhz1.ticks(ts));
+		}
+		slow.timebase = Timebase::Mhz1;
+
+		let mut sections = SectionBuffer::new();
+		decoder_8n1_300().decode(&slow, &mut sections).unwrap();
+		let expected = decode_sections("UART/UART_8N1_300_Hallo.csv", decoder_8n1_300());
+		assert_eq!(sections.len, expected.len);
+		for (a, b) in sections.iter().zip(expected.iter()) {
+			assert_eq!(a.content, b.content);
+			// Within 1.5 % of a bit (3333 µs)
+			assert!(a.start.abs_diff(b.start / 90) <= 50);
+		}
+	}
+
 	#[test]
 	fn test_8n1_1234567() {
 		// TODO: ask Haron if the test was taken correctly (it seems to be wrong), could still be useful
@@ -2,6 +2,7 @@ use crate::timeindicator::TimeIndicator;
 use crate::font::{lcd_str, lcd_str_undraw};
 use crate::lcd::{LCD_WHITE, LCD_BLACK, LCD_HEIGHT};
 use crate::terminus16::TERMINUS16;
+use crate::sample::Timestamp;
 
 pub struct DurationIndicator
 {
@@ -28,13 +29,13 @@ impl DurationIndicator
 		lcd_str_undraw(X, Y, LABEL.chars().count(), &TERMINUS16);
 	}
 
-	pub fn show(&mut self, t: u32)
+	pub fn show(&mut self, t: Timestamp, ticks_per_us: u32)
 	{
 		if !self.timeindicator.visible
 		{
 			lcd_str(X, Y, LABEL, LCD_WHITE, LCD_BLACK, &TERMINUS16);
 		}
 
-		self.timeindicator.show(t);
+		self.timeindicator.show(t, ticks_per_us);
 	}
 }
@@ -0,0 +1,367 @@
+use crate::decoder::TIMER_CLOCK_RATE;
+
+const TICKS_PER_US: u32 = TIMER_CLOCK_RATE / 1_000_000;
+
+// Shortest step the player can keep up with, in timer ticks
+const MIN_STEP: u32 = 45;
+
+// 1-Wire master timings in microseconds (standard speed)
+const ONEWIRE_RESET: u32 = 480;
+const ONEWIRE_SLOT: u32 = 70;
+const ONEWIRE_ONE_LOW: u32 = 6;
+const ONEWIRE_ZERO_LOW: u32 = 60;
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default)]
+pub enum GenProtocol
+{
+	// 8N1, LSB first
+	#[default]
+	Uart,
+
+	// Mode 0, MSB first, CS active low
+	Spi,
+
+	// All bytes in one transfer, the first one is the address byte.
+	// The acknowledge bit is released for the device to pull low.
+	I2c,
+
+	// Reset followed by the bytes, the presence pulse is up to the device
+	OneWire
+}
+
+// Transmits bytes as frames of a protocol on pins of the yellow port
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct Generator
+{
+	pub protocol: GenProtocol,
+
+	// Yellow port pins 0-7
+	// TX, MOSI, SDA or the 1-Wire line
+	pub data_pin: u8,
+
+	// SCK or SCL
+	pub clock_pin: u8,
+
+	// SPI chip select
+	pub cs_pin: u8,
+
+	// Baudrate or clock frequency in Hz, 1-Wire ignores it
+	pub bitrate: u32
+}
+
+// Level of the port, each change is held for a number of timer ticks
+struct Wave<'a, F: FnMut(u8, u32)>
+{
+	out: &'a mut F,
+	level: u8
+}
+
+impl<'a, F: FnMut(u8, u32)> Wave<'a, F>
+{
+	fn hold(&mut self, ticks: u32)
+	{
+		(self.out)(self.level, ticks);
+	}
+
+	fn step(&mut self, pin: u8, high: bool, ticks: u32)
+	{
+		if high { self.level |= 1 << pin; } else { self.level &= !(1 << pin); }
+		self.hold(ticks);
+	}
+}
+
+impl Generator
+{
+	// Bit mask of the used pins
+	pub fn pins(&self) -> u8
+	{
+		let data = 1 << self.data_pin;
+		let clock = 1 << self.clock_pin;
+		match self.protocol
+		{
+			GenProtocol::Uart | GenProtocol::OneWire => data,
+			GenProtocol::I2c => data | clock,
+			GenProtocol::Spi => data | clock | (1 << self.cs_pin)
+		}
+	}
+
+	// Lines are only pulled low, the pull-ups make them high
+	pub fn is_open_drain(&self) -> bool
+	{
+		matches!(self.protocol, GenProtocol::I2c | GenProtocol::OneWire)
+	}
+
+	// Level of the used pins between frames
+	pub fn idle(&self) -> u8
+	{
+		match self.protocol
+		{
+			GenProtocol::Spi => self.pins() & !(1 << self.clock_pin),
+			_ => self.pins()
+		}
+	}
+
+	pub fn is_valid(&self) -> bool
+	{
+		let pins = [self.data_pin, self.clock_pin, self.cs_pin];
+		let used = match self.protocol
+		{
+			GenProtocol::Uart | GenProtocol::OneWire => 1,
+			GenProtocol::I2c => 2,
+			GenProtocol::Spi => 3
+		};
+
+		if pins[..used].iter().any(|&p| p > 7) ||
+			self.pins().count_ones() != used as u32
+		{
+			return false;
+		}
+
+		self.protocol == GenProtocol::OneWire ||
+			(self.bitrate != 0 && self.quarter() >= MIN_STEP)
+	}
+
+	// Quarter of a clock period in timer ticks
+	fn quarter(&self) -> u32
+	{
+		TIMER_CLOCK_RATE / 4 / self.bitrate
+	}
+
+	// Calls out with the level of the port and how long to hold it
+	// for every change, starting and ending with the idle level
+	pub fn generate(&self, data: &[u8], out: &mut impl FnMut(u8, u32))
+	{
+		let mut w = Wave { out, level: self.idle() };
+		match self.protocol
+		{
+			GenProtocol::Uart => self.uart(&mut w, data),
+			GenProtocol::Spi => self.spi(&mut w, data),
+			GenProtocol::I2c => self.i2c(&mut w, data),
+			GenProtocol::OneWire => self.onewire(&mut w, data)
+		}
+	}
+
+	fn uart<F: FnMut(u8, u32)>(&self, w: &mut Wave<F>, data: &[u8])
+	{
+		let bit = TIMER_CLOCK_RATE / self.bitrate;
+		let tx = self.data_pin;
+
+		/* A full frame of idle lets a receiver find the first start bit */
+		w.hold(10 * bit);
+		for &b in data
+		{
+			w.step(tx, false, bit);
+			for i in 0..8
+			{
+				w.step(tx, (b >> i) & 1 != 0, bit);
+			}
+
+			w.step(tx, true, bit);
+		}
+	}
+
+	/* MOSI changes after the falling edge, the device samples on the rising edge */
+	fn spi<F: FnMut(u8, u32)>(&self, w: &mut Wave<F>, data: &[u8])
+	{
+		let q = self.quarter();
+		let (mosi, sck, cs) = (self.data_pin, self.clock_pin, self.cs_pin);
+		w.hold(2 * q);
+		w.step(cs, false, 2 * q);
+		for &b in data
+		{
+			for i in (0..8).rev()
+			{
+				w.step(sck, false, q);
+				w.step(mosi, (b >> i) & 1 != 0, q);
+				w.step(sck, true, 2 * q);
+			}
+		}
+
+		w.step(sck, false, 2 * q);
+		w.step(cs, true, 2 * q);
+	}
+
+	/* SDA only changes while SCL is low, except for start and stop */
+	fn i2c<F: FnMut(u8, u32)>(&self, w: &mut Wave<F>, data: &[u8])
+	{
+		let q = self.quarter();
+		let (sda, scl) = (self.data_pin, self.clock_pin);
+		let bit = |w: &mut Wave<F>, high: bool|
+		{
+			w.step(scl, false, q);
+			w.step(sda, high, q);
+			w.step(scl, true, 2 * q);
+		};
+
+		w.hold(2 * q);
+		w.step(sda, false, 2 * q);
+		for &b in data
+		{
+			for i in (0..8).rev()
+			{
+				bit(w, (b >> i) & 1 != 0);
+			}
+
+			bit(w, true);
+		}
+
+		w.step(scl, false, q);
+		w.step(sda, false, q);
+		w.step(scl, true, 2 * q);
+		w.step(sda, true, 2 * q);
+	}
+
+	fn onewire<F: FnMut(u8, u32)>(&self, w: &mut Wave<F>, data: &[u8])
+	{
+		let dq = self.data_pin;
+		w.hold(ONEWIRE_SLOT * TICKS_PER_US);
+		w.step(dq, false, ONEWIRE_RESET * TICKS_PER_US);
+		w.step(dq, true, ONEWIRE_RESET * TICKS_PER_US);
+		for &b in data
+		{
+			for i in 0..8
+			{
+				let low = if (b >> i) & 1 != 0 { ONEWIRE_ONE_LOW } else { ONEWIRE_ZERO_LOW };
+				w.step(dq, false, low * TICKS_PER_US);
+				w.step(dq, true, (ONEWIRE_SLOT - low) * TICKS_PER_US);
+			}
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests
+{
+	use crate::generator::{GenProtocol, Generator};
+	use crate::sample::{Sample, SampleBuffer, Timestamp};
+	use crate::decoder::{Decoder, SectionBuffer, SectionContent};
+	use crate::decoder_uart::{DecoderUart, DataBits, Parity, StopBits};
+	use crate::decoder_spi::{BitOrder, CsPolarity, DecoderSPI};
+	use crate::decoder_i2c::DecoderI2C;
+
+	const HALLO: &[u8] = b"Hallo";
+
+	fn generator(protocol: GenProtocol) -> Generator
+	{
+		Generator
+		{
+			protocol,
+			data_pin: 0,
+			clock_pin: 1,
+			cs_pin: 2,
+			bitrate: 115200
+		}
+	}
+
+	// Record the output like a capture, yellow pin n is channel 8 + n
+	fn record(gen: &Generator, data: &[u8]) -> SampleBuffer
+	{
+		let mut buf = SampleBuffer::new();
+		let mut t: Timestamp = 0;
+		gen.generate(data, &mut |level, ticks|
+		{
+			let s = (level as Sample) << 8;
+			if buf.len == 0 || buf.samples[buf.len - 1] != s
+			{
+				buf.push(s, t);
+			}
+
+			t += ticks as Timestamp;
+		});
+
+		let last = buf.samples[buf.len - 1];
+		buf.push(last, t);
+		buf
+	}
+
+	fn decode(buf: &SampleBuffer, decoder: impl Decoder) -> Vec<SectionContent>
+	{
+		let mut sections = SectionBuffer::new();
+		decoder.decode(buf, &mut sections).unwrap();
+		sections.sections[..sections.len].iter().map(|s| s.content).collect()
+	}
+
+	fn bytes(content: &[SectionContent]) -> Vec<u8>
+	{
+		content.iter().filter_map(|c| match c
+		{
+			SectionContent::Data(b) => Some(*b as u8),
+			SectionContent::TxByte(b) | SectionContent::Byte(b) => Some(*b),
+			_ => None
+		}).collect()
+	}
+
+	#[test]
+	fn test_uart()
+	{
+		let buf = record(&generator(GenProtocol::Uart), HALLO);
+		let uart = DecoderUart
+		{
+			rx_pin: 8,
+			tx_pin: 9,
+			databits: DataBits::Eight,
+			parity: Parity::None,
+			stopbits: StopBits::One,
+			baudrate: 115200
+		};
+
+		let content = decode(&buf, uart);
+		assert_eq!(bytes(&content), HALLO);
+		assert!(!content.iter().any(|c| matches!(c, SectionContent::Err(_))));
+	}
+
+	#[test]
+	fn test_spi()
+	{
+		let buf = record(&generator(GenProtocol::Spi), HALLO);
+		let spi = DecoderSPI
+		{
+			mosi_pin: 8,
+			miso_pin: 11,
+			sck_pin: 9,
+			cs_pin: 10,
+			cs_polarity: CsPolarity::ActiveLow,
+			mode: 0,
+			bitorder: BitOrder::MsbFirst,
+			word_size: 8
+		};
+
+		assert_eq!(bytes(&decode(&buf, spi)), HALLO);
+	}
+
+	#[test]
+	fn test_i2c()
+	{
+		let data = [0xA0, 0x12, 0x34];
+		let buf = record(&generator(GenProtocol::I2c), &data);
+		let content = decode(&buf, DecoderI2C { sda_pin: 8, scl_pin: 9 });
+		assert_eq!(content.first(), Some(&SectionContent::StartBit));
+		assert!(content.contains(&SectionContent::I2cAddress(0x50)));
+		assert!(content.contains(&SectionContent::I2cWrite));
+		assert_eq!(bytes(&content), [0x12, 0x34]);
+		assert_eq!(content.last(), Some(&SectionContent::StopBit));
+	}
+
+	#[test]
+	fn test_valid()
+	{
+		assert!(generator(GenProtocol::Spi).is_valid());
+
+		let mut gen = generator(GenProtocol::Spi);
+		gen.cs_pin = 1;
+		assert!(!gen.is_valid());
+
+		/* CS is unused by I2C */
+		gen.protocol = GenProtocol::I2c;
+		assert!(gen.is_valid());
+
+		gen.data_pin = 8;
+		assert!(!gen.is_valid());
+
+		let mut gen = generator(GenProtocol::Uart);
+		gen.bitrate = 10_000_000;
+		assert!(!gen.is_valid());
+		gen.protocol = GenProtocol::OneWire;
+		assert!(gen.is_valid());
+	}
+}
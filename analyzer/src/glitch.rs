@@ -0,0 +1,136 @@
+use crate::sample::{Sample, SampleBuffer, Timestamp, CHANNELS};
+
+// Removes pulses that are shorter than a minimum width,
+// caused by noise on long wires or breadboards
+#[derive(serde::Serialize, serde::Deserialize, Clone, Copy, PartialEq, Eq, Debug, Default)]
+pub struct GlitchFilter
+{
+	// Minimum pulse width per channel in timer clock cycles (90 MHz),
+	// 0 disables the filter
+	pub min_width: [u32; CHANNELS as usize]
+}
+
+impl GlitchFilter
+{
+	// Filter a captured buffer and store the number of removed pulses in it
+	pub fn apply(&self, buf: &mut SampleBuffer)
+	{
+		let mut count = 0;
+		for ch in 0..CHANNELS
+		{
+			let width = self.min_width[ch as usize];
+			if width != 0
+			{
+				let ticks = buf.timebase.ticks(width as Timestamp);
+				count += filter_channel(buf, ch, ticks);
+			}
+		}
+
+		if count > 0
+		{
+			buf.remove_duplicates();
+		}
+
+		buf.glitches = count;
+	}
+}
+
+// Set the channel back to its previous level for every pulse shorter than width.
+// A pulse still running at the end of the buffer is kept, its width is unknown.
+fn filter_channel(buf: &mut SampleBuffer, ch: u32, width: Timestamp) -> u32
+{
+	let bit: Sample = 1 << ch;
+	let mut count = 0;
+	let mut i = 1;
+	while i < buf.len
+	{
+		let prev = buf.samples[i - 1] & bit;
+		if buf.samples[i] & bit == prev
+		{
+			i += 1;
+			continue;
+		}
+
+		/* Pulse from i until the level returns at j */
+		let mut j = i + 1;
+		while j < buf.len && buf.samples[j] & bit != prev
+		{
+			j += 1;
+		}
+
+		if j < buf.len && buf.timestamp(j) - buf.timestamp(i) < width
+		{
+			for s in &mut buf.samples[i..j]
+			{
+				*s = (*s & !bit) | prev;
+			}
+
+			count += 1;
+		}
+
+		i = j;
+	}
+
+	count
+}
+
+#[cfg(test)]
+mod tests
+{
+	use crate::glitch::GlitchFilter;
+	use crate::sample::SampleBuffer;
+	use crate::test_utils::load_sample_buffer;
+
+	fn filter(ch: usize, width: u32) -> GlitchFilter
+	{
+		let mut f = GlitchFilter::default();
+		f.min_width[ch] = width;
+		f
+	}
+
+	#[test]
+	fn test_spikes()
+	{
+		let mut buf = SampleBuffer::new();
+		buf.push(0b00, 0);
+		buf.push(0b01, 100);
+		buf.push(0b00, 103);
+		buf.push(0b10, 200);
+		buf.push(0b00, 300);
+		buf.push(0b01, 400);
+		buf.push(0b01, 1000);
+
+		filter(0, 10).apply(&mut buf);
+		assert_eq!(buf.glitches, 1);
+		assert_eq!(buf.len, 5);
+		assert_eq!(buf.get_content(1, 1), Some((true, 200)));
+		assert_eq!(buf.get_content(3, 0), Some((true, 400)));
+		assert_eq!(buf.get_content(4, 0), Some((true, 1000)));
+	}
+
+	#[test]
+	fn test_per_channel()
+	{
+		let mut buf = SampleBuffer::new();
+		buf.push(0b11, 0);
+		buf.push(0b00, 100);
+		buf.push(0b11, 105);
+		buf.push(0b11, 1000);
+
+		filter(1, 10).apply(&mut buf);
+		assert_eq!(buf.glitches, 1);
+		assert_eq!(buf.get_content(1, 0), Some((false, 100)));
+		assert_eq!(buf.get_content(1, 1), Some((true, 100)));
+	}
+
+	#[test]
+	fn test_uart_clean()
+	{
+		/* One bit at 300 baud is 300000 ticks */
+		let mut buf = load_sample_buffer("UART/UART_8N1_300_Hallo.csv");
+		let len = buf.len;
+		filter(0, 9000).apply(&mut buf);
+		assert_eq!(buf.glitches, 0);
+		assert_eq!(buf.len, len);
+	}
+}
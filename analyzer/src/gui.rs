@@ -1,20 +1,21 @@
 use crate::touch::{Coordinate, LCDTouch};
-use crate::{decoder, touch};
+use crate::touch;
 use crate::decoder_framebuffer::DecoderFrameBuffer;
 use crate::delay::delay_ms;
 use crate::hw::{get_tp_irq, lcd_cs_0, HW};
-use crate::lcd::{lcd_clear, lcd_color, lcd_hline, lcd_rect, lcd_vline, LCD_BLACK, LCD_GREEN, LCD_HEIGHT, LCD_RED, LCD_WHITE, LCD_WIDTH};
-use crate::font::{Font, lcd_icon_color, lcd_icon_undraw, lcd_icon_bw, lcd_rect_border, lcd_char, lcd_str, lcd_str_center};
+use crate::lcd::{lcd_clear, lcd_color, lcd_hline, lcd_rect, lcd_vline, LCD_BLACK, LCD_GREEN, LCD_HEIGHT, LCD_RED, LCD_WHITE, LCD_WIDTH, LCD_YELLOW};
+use crate::font::{Font, lcd_icon_color, lcd_icon_undraw, lcd_icon_bw, lcd_rect_border, lcd_str, lcd_str_center};
 use crate::terminus16_bold::TERMINUS16_BOLD;
 use crate::terminus16::{TERMINUS16, Icon};
 use crate::tinyfont::TINYFONT;
 use crate::decoder_uart::{StopBits, Parity, DataBits, DecoderUart};
-use crate::decoder_spi::{BitOrder, DecoderSPI};
+use crate::decoder_spi::{BitOrder, CsPolarity, DecoderSPI, WORD_SIZE_MIN, WORD_SIZE_MAX};
+use crate::decoder_qspi::{DataPhase, DecoderQSPI, Lanes};
 use crate::decoder_i2c::DecoderI2C;
 use crate::decoder_onewire::DecoderOneWire;
-use crate::decoder::{Decoder, DecoderPin, SectionBuffer, Section};
+use crate::decoder::{Decoder, DecoderPin, DecoderUnion, SectionBuffer};
 use crate::sampler;
-use crate::sample::SampleBuffer;
+use crate::sample::{CaptureEnd, Sample, SampleBuffer, Timebase, Timestamp, CHANNELS};
 use core::str;
 use core::fmt::Write;
 use crate::bytewriter::ByteMutWriter;
@@ -22,22 +23,38 @@ use crate::hw;
 use crate::positionindicator::PositionIndicator;
 use crate::waveform::{WaveformBuffer, CHANNEL_LABEL_WIDTH, WAVEFORM_SPACING,
 	WAVEFORM_PIN_Y, WAVEFORMS_Y, WAVEFORM_W_USIZE, WAVEFORM_W};
-use crate::decoder_storage::{DecoderUnion, DecoderStorage};
+use crate::decoder_storage::{DecoderStorage, SELS_LEN};
 use crate::cursors::Cursors;
+use crate::arena::capture_buffers;
+use crate::trigger::{Trigger, TriggerKind};
+use crate::glitch::GlitchFilter;
+use crate::stream::StreamResult;
+use crate::stop::{FrameCheck, StopCondition, StopKind};
+use crate::generator::{GenProtocol, Generator};
+use crate::player;
+use crate::selftest::{TestResult, LOOPBACK_TESTS};
+use crate::remote::{Command, RemoteError, RemoteLink, Reply, Status};
 
 const BUTTON_COUNT: usize = 8;
 const ICON_BOX: u32 = 30;
 
 const COLOR_SEL: u16 = lcd_color(0, 128, 255);
+const COLOR_DISABLED: u16 = lcd_color(96, 96, 96);
 const BORDER_SEL: u32 = 2;
 const BORDER_DEFAULT: u32 = 1;
 const TITLE_FONT: &Font = &TERMINUS16_BOLD;
 const BUTTON_HEIGHT: u32 = 26;
 const BUTTON_FONT: &Font = &TERMINUS16_BOLD;
-const DECODER_COUNT: u32 = 5;
+const DECODER_COUNT: u32 = 6;
 
 const MA_BOTTOM_TEXT_X: u32 = 26;
 
+/* Number of waveform rows, the channels are shown in banks of this size */
+const BANK_SIZE: u32 = 8;
+
+/* Capture resolution, shares the place with the cursor duration */
+const MA_RESOLUTION_X: u32 = 105;
+
 const TITLE_Y: u32 = ICON_BOX / 2 - TITLE_FONT.width;
 const TITLE_X: u32 = TITLE_Y;
 
@@ -45,7 +62,7 @@ const DA_PADDING: u32 = 10;
 const Y_BEGIN: u32 = ICON_BOX + 1;
 const DA_BTN_WIDTH: u32 = 100;
 
-const MA_ICONS: u32 = 3;
+const MA_ICONS: u32 = 6;
 const ICON_PADDING: u32 = 7;
 
 const ACTION_ICONS_SKIP: u32 = ICON_BOX + 1;
@@ -60,6 +77,7 @@ const INPUT_LABEL_Y: u32 = Y_BEGIN + DA_PADDING;
 const INPUT_BOX_Y: u32 = Y_BEGIN + DA_PADDING + 16;
 
 const INPUT_TEXT_Y: u32 = Y_BEGIN + DA_PADDING + 18;
+const INPUTS_PER_PAGE: u32 = 6;
 const TERM_Y: u32 = 40;
 
 #[derive(PartialEq, Eq, Clone, Copy)]
@@ -81,7 +99,8 @@ pub enum Action
 	ZoomIn,
 	ZoomOut,
 	Cycle,
-	Stop
+	Stop,
+	Bank
 }
 
 #[derive(PartialEq)]
@@ -92,8 +111,20 @@ enum Mode
 	DecoderAdd,
 	DecoderUart,
 	DecoderSpi,
+	DecoderQspi,
 	DecoderOneWire,
-	DecoderI2C
+	DecoderI2C,
+	Trigger,
+	Generator
+}
+
+// What Start does, in the order of the Capture setting
+#[derive(PartialEq, Eq, Clone, Copy)]
+enum CaptureMode
+{
+	Buffer,
+	Stream,
+	Sump
 }
 
 fn boxsel(x: u32, y: u32, w: u32, h: u32)
@@ -185,9 +216,10 @@ pub struct Input
 	default_val: u8
 }
 
-const SELECT_PIN_LIST: [&str; 8] =
+const SELECT_PIN_LIST: [&str; CHANNELS as usize] =
 [
-	"0", "1", "2", "3", "4", "5", "6", "7"
+	"0", "1", "2", "3", "4", "5", "6", "7",
+	"8", "9", "10", "11", "12", "13", "14", "15"
 ];
 
 const SELECT_PARITY_LIST: [&str; 3] =
@@ -347,6 +379,19 @@ const SPI_CS: Input = Input
 	default_val: 3
 };
 
+const SELECT_CS_POLARITY: Select = Select
+{
+	align: Align::Left,
+	options: &["Low", "High"]
+};
+
+const SPI_CS_POLARITY: Input = Input
+{
+	select: &SELECT_CS_POLARITY,
+	label: "CS Active",
+	default_val: 0
+};
+
 const SELECT_MODE: Select = Select
 {
 	align: Align::Left,
@@ -373,14 +418,141 @@ const SPI_BITORDER: Input = Input
 	default_val: 0
 };
 
-const SPI_INPUTS: [&Input; 6] =
+const SELECT_WORD_SIZE_LIST: [&str; (WORD_SIZE_MAX - WORD_SIZE_MIN + 1) as usize] =
+[
+	"4", "5", "6", "7", "8", "9", "10", "11", "12", "13",
+	"14", "15", "16", "17", "18", "19", "20", "21", "22", "23",
+	"24", "25", "26", "27", "28", "29", "30", "31", "32"
+];
+
+const SELECT_WORD_SIZE: Select = Select
+{
+	align: Align::Right,
+	options: &SELECT_WORD_SIZE_LIST
+};
+
+const SPI_WORD_SIZE: Input = Input
+{
+	select: &SELECT_WORD_SIZE,
+	label: "Word Bits",
+	default_val: 4
+};
+
+const SPI_INPUTS: [&Input; 8] =
 [
 	&SPI_MOSI,
 	&SPI_MISO,
 	&SPI_SCK,
 	&SPI_CS,
+	&SPI_CS_POLARITY,
 	&SPI_MODE,
-	&SPI_BITORDER
+	&SPI_BITORDER,
+	&SPI_WORD_SIZE
+];
+
+/* QSPI, CS Active and Mode as for SPI */
+const QSPI_IO0: Input = Input
+{
+	select: &SELECT_PIN,
+	label: "IO0 Pin",
+	default_val: 0
+};
+
+const QSPI_IO1: Input = Input
+{
+	select: &SELECT_PIN,
+	label: "IO1 Pin",
+	default_val: 1
+};
+
+const QSPI_IO2: Input = Input
+{
+	select: &SELECT_PIN,
+	label: "IO2 Pin",
+	default_val: 2
+};
+
+const QSPI_IO3: Input = Input
+{
+	select: &SELECT_PIN,
+	label: "IO3 Pin",
+	default_val: 3
+};
+
+const QSPI_SCK: Input = Input
+{
+	select: &SELECT_PIN,
+	label: "SCK Pin",
+	default_val: 4
+};
+
+const QSPI_CS: Input = Input
+{
+	select: &SELECT_PIN,
+	label: "CS Pin",
+	default_val: 5
+};
+
+const SELECT_COMMAND_BYTES: Select = Select
+{
+	align: Align::Right,
+	options: &["0", "1", "2", "3", "4", "5", "6", "7", "8"]
+};
+
+const QSPI_COMMAND_BYTES: Input = Input
+{
+	select: &SELECT_COMMAND_BYTES,
+	label: "Cmd Bytes",
+	default_val: 1
+};
+
+const SELECT_LANES: Select = Select
+{
+	align: Align::Right,
+	options: &["1", "2", "4"]
+};
+
+const QSPI_COMMAND_LANES: Input = Input
+{
+	select: &SELECT_LANES,
+	label: "Cmd Lines",
+	default_val: 0
+};
+
+const QSPI_DATA_LANES: Input = Input
+{
+	select: &SELECT_LANES,
+	label: "Data Lines",
+	default_val: 2
+};
+
+const SELECT_DATA_PHASE: Select = Select
+{
+	align: Align::Left,
+	options: &["Read", "Write"]
+};
+
+const QSPI_DATA_PHASE: Input = Input
+{
+	select: &SELECT_DATA_PHASE,
+	label: "Data",
+	default_val: 0
+};
+
+const QSPI_INPUTS: [&Input; 12] =
+[
+	&QSPI_IO0,
+	&QSPI_IO1,
+	&QSPI_IO2,
+	&QSPI_IO3,
+	&QSPI_SCK,
+	&QSPI_CS,
+	&SPI_CS_POLARITY,
+	&SPI_MODE,
+	&QSPI_COMMAND_BYTES,
+	&QSPI_COMMAND_LANES,
+	&QSPI_DATA_LANES,
+	&QSPI_DATA_PHASE
 ];
 
 /* I2C */
@@ -417,6 +589,471 @@ const ONEWIRE_INPUTS: [&Input; 1] =
 	&ONEWIRE_PIN
 ];
 
+/* Trigger */
+const SELECT_TRIGGER_KIND: Select = Select
+{
+	align: Align::Left,
+	options: &["Off", "Rising Edge", "Falling Edge", "Any Edge", "Match", "Mismatch"]
+};
+
+const SELECT_PATTERN: Select = Select
+{
+	align: Align::Right,
+	options: &["X", "0", "1"]
+};
+
+const TRIGGER_KIND: Input = Input
+{
+	select: &SELECT_TRIGGER_KIND,
+	label: "Trigger",
+	default_val: 0
+};
+
+const TRIGGER_CHANNEL: Input = Input
+{
+	select: &SELECT_PIN,
+	label: "Edge Channel",
+	default_val: 0
+};
+
+static POST_TRIGGER: [u8; 7] =
+[
+	100,
+	90,
+	75,
+	50,
+	25,
+	10,
+	0
+];
+
+const SELECT_POST_TRIGGER: Select = Select
+{
+	align: Align::Right,
+	options: &["100 %", "90 %", "75 %", "50 %", "25 %", "10 %", "0 %"]
+};
+
+const TRIGGER_POST: Input = Input
+{
+	select: &SELECT_POST_TRIGGER,
+	label: "Post-Trigger",
+	default_val: 0
+};
+
+const SELECT_CAPTURE_MODE: Select = Select
+{
+	align: Align::Right,
+	options: &["Buffer", "Stream", "SUMP"]
+};
+
+const TRIGGER_CAPTURE_MODE: Input = Input
+{
+	select: &SELECT_CAPTURE_MODE,
+	label: "Capture",
+	default_val: 0
+};
+
+/* Stop condition */
+const SELECT_STOP_KIND: Select = Select
+{
+	align: Align::Left,
+	options: &["Off", "Duration", "Edges", "Frames", "Pattern", "Idle"]
+};
+
+const TRIGGER_STOP_KIND: Input = Input
+{
+	select: &SELECT_STOP_KIND,
+	label: "Stop",
+	default_val: 0
+};
+
+static STOP_TIME_US: [u32; 7] =
+[
+	100,
+	1_000,
+	10_000,
+	100_000,
+	1_000_000,
+	10_000_000,
+	60_000_000
+];
+
+const SELECT_STOP_TIME: Select = Select
+{
+	align: Align::Right,
+	options: &["100 µs", "1 ms", "10 ms", "100 ms", "1 s", "10 s", "60 s"]
+};
+
+const TRIGGER_STOP_TIME: Input = Input
+{
+	select: &SELECT_STOP_TIME,
+	label: "Stop Time",
+	default_val: 0
+};
+
+static STOP_COUNT: [u32; 5] =
+[
+	1,
+	10,
+	100,
+	1_000,
+	10_000
+];
+
+const SELECT_STOP_COUNT: Select = Select
+{
+	align: Align::Right,
+	options: &["1", "10", "100", "1000", "10000"]
+};
+
+const TRIGGER_STOP_COUNT: Input = Input
+{
+	select: &SELECT_STOP_COUNT,
+	label: "Stop Count",
+	default_val: 0
+};
+
+const SELECT_TIMEBASE: Select = Select
+{
+	align: Align::Right,
+	options: &["90 MHz", "10 MHz", "1 MHz"]
+};
+
+const TRIGGER_TIMEBASE: Input = Input
+{
+	select: &SELECT_TIMEBASE,
+	label: "Timebase",
+	default_val: 0
+};
+
+/* Labels of the per channel inputs of a group */
+macro_rules! channel_labels
+{
+	($group:literal) =>
+	{
+		[
+			concat!($group, " CH0"), concat!($group, " CH1"), concat!($group, " CH2"), concat!($group, " CH3"),
+			concat!($group, " CH4"), concat!($group, " CH5"), concat!($group, " CH6"), concat!($group, " CH7"),
+			concat!($group, " CH8"), concat!($group, " CH9"), concat!($group, " CH10"), concat!($group, " CH11"),
+			concat!($group, " CH12"), concat!($group, " CH13"), concat!($group, " CH14"), concat!($group, " CH15")
+		]
+	};
+}
+
+/* One input per channel, all with the first option as default */
+const fn channel_inputs(select: &'static Select, labels: [&'static str; CHANNELS as usize])
+	-> [Input; CHANNELS as usize]
+{
+	let mut inputs = [const { Input { select: &SELECT_PATTERN, label: "", default_val: 0 } }; CHANNELS as usize];
+	let mut ch = 0;
+	while ch < inputs.len()
+	{
+		inputs[ch] = Input { select, label: labels[ch], default_val: 0 };
+		ch += 1;
+	}
+
+	inputs
+}
+
+static TRIGGER_PATTERN: [Input; CHANNELS as usize] = channel_inputs(&SELECT_PATTERN, channel_labels!("Pattern"));
+
+/* Glitch filter, minimum pulse width per channel.
+ * 200 ns is the shortest setting that removes single sample spikes. */
+static GLITCH_WIDTH_NS: [u32; 8] =
+[
+	0,
+	200,
+	500,
+	1_000,
+	2_000,
+	5_000,
+	10_000,
+	50_000
+];
+
+const SELECT_GLITCH: Select = Select
+{
+	align: Align::Right,
+	options: &["Off", "200 ns", "500 ns", "1 µs", "2 µs", "5 µs", "10 µs", "50 µs"]
+};
+
+static TRIGGER_FILTER: [Input; CHANNELS as usize] = channel_inputs(&SELECT_GLITCH, channel_labels!("Filter"));
+
+static TRIGGER_STOP: [Input; CHANNELS as usize] = channel_inputs(&SELECT_PATTERN, channel_labels!("Stop"));
+
+/* Channel enable, changes on disabled channels are not recorded */
+const SELECT_CHANNEL_ENABLE: Select = Select
+{
+	align: Align::Left,
+	options: &["On", "Off"]
+};
+
+static TRIGGER_ENABLE: [Input; CHANNELS as usize] = channel_inputs(&SELECT_CHANNEL_ENABLE, channel_labels!("Record"));
+
+/* General settings, they come first */
+const TRIGGER_SETTINGS: [&Input; 8] =
+[
+	&TRIGGER_KIND,
+	&TRIGGER_CHANNEL,
+	&TRIGGER_POST,
+	&TRIGGER_CAPTURE_MODE,
+	&TRIGGER_STOP_KIND,
+	&TRIGGER_STOP_TIME,
+	&TRIGGER_STOP_COUNT,
+	&TRIGGER_TIMEBASE
+];
+
+/* Index of the first pattern input, one input per channel */
+const TRIGGER_PATTERN_FIRST: usize = TRIGGER_SETTINGS.len();
+
+/* Index of the first glitch filter input, one input per channel */
+const TRIGGER_FILTER_FIRST: usize = TRIGGER_PATTERN_FIRST + CHANNELS as usize;
+
+/* Index of the first stop pattern input, one input per channel */
+const TRIGGER_STOP_FIRST: usize = TRIGGER_FILTER_FIRST + CHANNELS as usize;
+
+/* Index of the first channel enable input, one input per channel */
+const TRIGGER_ENABLE_FIRST: usize = TRIGGER_STOP_FIRST + CHANNELS as usize;
+
+const TRIGGER_INPUTS_LEN: usize = TRIGGER_ENABLE_FIRST + CHANNELS as usize;
+
+/* Each group of channel inputs starts on its own page */
+const TRIGGER_PAGE_BREAKS: [usize; 4] =
+[
+	TRIGGER_PATTERN_FIRST,
+	TRIGGER_FILTER_FIRST,
+	TRIGGER_STOP_FIRST,
+	TRIGGER_ENABLE_FIRST
+];
+
+/* The settings followed by the channel groups in the order of TRIGGER_PAGE_BREAKS */
+const fn trigger_inputs(groups: [&'static [Input; CHANNELS as usize]; 4])
+	-> [&'static Input; TRIGGER_INPUTS_LEN]
+{
+	let mut inputs = [&TRIGGER_KIND; TRIGGER_INPUTS_LEN];
+	let mut i = 0;
+	while i < TRIGGER_SETTINGS.len()
+	{
+		inputs[i] = TRIGGER_SETTINGS[i];
+		i += 1;
+	}
+
+	let mut g = 0;
+	while g < groups.len()
+	{
+		let mut ch = 0;
+		while ch < CHANNELS as usize
+		{
+			inputs[TRIGGER_PAGE_BREAKS[g] + ch] = &groups[g][ch];
+			ch += 1;
+		}
+
+		g += 1;
+	}
+
+	inputs
+}
+
+static TRIGGER_INPUTS: [&Input; TRIGGER_INPUTS_LEN] =
+	trigger_inputs([&TRIGGER_PATTERN, &TRIGGER_FILTER, &TRIGGER_STOP, &TRIGGER_ENABLE]);
+
+/* Generator */
+const SELECT_GEN_PROTOCOL: Select = Select
+{
+	align: Align::Left,
+	options: &["UART", "SPI", "I2C", "1-Wire"]
+};
+
+/* The generator drives the yellow port, channels 8 to 15 */
+const SELECT_YELLOW_PIN: Select = Select
+{
+	align: Align::Right,
+	options: &["8", "9", "10", "11", "12", "13", "14", "15"]
+};
+
+const SELECT_BITRATE_LIST: [&str; 6] =
+[
+	"300", "1200", "9600", "19200", "57600", "115200"
+];
+
+static BITRATES: [u32; 6] =
+[
+	300, 1200, 9600, 19200, 57600, 115200
+];
+
+const SELECT_BITRATE: Select = Select
+{
+	align: Align::Right,
+	options: &SELECT_BITRATE_LIST
+};
+
+const SELECT_LENGTH: Select = Select
+{
+	align: Align::Right,
+	options: &["1", "2", "3", "4", "5", "6", "7", "8"]
+};
+
+const SELECT_BYTE_LIST: [&str; 256] =
+[
+	"00", "01", "02", "03", "04", "05", "06", "07",
+	"08", "09", "0A", "0B", "0C", "0D", "0E", "0F",
+	"10", "11", "12", "13", "14", "15", "16", "17",
+	"18", "19", "1A", "1B", "1C", "1D", "1E", "1F",
+	"20", "21", "22", "23", "24", "25", "26", "27",
+	"28", "29", "2A", "2B", "2C", "2D", "2E", "2F",
+	"30", "31", "32", "33", "34", "35", "36", "37",
+	"38", "39", "3A", "3B", "3C", "3D", "3E", "3F",
+	"40", "41", "42", "43", "44", "45", "46", "47",
+	"48", "49", "4A", "4B", "4C", "4D", "4E", "4F",
+	"50", "51", "52", "53", "54", "55", "56", "57",
+	"58", "59", "5A", "5B", "5C", "5D", "5E", "5F",
+	"60", "61", "62", "63", "64", "65", "66", "67",
+	"68", "69", "6A", "6B", "6C", "6D", "6E", "6F",
+	"70", "71", "72", "73", "74", "75", "76", "77",
+	"78", "79", "7A", "7B", "7C", "7D", "7E", "7F",
+	"80", "81", "82", "83", "84", "85", "86", "87",
+	"88", "89", "8A", "8B", "8C", "8D", "8E", "8F",
+	"90", "91", "92", "93", "94", "95", "96", "97",
+	"98", "99", "9A", "9B", "9C", "9D", "9E", "9F",
+	"A0", "A1", "A2", "A3", "A4", "A5", "A6", "A7",
+	"A8", "A9", "AA", "AB", "AC", "AD", "AE", "AF",
+	"B0", "B1", "B2", "B3", "B4", "B5", "B6", "B7",
+	"B8", "B9", "BA", "BB", "BC", "BD", "BE", "BF",
+	"C0", "C1", "C2", "C3", "C4", "C5", "C6", "C7",
+	"C8", "C9", "CA", "CB", "CC", "CD", "CE", "CF",
+	"D0", "D1", "D2", "D3", "D4", "D5", "D6", "D7",
+	"D8", "D9", "DA", "DB", "DC", "DD", "DE", "DF",
+	"E0", "E1", "E2", "E3", "E4", "E5", "E6", "E7",
+	"E8", "E9", "EA", "EB", "EC", "ED", "EE", "EF",
+	"F0", "F1", "F2", "F3", "F4", "F5", "F6", "F7",
+	"F8", "F9", "FA", "FB", "FC", "FD", "FE", "FF"
+];
+
+const SELECT_BYTE: Select = Select
+{
+	align: Align::Right,
+	options: &SELECT_BYTE_LIST
+};
+
+const GEN_PROTOCOL: Input = Input
+{
+	select: &SELECT_GEN_PROTOCOL,
+	label: "Protocol",
+	default_val: 0
+};
+
+const GEN_DATA: Input = Input
+{
+	select: &SELECT_YELLOW_PIN,
+	label: "Data Pin",
+	default_val: 0
+};
+
+const GEN_CLOCK: Input = Input
+{
+	select: &SELECT_YELLOW_PIN,
+	label: "Clock Pin",
+	default_val: 1
+};
+
+const GEN_CS: Input = Input
+{
+	select: &SELECT_YELLOW_PIN,
+	label: "CS Pin",
+	default_val: 2
+};
+
+const GEN_BITRATE: Input = Input
+{
+	select: &SELECT_BITRATE,
+	label: "Bit Rate",
+	default_val: 5
+};
+
+const GEN_LENGTH: Input = Input
+{
+	select: &SELECT_LENGTH,
+	label: "Length",
+	default_val: 4
+};
+
+const GEN_B0: Input = Input
+{
+	select: &SELECT_BYTE,
+	label: "Byte 1",
+	default_val: 0x48
+};
+
+const GEN_B1: Input = Input
+{
+	select: &SELECT_BYTE,
+	label: "Byte 2",
+	default_val: 0x61
+};
+
+const GEN_B2: Input = Input
+{
+	select: &SELECT_BYTE,
+	label: "Byte 3",
+	default_val: 0x6C
+};
+
+const GEN_B3: Input = Input
+{
+	select: &SELECT_BYTE,
+	label: "Byte 4",
+	default_val: 0x6C
+};
+
+const GEN_B4: Input = Input
+{
+	select: &SELECT_BYTE,
+	label: "Byte 5",
+	default_val: 0x6F
+};
+
+const GEN_B5: Input = Input
+{
+	select: &SELECT_BYTE,
+	label: "Byte 6",
+	default_val: 0x00
+};
+
+const GEN_B6: Input = Input
+{
+	select: &SELECT_BYTE,
+	label: "Byte 7",
+	default_val: 0x00
+};
+
+const GEN_B7: Input = Input
+{
+	select: &SELECT_BYTE,
+	label: "Byte 8",
+	default_val: 0x00
+};
+
+/* Index of the first data byte input */
+const GEN_BYTE_FIRST: usize = 6;
+
+const GEN_INPUTS: [&Input; 14] =
+[
+	&GEN_PROTOCOL,
+	&GEN_DATA,
+	&GEN_CLOCK,
+	&GEN_CS,
+	&GEN_BITRATE,
+	&GEN_LENGTH,
+	&GEN_B0,
+	&GEN_B1,
+	&GEN_B2,
+	&GEN_B3,
+	&GEN_B4,
+	&GEN_B5,
+	&GEN_B6,
+	&GEN_B7
+];
+
 /* Get value */
 fn item_to_baudrate(idx: usize) -> u32
 {
@@ -433,11 +1070,162 @@ fn item_to_bitorder(idx: usize) -> BitOrder
 	if idx == 0 { BitOrder::MsbFirst } else { BitOrder::LsbFirst }
 }
 
+fn item_to_cs_polarity(idx: usize) -> CsPolarity
+{
+	if idx == 0 { CsPolarity::ActiveLow } else { CsPolarity::ActiveHigh }
+}
+
 fn item_to_spimode(idx: usize) -> u8
 {
 	idx as u8
 }
 
+fn item_to_word_size(idx: usize) -> u8
+{
+	idx as u8 + WORD_SIZE_MIN
+}
+
+fn item_to_lanes(idx: usize) -> Lanes
+{
+	match idx
+	{
+		0 => Lanes::Single,
+		1 => Lanes::Dual,
+		_ => Lanes::Quad
+	}
+}
+
+fn item_to_data_phase(idx: usize) -> DataPhase
+{
+	if idx == 0 { DataPhase::Read } else { DataPhase::Write }
+}
+
+fn item_to_post_trigger(idx: usize) -> u8
+{
+	POST_TRIGGER[idx]
+}
+
+fn item_to_glitch_width(idx: usize) -> u32
+{
+	GLITCH_WIDTH_NS[idx] * hw::TICKS_PER_US / 1000
+}
+
+/* One pattern input per channel: X, 0, 1 */
+fn items_to_pattern(sels: &[u8]) -> (Sample, Sample)
+{
+	let mut mask = 0;
+	let mut value = 0;
+	for (ch, sel) in sels.iter().enumerate().take(CHANNELS as usize)
+	{
+		let bit = 1 << ch;
+		match sel
+		{
+			1 => { mask |= bit; }
+			2 => { mask |= bit; value |= bit; }
+			_ => {}
+		}
+	}
+
+	(mask, value)
+}
+
+fn pattern_to_items(mask: Sample, value: Sample, sels: &mut [u8])
+{
+	for (ch, sel) in sels.iter_mut().enumerate().take(CHANNELS as usize)
+	{
+		let bit = 1 << ch;
+		*sel = if mask & bit == 0 { 0 }
+			else if value & bit == 0 { 1 }
+			else { 2 };
+	}
+}
+
+fn items_to_channels(sels: &[u8]) -> Sample
+{
+	let mut channels = 0;
+	for (ch, &sel) in sels.iter().take(CHANNELS as usize).enumerate()
+	{
+		if sel == 0
+		{
+			channels |= 1 << ch;
+		}
+	}
+
+	channels
+}
+
+fn item_to_stop_kind(idx: usize) -> StopKind
+{
+	match idx
+	{
+		1 => StopKind::Duration,
+		2 => StopKind::Edges,
+		3 => StopKind::Frames,
+		4 => StopKind::Pattern,
+		5 => StopKind::Idle,
+		_ => StopKind::Off
+	}
+}
+
+fn item_to_stop_time(idx: usize) -> u32
+{
+	STOP_TIME_US[idx]
+}
+
+fn item_to_stop_count(idx: usize) -> u32
+{
+	STOP_COUNT[idx]
+}
+
+fn item_to_capture_mode(idx: usize) -> CaptureMode
+{
+	match idx
+	{
+		1 => CaptureMode::Stream,
+		2 => CaptureMode::Sump,
+		_ => CaptureMode::Buffer
+	}
+}
+
+fn item_to_timebase(idx: usize) -> Timebase
+{
+	match idx
+	{
+		1 => Timebase::Mhz10,
+		2 => Timebase::Mhz1,
+		_ => Timebase::Mhz90
+	}
+}
+
+fn item_to_gen_protocol(idx: usize) -> GenProtocol
+{
+	match idx
+	{
+		1 => GenProtocol::Spi,
+		2 => GenProtocol::I2c,
+		3 => GenProtocol::OneWire,
+		_ => GenProtocol::Uart
+	}
+}
+
+fn item_to_bitrate(idx: usize) -> u32
+{
+	BITRATES[idx]
+}
+
+fn item_to_trigger_kind(idx: usize) -> TriggerKind
+{
+	match idx
+	{
+		1 => TriggerKind::Rising,
+		2 => TriggerKind::Falling,
+		3 => TriggerKind::AnyEdge,
+		4 => TriggerKind::Match,
+		5 => TriggerKind::Mismatch,
+		_ => TriggerKind::Off
+	}
+}
+
 fn item_to_databits(idx: usize) -> DataBits
 {
 	match idx
@@ -470,6 +1258,95 @@ fn item_to_stopbits(idx: usize) -> StopBits
 	}
 }
 
+/* Get item, the default if the value is not in the menu */
+fn value_to_item<T: PartialEq>(input: &Input, value: T, item_to_value: fn(usize) -> T) -> u8
+{
+	(0..input.select.options.len())
+		.position(|idx| item_to_value(idx) == value)
+		.map_or(input.default_val, |idx| idx as u8)
+}
+
+/* Inputs of the menu of a decoder */
+fn decoder_inputs(decoder: &DecoderUnion) -> &'static [&'static Input]
+{
+	match decoder
+	{
+		DecoderUnion::None | DecoderUnion::Uart(_) => &UART_INPUTS,
+		DecoderUnion::SPI(_) => &SPI_INPUTS,
+		DecoderUnion::QSPI(_) => &QSPI_INPUTS,
+		DecoderUnion::I2C(_) => &I2C_INPUTS,
+		DecoderUnion::OneWire(_) => &ONEWIRE_INPUTS
+	}
+}
+
+/* Selections the menu of the decoder offers */
+pub fn items_valid(decoder: &DecoderUnion, sels: &[u8]) -> bool
+{
+	decoder_inputs(decoder).iter().zip(sels)
+		.all(|(input, &sel)| (sel as usize) < input.select.options.len())
+}
+
+/* Menu selections of a decoder that did not come from the menu */
+pub fn decoder_to_items(decoder: &DecoderUnion, sels: &mut [u8])
+{
+	match decoder
+	{
+		DecoderUnion::None => {}
+		DecoderUnion::Uart(d) =>
+		{
+			sels[..UART_INPUTS.len()].copy_from_slice(&[
+				value_to_item(&UART_RX, d.rx_pin, item_to_pin),
+				value_to_item(&UART_TX, d.tx_pin, item_to_pin),
+				value_to_item(&UART_BAUDRATE, d.baudrate, item_to_baudrate),
+				value_to_item(&UART_DATABITS, d.databits, item_to_databits),
+				value_to_item(&UART_PARITY, d.parity, item_to_parity),
+				value_to_item(&UART_STOPBITS, d.stopbits, item_to_stopbits)
+			]);
+		}
+		DecoderUnion::SPI(d) =>
+		{
+			sels[..SPI_INPUTS.len()].copy_from_slice(&[
+				value_to_item(&SPI_MOSI, d.miso_pin, item_to_pin),
+				value_to_item(&SPI_MISO, d.mosi_pin, item_to_pin),
+				value_to_item(&SPI_SCK, d.sck_pin, item_to_pin),
+				value_to_item(&SPI_CS, d.cs_pin, item_to_pin),
+				value_to_item(&SPI_CS_POLARITY, d.cs_polarity, item_to_cs_polarity),
+				value_to_item(&SPI_MODE, d.mode, item_to_spimode),
+				value_to_item(&SPI_BITORDER, d.bitorder, item_to_bitorder),
+				value_to_item(&SPI_WORD_SIZE, d.word_size, item_to_word_size)
+			]);
+		}
+		DecoderUnion::QSPI(d) =>
+		{
+			sels[..QSPI_INPUTS.len()].copy_from_slice(&[
+				value_to_item(&QSPI_IO0, d.io_pins[0], item_to_pin),
+				value_to_item(&QSPI_IO1, d.io_pins[1], item_to_pin),
+				value_to_item(&QSPI_IO2, d.io_pins[2], item_to_pin),
+				value_to_item(&QSPI_IO3, d.io_pins[3], item_to_pin),
+				value_to_item(&QSPI_SCK, d.sck_pin, item_to_pin),
+				value_to_item(&QSPI_CS, d.cs_pin, item_to_pin),
+				value_to_item(&SPI_CS_POLARITY, d.cs_polarity, item_to_cs_polarity),
+				value_to_item(&SPI_MODE, d.mode, item_to_spimode),
+				value_to_item(&QSPI_COMMAND_BYTES, d.command_bytes, |idx| idx as u8),
+				value_to_item(&QSPI_COMMAND_LANES, d.command_lanes, item_to_lanes),
+				value_to_item(&QSPI_DATA_LANES, d.data_lanes, item_to_lanes),
+				value_to_item(&QSPI_DATA_PHASE, d.data_phase, item_to_data_phase)
+			]);
+		}
+		DecoderUnion::I2C(d) =>
+		{
+			sels[..I2C_INPUTS.len()].copy_from_slice(&[
+				value_to_item(&I2C_SDA, d.sda_pin, item_to_pin),
+				value_to_item(&I2C_SCL, d.scl_pin, item_to_pin)
+			]);
+		}
+		DecoderUnion::OneWire(d) =>
+		{
+			sels[0] = value_to_item(&ONEWIRE_PIN, d.onewire_pin, item_to_pin);
+		}
+	}
+}
+
 fn cycle_fwd(idx: u32, count: u32) -> u32
 {
 	if idx == count - 1 { 0 } else { idx + 1 }
@@ -483,7 +1360,7 @@ fn cycle_bwd(idx: u32, count: u32) -> u32
 const ACTIONS_INFO: [Action; 8] =
 [
 	Action::None, Action::None, Action::None, Action::None,
-	Action::None, Action::None, Action::None, Action::Enter
+	Action::None, Action::None, Action::Check, Action::Enter
 ];
 
 const ACTIONS_SAMPLING: [Action; 8] =
@@ -494,7 +1371,7 @@ const ACTIONS_SAMPLING: [Action; 8] =
 
 const ACTIONS_MAIN: [Action; 8] =
 [
-	Action::None, Action::Cursors, Action::Left, Action::Right,
+	Action::Bank, Action::Cursors, Action::Left, Action::Right,
 	Action::ZoomIn, Action::ZoomOut, Action::Cycle, Action::Enter
 ];
 
@@ -529,8 +1406,11 @@ struct ZoomLevel
 	unit: TimeUnit
 }
 
-const ZOOM_LEVELS: [ZoomLevel; 21] =
+const ZOOM_LEVELS: [ZoomLevel; 24] =
 [
+	ZoomLevel { value:  60, unit: TimeUnit::Second },
+	ZoomLevel { value:  20, unit: TimeUnit::Second },
+	ZoomLevel { value:  10, unit: TimeUnit::Second },
 	ZoomLevel { value:   5, unit: TimeUnit::Second },
 	ZoomLevel { value:   2, unit: TimeUnit::Second },
 	ZoomLevel { value:   1, unit: TimeUnit::Second },
@@ -554,15 +1434,18 @@ const ZOOM_LEVELS: [ZoomLevel; 21] =
 	ZoomLevel { value:   1, unit: TimeUnit::Microsecond }
 ];
 
+// Initial zoom level after a capture (5 s), zoomed out further if it is longer
+const ZOOM_DEFAULT: usize = 3;
+
 fn map(x: f64, in_min: f64, in_max: f64, out_min: f64, out_max: f64) -> f64
 {
 	(x - in_min) * (out_max - out_min) / (in_max - in_min) + out_min
 }
 
-pub fn t_to_x(t: u32, start: u32, end: u32) -> u32
+pub fn t_to_x(t: Timestamp, start: Timestamp, end: Timestamp) -> u32
 {
 	let max = (WAVEFORM_W - 1) as f64;
-	let x = map(t.into(), start.into(), end.into(), 0.0, max);
+	let x = map(t as f64, start as f64, end as f64, 0.0, max);
 	f64::min(f64::max(x, 0.0), max) as u32
 }
 
@@ -574,21 +1457,37 @@ pub struct Gui
 	ma_selected: u32,
 	da_selected: u32,
 	cd_selected: u32,
-	sels: [u8; 8],
+	sels: [u8; SELS_LEN],
+	trigger_sels: [u8; TRIGGER_INPUTS_LEN],
+	gen_sels: [u8; GEN_INPUTS.len()],
 	inputs: &'static [&'static Input],
+
+	// Inputs that start a new page besides every INPUTS_PER_PAGE
+	page_breaks: &'static [usize],
 	term_rows: u32,
 	term_lens: [u8; 16],
 	pub buf: SampleBuffer,
 	sec_buf: SectionBuffer,
 	cur_decoder: DecoderUnion,
+	trigger: Trigger,
+	filter: GlitchFilter,
+	stop: StopCondition,
+	timebase: Timebase,
+	channels: Sample,
+	capture_mode: CaptureMode,
+	repeat: bool,
+	captures: u32,
+	error_captures: u32,
 	decoder_framebuf: DecoderFrameBuffer<WAVEFORM_W_USIZE>,
-	t_start: u32,
-	t_end: u32,
+	t_start: Timestamp,
+	t_end: Timestamp,
 	hw: HW,
 	zoom: usize,
+	bank: u32,
 	pi: PositionIndicator,
 	wf: WaveformBuffer,
-	cursors: Cursors
+	cursors: Cursors,
+	remote: RemoteLink
 }
 
 impl Gui
@@ -630,7 +1529,8 @@ impl Gui
 		Self::bottom_divider();
 
 		/* Borrow flash Temporarily to get the Saved Decoder */
-		let (decoder, sels) = DecoderStorage::load(&hw.user_flash);
+		let (decoder, sels, trigger, filter, stop, channels) = DecoderStorage::load(&hw.user_flash);
+		let (buf, sec_buf) = capture_buffers(hw::capture_memory());
 
 		let mut gui = Gui
 		{
@@ -641,24 +1541,34 @@ impl Gui
 			da_selected: 0,
 			cd_selected: 0,
 			sels,
-			inputs: &UART_INPUTS,
+			trigger_sels: [0; TRIGGER_INPUTS_LEN],
+			gen_sels: GEN_INPUTS.map(|input| input.default_val),
+			inputs: decoder_inputs(&decoder),
+			page_breaks: &[],
 			term_rows: 0,
 			term_lens: [0; 16],
-			buf: SampleBuffer::new(),
-			sec_buf: SectionBuffer
-			{
-				sections: [Section::default(); decoder::SECBUF_SIZE],
-				len: 0
-			},
+			buf,
+			sec_buf,
 			cur_decoder: decoder,
+			trigger,
+			filter,
+			stop,
+			timebase: Timebase::Mhz90,
+			channels,
+			capture_mode: CaptureMode::Buffer,
+			repeat: false,
+			captures: 0,
+			error_captures: 0,
 			decoder_framebuf: DecoderFrameBuffer::new(),
 			t_start: 0,
-			t_end: 5 * 1_000_000 * hw::TICKS_PER_US,
+			t_end: 5 * 1_000_000 * Timebase::Mhz90.ticks_per_us() as Timestamp,
 			hw,
-			zoom: 0,
+			zoom: ZOOM_DEFAULT,
+			bank: 0,
 			pi: PositionIndicator::new(),
 			wf: WaveformBuffer::new(),
-			cursors: Cursors::new()
+			cursors: Cursors::new(),
+			remote: RemoteLink::new()
 		};
 
 		gui.icon_box();
@@ -713,6 +1623,7 @@ impl Gui
 			Action::ZoomOut => lcd_icon_bw(x, y, Icon::TimeShrink),
 			Action::Cycle => lcd_icon_bw(x, y, Icon::Cycle),
 			Action::Stop => lcd_icon_bw(x, y, Icon::Stop),
+			Action::Bank => lcd_icon_bw(x, y, Icon::Bank),
 			_ => lcd_icon_undraw(x, y)
 		}
 	}
@@ -761,8 +1672,11 @@ impl Gui
 			Mode::DecoderAdd => { self.da_action(action); }
 			Mode::DecoderUart => { self.u_action(action); }
 			Mode::DecoderSpi => { self.s_action(action); }
+			Mode::DecoderQspi => { self.q_action(action); }
 			Mode::DecoderI2C => { self.i_action(action); }
 			Mode::DecoderOneWire => { self.o_action(action); }
+			Mode::Trigger => { self.t_action(action); }
+			Mode::Generator => { self.g_action(action); }
 		};
 	}
 
@@ -771,6 +1685,78 @@ impl Gui
 		self.action(self.button_to_action(key));
 	}
 
+	// A byte from the serial port, answers each complete command
+	pub fn remote(&mut self, b: u8)
+	{
+		let Some(cmd) = self.remote.push(b) else
+		{
+			return;
+		};
+
+		let reply = match cmd
+		{
+			Ok(cmd) => self.remote_command(cmd),
+			Err(e) => Reply::Error(e)
+		};
+		sampler::remote_reply(&reply);
+
+		/* Commands received during a capture are answered after Run */
+		for reply in self.remote.deferred()
+		{
+			sampler::remote_reply(reply);
+		}
+
+		self.remote.done();
+	}
+
+	fn remote_command(&mut self, cmd: Command) -> Reply
+	{
+		/* Everything but the buttons needs the main screen */
+		let ready = self.mode == Mode::Main && !self.cursors.en;
+		match cmd
+		{
+			Command::Status => Reply::Status(Status
+			{
+				ready,
+				samples: self.buf.len as u32,
+				sections: self.sec_buf.len as u32,
+				captures: self.captures,
+				error_captures: self.error_captures
+			}),
+			Command::Key(key) if key as usize >= BUTTON_COUNT => Reply::Error(RemoteError::Argument),
+			Command::Key(key) =>
+			{
+				self.key(key as i32);
+				Reply::Ok
+			}
+			_ if !ready => Reply::Error(RemoteError::Busy),
+			Command::Run if self.capture_mode == CaptureMode::Sump => Reply::Error(RemoteError::Busy),
+			Command::Run =>
+			{
+				let mut link = core::mem::replace(&mut self.remote, RemoteLink::new());
+				sampler::remote_capture(&mut link, || self.ma_run());
+				self.remote = link;
+				Reply::Ok
+			}
+			Command::Stop => Reply::Ok,
+			Command::Fetch =>
+			{
+				sampler::transfer_blocking(&self.buf, &self.sec_buf);
+				Reply::Ok
+			}
+			Command::SetDecoder(decoder) if decoder.decoder().is_some_and(|d| !d.is_valid()) =>
+				Reply::Error(RemoteError::Argument),
+			Command::SetDecoder(decoder) =>
+			{
+				/* Saved with the decoder, the menu opens with them */
+				decoder_to_items(&decoder, &mut self.sels);
+				self.inputs = decoder_inputs(&decoder);
+				self.decoder_done(decoder);
+				Reply::Ok
+			}
+		}
+	}
+
 	pub fn touch(&mut self, coords: Coordinate)
 	{
 		/* Bottom Buttons */
@@ -829,21 +1815,13 @@ impl Gui
 			}
 		}
 
-		/* Inside of a Decoder */
+		/* Inside of a Decoder or Settings Page */
 		if self.mode == Mode::DecoderI2C || self.mode == Mode::DecoderOneWire ||
-		self.mode == Mode::DecoderSpi || self.mode == Mode::DecoderUart
+		self.mode == Mode::DecoderSpi || self.mode == Mode::DecoderQspi ||
+		self.mode == Mode::DecoderUart || self.mode == Mode::Trigger || self.mode == Mode::Generator
 		{
-			let inputs: Option<core::slice::Iter<'_, &Input>> = match self.mode
-			{
-				Mode::DecoderI2C => Some(I2C_INPUTS.iter()),
-				Mode::DecoderOneWire => Some(ONEWIRE_INPUTS.iter()),
-				Mode::DecoderSpi => Some(SPI_INPUTS.iter()),
-				Mode::DecoderUart => Some(UART_INPUTS.iter()),
-
-				_ => None
-			};
-
-			for (y, input) in inputs.unwrap().enumerate()
+			let (_, first, len) = self.cd_page(self.cd_selected);
+			for y in 0..len
 			{
 				/* Plus */
 				let x1 = Self::input_text_x(Align::Right, TERMINUS16.width(" ")) + DA_PADDING * 1;
@@ -851,12 +1829,10 @@ impl Gui
 				let x2 = x1 + DA_PADDING * 2;
 				let y2 = y1 + TERMINUS16_BOLD.height + 10;
 
-				writeln!(self.hw.tx, "{} {} {} {}", x1, y1, x2, y2);
-
 				if coords.x > x1 as u16 && coords.x < x2 as u16
 				&& coords.y > y1 as u16 && coords.y < y2 as u16
 				{
-					self.cd_selected = y as u32;
+					self.cd_selected = first + y;
 					self.cd_left();
 				}
 
@@ -869,7 +1845,7 @@ impl Gui
 				if coords.x > x1 as u16 && coords.x < x2 as u16
 				&& coords.y > y1 as u16 && coords.y < y2 as u16
 				{
-					self.cd_selected = y as u32;
+					self.cd_selected = first + y;
 					self.cd_right();
 				}
 			}
@@ -884,8 +1860,11 @@ impl Gui
 			Mode::DecoderAdd => self.da_close(),
 			Mode::DecoderUart => self.cd_undraw(),
 			Mode::DecoderSpi => self.cd_undraw(),
+			Mode::DecoderQspi => self.cd_undraw(),
 			Mode::DecoderI2C => self.cd_undraw(),
 			Mode::DecoderOneWire => self.cd_undraw(),
+			Mode::Trigger => self.cd_undraw(),
+			Mode::Generator => self.cd_undraw(),
 			Mode::Info => self.info_close()
 		};
 
@@ -896,37 +1875,94 @@ impl Gui
 			Mode::DecoderAdd => self.da_open(),
 			Mode::DecoderUart => self.u_open(),
 			Mode::DecoderSpi => self.s_open(),
+			Mode::DecoderQspi => self.q_open(),
 			Mode::DecoderI2C => self.i_open(),
 			Mode::DecoderOneWire => self.o_open(),
+			Mode::Trigger => self.t_open(),
+			Mode::Generator => self.g_open(),
 			Mode::Info => self.info_open()
 		};
 	}
 
-	/* === INFO === */
-	fn info_action(&mut self, action: Action)
+	/* === INFO === */
+	fn info_action(&mut self, action: Action)
+	{
+		match action
+		{
+			Action::Enter => self.mode_switch(Mode::Main),
+			Action::Check => self.info_self_test(),
+			_ => {}
+		}
+	}
+
+	fn info_close(&mut self)
+	{
+		self.term_undraw();
+	}
+
+	fn info_open(&mut self)
+	{
+		self.actions_set(&ACTIONS_INFO);
+		self.title_set("About");
+		self.term_print("ITS-Board Logic Analyzer V0.1");
+		self.term_print("Created by Joel Kypke, Haron Nazari, Anton Tchekov");
+		self.term_print("");
+		self.term_print("Check: Self Test, Enter: Continue");
+	}
+
+	/* Loopback through jumper wires, replaces the capture */
+	fn info_self_test(&mut self)
+	{
+		self.term_undraw();
+		self.term_print("Self Test: Yellow pins 0-2 wired to blue pins 0-2");
+		self.term_print("");
+		for test in &LOOPBACK_TESTS
+		{
+			sampler::sample_generator(&mut self.buf, &test.gen, test.data);
+			self.sec_buf.clear();
+			test.decode(&self.buf, &mut self.sec_buf);
+
+			let mut a: [u8; 64] = [0; 64];
+			let mut line = ByteMutWriter::new(&mut a);
+			write!(line, "{:<8}", test.name).unwrap();
+			match test.check(&self.buf, &self.sec_buf)
+			{
+				TestResult::Pass(ns) => write!(line, "Pass, max. error {} ns", ns),
+				TestResult::Timing(ns) => write!(line, "FAIL, max. error {} ns", ns),
+				TestResult::Decode => write!(line, "FAIL, wrong data decoded"),
+				TestResult::Edges => write!(line, "FAIL, edges missing (wiring?)"),
+				TestResult::Capture => write!(line, "FAIL, capture overrun")
+			}.unwrap();
+			self.term_print(line.as_str());
+		}
+
+		self.run_decoder();
+		self.t_start = 0;
+		self.term_print("");
+		self.term_print("Check: Run again, Enter: Continue");
+	}
+
+	/* === CD COMMON === */
+	fn cd_sels(&self) -> &[u8]
 	{
-		if action == Action::Enter
+		match self.mode
 		{
-			self.mode_switch(Mode::Main);
+			Mode::Trigger => &self.trigger_sels,
+			Mode::Generator => &self.gen_sels,
+			_ => &self.sels
 		}
 	}
 
-	fn info_close(&mut self)
-	{
-		self.term_undraw();
-	}
-
-	fn info_open(&mut self)
+	fn cd_sels_mut(&mut self) -> &mut [u8]
 	{
-		self.actions_set(&ACTIONS_INFO);
-		self.title_set("About");
-		self.term_print("ITS-Board Logic Analyzer V0.1");
-		self.term_print("Created by Joel Kypke, Haron Nazari, Anton Tchekov");
-		self.term_print("");
-		self.term_print("Press any key to continue ...");
+		match self.mode
+		{
+			Mode::Trigger => &mut self.trigger_sels,
+			Mode::Generator => &mut self.gen_sels,
+			_ => &mut self.sels
+		}
 	}
 
-	/* === CD COMMON === */
 	fn cd_up(&mut self)
 	{
 		let prev = self.cd_selected;
@@ -949,16 +1985,18 @@ impl Gui
 	fn cd_left(&mut self)
 	{
 		let idx = self.cd_selected as usize;
-		let prev = self.sels[idx];
-		self.sels[idx] = cycle_bwd(self.sels[idx].into(), self.cur_num_options()) as u8;
+		let prev = self.cd_sels()[idx];
+		let count = self.cur_num_options();
+		self.cd_sels_mut()[idx] = cycle_bwd(prev.into(), count) as u8;
 		self.cd_sel_update(prev);
 	}
 
 	fn cd_right(&mut self)
 	{
 		let idx = self.cd_selected as usize;
-		let prev = self.sels[idx];
-		self.sels[idx] = cycle_fwd(self.sels[idx].into(), self.cur_num_options()) as u8;
+		let prev = self.cd_sels()[idx];
+		let count = self.cur_num_options();
+		self.cd_sels_mut()[idx] = cycle_fwd(prev.into(), count) as u8;
 		self.cd_sel_update(prev);
 	}
 
@@ -969,12 +2007,12 @@ impl Gui
 		let select = input.select;
 		let align = select.align;
 		let options = select.options;
-		let cur_idx = self.sels[y as usize];
+		let cur_idx = self.cd_sels()[y as usize];
 		let prev_text = options[prev_idx as usize];
 		let text = options[cur_idx as usize];
 		let w = TERMINUS16.width(text);
 		let x = Self::input_text_x(align, w);
-		let ry = INPUT_TEXT_Y + y * INPUT_Y_SKIP;
+		let ry = INPUT_TEXT_Y + self.cd_row(y) * INPUT_Y_SKIP;
 
 		let wdiff = TERMINUS16.width(prev_text) as i32 - w as i32;
 		if wdiff > 0
@@ -989,8 +2027,15 @@ impl Gui
 
 	fn cd_update(&mut self, prev: u32)
 	{
-		input_deselect(prev);
-		input_select(self.cd_selected);
+		if self.cd_page(prev) != self.cd_page(self.cd_selected)
+		{
+			self.cd_page_undraw(prev);
+			self.cd_page_render();
+			return;
+		}
+
+		input_deselect(self.cd_row(prev));
+		input_select(self.cd_row(self.cd_selected));
 	}
 
 	fn input_text_x(align: Align, w: u32) -> u32
@@ -998,8 +2043,10 @@ impl Gui
 		DA_PADDING + if align == Align::Right { INPUT_WIDTH - 2 - w } else { 2 }
 	}
 
-	fn input_undraw(&mut self, input: &Input, y: u32)
+	fn input_undraw(&mut self, input: &Input, idx: u32)
 	{
+		let y = self.cd_row(idx);
+
 		// Undraw Label
 		lcd_rect(DA_PADDING, Y_BEGIN + DA_PADDING + y * INPUT_Y_SKIP,
 			TERMINUS16.width(input.label), TERMINUS16.height, LCD_BLACK);
@@ -1009,7 +2056,7 @@ impl Gui
 			INPUT_WIDTH, INPUT_HEIGHT);
 
 		// Undraw Content
-		let text = input.select.options[self.sels[y as usize] as usize];
+		let text = input.select.options[self.cd_sels()[idx as usize] as usize];
 		let w = TERMINUS16.width(text);
 		let y = INPUT_TEXT_Y + y * INPUT_Y_SKIP;
 		let x = Self::input_text_x(input.select.align, w);
@@ -1019,12 +2066,13 @@ impl Gui
 		y, DA_PADDING * 8, TERMINUS16_BOLD.height, LCD_BLACK);
 	}
 
-	fn input_render(&mut self, input: &Input, y: u32)
+	fn input_render(&mut self, input: &Input, idx: u32)
 	{
+		let y = self.cd_row(idx);
 		lcd_str(DA_PADDING, INPUT_LABEL_Y + y * INPUT_Y_SKIP,
 			input.label, LCD_WHITE, LCD_BLACK, &TERMINUS16);
 
-		if y == self.cd_selected
+		if idx == self.cd_selected
 		{
 			input_select(y);
 		}
@@ -1033,7 +2081,7 @@ impl Gui
 			input_deselect(y);
 		}
 
-		let text = input.select.options[self.sels[y as usize] as usize];
+		let text = input.select.options[self.cd_sels()[idx as usize] as usize];
 		lcd_str(Self::input_text_x(input.select.align, TERMINUS16.width(text)),
 			INPUT_TEXT_Y + y * INPUT_Y_SKIP,
 			text, LCD_WHITE, LCD_BLACK, &TERMINUS16);
@@ -1045,33 +2093,106 @@ impl Gui
 		, INPUT_TEXT_Y + y * INPUT_Y_SKIP, "+", LCD_WHITE, LCD_BLACK, &TERMINUS16_BOLD);
 	}
 
-	fn cd_undraw(&mut self)
+	/* Number of the page, index of its first input and number of inputs of the page holding idx */
+	fn cd_page(&self, idx: u32) -> (u32, u32, u32)
 	{
-		let mut y = 0;
-		for input in self.inputs
+		let mut page = 0;
+		let mut start = 0;
+		for end in self.page_breaks.iter().map(|&b| b as u32).chain([self.inputs.len() as u32])
 		{
-			self.input_undraw(input, y);
-			y += 1;
+			if idx < end
+			{
+				let first = start + (idx - start) / INPUTS_PER_PAGE * INPUTS_PER_PAGE;
+				return (page + (idx - start) / INPUTS_PER_PAGE, first, u32::min(end - first, INPUTS_PER_PAGE));
+			}
+
+			page += (end - start).div_ceil(INPUTS_PER_PAGE);
+			start = end;
 		}
+
+		(page, start, 0)
 	}
 
-	fn cd_render(&mut self, inputs: &'static [&Input])
+	/* Row of the input on its page */
+	fn cd_row(&self, idx: u32) -> u32
+	{
+		idx - self.cd_page(idx).1
+	}
+
+	fn cd_pages(&self) -> u32
+	{
+		self.cd_page(self.inputs.len() as u32 - 1).0 + 1
+	}
+
+	fn cd_page_indicator(&self, color: u16)
+	{
+		if self.cd_pages() < 2
+		{
+			return;
+		}
+
+		let mut a: [u8; 16] = [0; 16];
+		let mut buf = ByteMutWriter::new(&mut a);
+		write!(buf, "{}/{}", self.cd_page(self.cd_selected).0 + 1, self.cd_pages()).unwrap();
+		lcd_str(MA_BOTTOM_TEXT_X, ACTION_ICONS_Y + 1, buf.as_str(),
+			color, LCD_BLACK, &TERMINUS16);
+	}
+
+	/* Undraw the page holding idx */
+	fn cd_page_undraw(&mut self, idx: u32)
+	{
+		let (_, first, len) = self.cd_page(idx);
+		for idx in first..(first + len)
+		{
+			self.input_undraw(self.inputs[idx as usize], idx);
+		}
+
+		self.cd_page_indicator(LCD_BLACK);
+	}
+
+	fn cd_page_render(&mut self)
+	{
+		let (_, first, len) = self.cd_page(self.cd_selected);
+		for idx in first..(first + len)
+		{
+			self.input_render(self.inputs[idx as usize], idx);
+		}
+
+		self.cd_page_indicator(LCD_WHITE);
+	}
+
+	fn cd_undraw(&mut self)
+	{
+		self.cd_page_undraw(self.cd_selected);
+	}
+
+	/* Reset the decoder selections, unless the same decoder is opened again */
+	fn cd_defaults(&mut self, inputs: &'static [&Input])
 	{
-		self.cd_selected = 0;
 		let same_inputs = core::ptr::addr_eq(self.inputs as *const _, inputs as *const _);
-		self.inputs = inputs;
-		self.actions_set(&ACTIONS_CD);
-		for (y, input) in inputs.iter().enumerate()
+		if !same_inputs || matches!(self.cur_decoder, DecoderUnion::None)
 		{
-			if !same_inputs || matches!(self.cur_decoder, DecoderUnion::None)
+			for (y, input) in inputs.iter().enumerate()
 			{
 				self.sels[y] = input.default_val;
 			}
-
-			self.input_render(input, y as u32);
 		}
 	}
 
+	fn cd_render(&mut self, inputs: &'static [&Input])
+	{
+		self.cd_render_pages(inputs, &[]);
+	}
+
+	fn cd_render_pages(&mut self, inputs: &'static [&Input], page_breaks: &'static [usize])
+	{
+		self.cd_selected = 0;
+		self.inputs = inputs;
+		self.page_breaks = page_breaks;
+		self.actions_set(&ACTIONS_CD);
+		self.cd_page_render();
+	}
+
 	fn cd_action(&mut self, action: Action)
 	{
 		match action
@@ -1103,15 +2224,21 @@ impl Gui
 		}
 	}
 
-	fn decoder_done(&mut self, decoder: DecoderUnion)
+	fn settings_save(&mut self)
 	{
 		let s = "Saving ...";
 		Self::draw_config_saved(0, LCD_GREEN, s);
-		DecoderStorage::save(&mut self.hw.user_flash, &decoder, &self.sels);
-		self.cur_decoder = decoder;
+		DecoderStorage::save(&mut self.hw.user_flash, &self.cur_decoder,
+			&self.sels, &self.trigger, &self.filter, &self.stop, self.channels);
 		Self::draw_config_saved(0, LCD_BLACK, s);
 
 		Self::draw_config_saved_animation();
+	}
+
+	fn decoder_done(&mut self, decoder: DecoderUnion)
+	{
+		self.cur_decoder = decoder;
+		self.settings_save();
 		self.run_decoder();
 		self.mode_switch(Mode::Main);
 	}
@@ -1132,6 +2259,7 @@ impl Gui
 	fn u_open(&mut self)
 	{
 		self.title_set("UART Decoder");
+		self.cd_defaults(&UART_INPUTS);
 		self.cd_render(&UART_INPUTS);
 	}
 
@@ -1165,6 +2293,7 @@ impl Gui
 	fn s_open(&mut self)
 	{
 		self.title_set("SPI Decoder");
+		self.cd_defaults(&SPI_INPUTS);
 		self.cd_render(&SPI_INPUTS);
 	}
 
@@ -1185,8 +2314,10 @@ impl Gui
 			mosi_pin: item_to_pin(self.sels[1].into()),
 			sck_pin: item_to_pin(self.sels[2].into()),
 			cs_pin: item_to_pin(self.sels[3].into()),
-			mode: item_to_spimode(self.sels[4].into()),
-			bitorder: item_to_bitorder(self.sels[5].into()),
+			cs_polarity: item_to_cs_polarity(self.sels[4].into()),
+			mode: item_to_spimode(self.sels[5].into()),
+			bitorder: item_to_bitorder(self.sels[6].into()),
+			word_size: item_to_word_size(self.sels[7].into())
 		};
 
 		if !d.is_valid() { Self::invalid_input(); return; }
@@ -1194,10 +2325,53 @@ impl Gui
 		self.decoder_done(x);
 	}
 
+	/* === QSPI (Q) MODE === */
+	fn q_open(&mut self)
+	{
+		self.title_set("Dual/Quad SPI Decoder");
+		self.cd_defaults(&QSPI_INPUTS);
+		self.cd_render(&QSPI_INPUTS);
+	}
+
+	fn q_action(&mut self, action: Action)
+	{
+		match action
+		{
+			Action::Check => self.q_save(),
+			_ => self.cd_action(action)
+		};
+	}
+
+	fn q_save(&mut self)
+	{
+		let d = DecoderQSPI
+		{
+			io_pins: [
+				item_to_pin(self.sels[0].into()),
+				item_to_pin(self.sels[1].into()),
+				item_to_pin(self.sels[2].into()),
+				item_to_pin(self.sels[3].into())
+			],
+			sck_pin: item_to_pin(self.sels[4].into()),
+			cs_pin: item_to_pin(self.sels[5].into()),
+			cs_polarity: item_to_cs_polarity(self.sels[6].into()),
+			mode: item_to_spimode(self.sels[7].into()),
+			command_bytes: self.sels[8],
+			command_lanes: item_to_lanes(self.sels[9].into()),
+			data_lanes: item_to_lanes(self.sels[10].into()),
+			data_phase: item_to_data_phase(self.sels[11].into())
+		};
+
+		if !d.is_valid() { Self::invalid_input(); return; }
+		let x = DecoderUnion::QSPI(d);
+		self.decoder_done(x);
+	}
+
 	/* === I2C (I) MODE === */
 	fn i_open(&mut self)
 	{
 		self.title_set("I2C Decoder");
+		self.cd_defaults(&I2C_INPUTS);
 		self.cd_render(&I2C_INPUTS);
 	}
 
@@ -1227,6 +2401,7 @@ impl Gui
 	fn o_open(&mut self)
 	{
 		self.title_set("OneWire Decoder");
+		self.cd_defaults(&ONEWIRE_INPUTS);
 		self.cd_render(&ONEWIRE_INPUTS);
 	}
 
@@ -1251,6 +2426,133 @@ impl Gui
 		self.decoder_done(x);
 	}
 
+	/* === TRIGGER (T) MODE === */
+	fn t_open(&mut self)
+	{
+		self.title_set("Capture Settings");
+		self.trigger_sels[0] = self.trigger.kind as u8;
+		self.trigger_sels[1] = self.trigger.channel as u8;
+		self.trigger_sels[2] = POST_TRIGGER.iter()
+			.position(|&p| p == self.trigger.post_trigger)
+			.unwrap_or(0) as u8;
+		self.trigger_sels[3] = self.capture_mode as u8;
+		self.trigger_sels[4] = self.stop.kind as u8;
+		self.trigger_sels[5] = STOP_TIME_US.iter()
+			.position(|&t| t == self.stop.time_us)
+			.unwrap_or(0) as u8;
+		self.trigger_sels[6] = STOP_COUNT.iter()
+			.position(|&c| c == self.stop.count)
+			.unwrap_or(0) as u8;
+		self.trigger_sels[7] = self.timebase as u8;
+		pattern_to_items(self.trigger.mask, self.trigger.value,
+			&mut self.trigger_sels[TRIGGER_PATTERN_FIRST..]);
+		pattern_to_items(self.stop.mask, self.stop.value,
+			&mut self.trigger_sels[TRIGGER_STOP_FIRST..]);
+		for ch in 0..CHANNELS as usize
+		{
+			self.trigger_sels[TRIGGER_FILTER_FIRST + ch] = (0..GLITCH_WIDTH_NS.len())
+				.position(|i| item_to_glitch_width(i) == self.filter.min_width[ch])
+				.unwrap_or(0) as u8;
+			self.trigger_sels[TRIGGER_ENABLE_FIRST + ch] = (self.channels & (1 << ch) == 0) as u8;
+		}
+
+		self.cd_render_pages(&TRIGGER_INPUTS, &TRIGGER_PAGE_BREAKS);
+	}
+
+	fn t_action(&mut self, action: Action)
+	{
+		match action
+		{
+			Action::Check => self.t_save(),
+			_ => self.cd_action(action)
+		};
+	}
+
+	fn t_save(&mut self)
+	{
+		let (mask, value) = items_to_pattern(&self.trigger_sels[TRIGGER_PATTERN_FIRST..]);
+		let mut filter = GlitchFilter::default();
+		for ch in 0..CHANNELS as usize
+		{
+			filter.min_width[ch] =
+				item_to_glitch_width(self.trigger_sels[TRIGGER_FILTER_FIRST + ch].into());
+		}
+
+		let t = Trigger
+		{
+			kind: item_to_trigger_kind(self.trigger_sels[0].into()),
+			channel: item_to_pin(self.trigger_sels[1].into()),
+			mask,
+			value,
+			post_trigger: item_to_post_trigger(self.trigger_sels[2].into())
+		};
+
+		let (mask, value) = items_to_pattern(&self.trigger_sels[TRIGGER_STOP_FIRST..]);
+		let stop = StopCondition
+		{
+			kind: item_to_stop_kind(self.trigger_sels[4].into()),
+			time_us: item_to_stop_time(self.trigger_sels[5].into()),
+			count: item_to_stop_count(self.trigger_sels[6].into()),
+			mask,
+			value
+		};
+
+		/* A trigger on a disabled channel would never fire */
+		let channels = items_to_channels(&self.trigger_sels[TRIGGER_ENABLE_FIRST..]);
+		if !t.is_valid() || !stop.is_valid() || t.channels() & !channels != 0
+		{
+			Self::invalid_input();
+			return;
+		}
+
+		self.channels = channels;
+		self.trigger = t;
+		self.filter = filter;
+		self.stop = stop;
+		self.capture_mode = item_to_capture_mode(self.trigger_sels[3].into());
+		self.timebase = item_to_timebase(self.trigger_sels[7].into());
+		self.settings_save();
+		self.mode_switch(Mode::Main);
+	}
+
+	/* === GENERATOR (G) MODE === */
+	fn g_open(&mut self)
+	{
+		self.title_set("Pattern Generator");
+		self.cd_render(&GEN_INPUTS);
+	}
+
+	fn g_action(&mut self, action: Action)
+	{
+		match action
+		{
+			Action::Check => self.g_send(),
+			_ => self.cd_action(action)
+		};
+	}
+
+	/* Stays in the dialog, so the same bytes can be sent again */
+	fn g_send(&mut self)
+	{
+		let gen = Generator
+		{
+			protocol: item_to_gen_protocol(self.gen_sels[0].into()),
+			data_pin: self.gen_sels[1],
+			clock_pin: self.gen_sels[2],
+			cs_pin: self.gen_sels[3],
+			bitrate: item_to_bitrate(self.gen_sels[4].into())
+		};
+
+		if !gen.is_valid() { Self::invalid_input(); return; }
+		let len = self.gen_sels[5] as usize + 1;
+		let data = &self.gen_sels[GEN_BYTE_FIRST..GEN_BYTE_FIRST + len];
+
+		let s = "Sending ...";
+		Self::draw_config_saved(0, LCD_GREEN, s);
+		player::play(&gen, data, &mut || {});
+		Self::draw_config_saved(0, LCD_BLACK, s);
+	}
+
 	/* === MAIN (MA) MODE === */
 	fn zoomlevel_draw(&self)
 	{
@@ -1269,6 +2571,95 @@ impl Gui
 			LCD_WHITE, LCD_BLACK, &TERMINUS16);
 	}
 
+	fn resolution_draw(&self)
+	{
+		if self.buf.overrun
+		{
+			lcd_str(MA_RESOLUTION_X, ACTION_ICONS_Y + 1, "Overrun",
+				LCD_RED, LCD_BLACK, &TERMINUS16);
+			return;
+		}
+
+		if self.repeat && self.captures > 0
+		{
+			let mut a: [u8; 16] = [0; 16];
+			let mut buf = ByteMutWriter::new(&mut a);
+			write!(buf, "#{} Err: {}", self.captures.min(9999),
+				self.error_captures.min(9999)).unwrap();
+			let color = if self.error_captures > 0 { LCD_RED } else { LCD_WHITE };
+			lcd_str(MA_RESOLUTION_X, ACTION_ICONS_Y + 1, buf.as_str(),
+				color, LCD_BLACK, &TERMINUS16);
+			return;
+		}
+
+		if self.buf.glitches > 0
+		{
+			let mut a: [u8; 16] = [0; 16];
+			let mut buf = ByteMutWriter::new(&mut a);
+			write!(buf, "{} Glitches", self.buf.glitches.min(99_999)).unwrap();
+			lcd_str(MA_RESOLUTION_X, ACTION_ICONS_Y + 1, buf.as_str(),
+				LCD_YELLOW, LCD_BLACK, &TERMINUS16);
+			return;
+		}
+
+		if self.buf.resolution == 0
+		{
+			return;
+		}
+
+		let mut a: [u8; 16] = [0; 16];
+		let mut buf = ByteMutWriter::new(&mut a);
+		let ns = self.buf.resolution * 1000 / self.buf.timebase.ticks_per_us();
+		if ns % 1000 == 0
+		{
+			write!(buf, "Res: {} µs", ns / 1000).unwrap();
+		}
+		else
+		{
+			write!(buf, "Res: {} ns", ns).unwrap();
+		}
+
+		lcd_str(MA_RESOLUTION_X, ACTION_ICONS_Y + 1, buf.as_str(),
+			LCD_WHITE, LCD_BLACK, &TERMINUS16);
+	}
+
+	fn stream_result_draw(&self, r: &StreamResult)
+	{
+		let mut a: [u8; 16] = [0; 16];
+		let mut buf = ByteMutWriter::new(&mut a);
+		if r.overrun
+		{
+			write!(buf, "Overrun").unwrap();
+		}
+		else if r.lost > 0
+		{
+			write!(buf, "{} lost", r.lost).unwrap();
+		}
+		else
+		{
+			write!(buf, "{} sent", r.edges).unwrap();
+		}
+
+		let color = if r.overrun || r.lost > 0 { LCD_RED } else { LCD_WHITE };
+		lcd_str(MA_RESOLUTION_X, ACTION_ICONS_Y + 1, buf.as_str(),
+			color, LCD_BLACK, &TERMINUS16);
+	}
+
+	fn sump_result_draw(&self, runs: u32)
+	{
+		let mut a: [u8; 16] = [0; 16];
+		let mut buf = ByteMutWriter::new(&mut a);
+		write!(buf, "{} sent", runs).unwrap();
+		lcd_str(MA_RESOLUTION_X, ACTION_ICONS_Y + 1, buf.as_str(),
+			LCD_WHITE, LCD_BLACK, &TERMINUS16);
+	}
+
+	fn resolution_undraw(&self)
+	{
+		lcd_rect(MA_RESOLUTION_X, ACTION_ICONS_Y + 1,
+			TERMINUS16.width * 15, TERMINUS16.height, LCD_BLACK);
+	}
+
 	fn zoomlevel_undraw(&self)
 	{
 		lcd_rect(MA_BOTTOM_TEXT_X, ACTION_ICONS_Y + 1,
@@ -1283,30 +2674,36 @@ impl Gui
 
 	fn sidebar_render_decoder_pins(&self)
 	{
-		let decoder: &dyn Decoder = match &self.cur_decoder
+		let Some(decoder) = self.cur_decoder.decoder() else
 		{
-			DecoderUnion::None => return,
-			DecoderUnion::Uart(dcd) => dcd,
-			DecoderUnion::SPI(dcd) => dcd,
-			DecoderUnion::I2C(dcd) => dcd,
-			DecoderUnion::OneWire(dcd) => dcd
+			return;
 		};
 
 		let mut i = 0;
 		while let Some((text, pin_num)) = decoder.get_pin(i)
 		{
-			let y = WAVEFORMS_Y + WAVEFORM_PIN_Y + pin_num * WAVEFORM_SPACING;
-			lcd_str(0, y, text, LCD_WHITE, LCD_BLACK, &TINYFONT);
 			i += 1;
+			if pin_num / BANK_SIZE != self.bank
+			{
+				continue;
+			}
+
+			let row = pin_num % BANK_SIZE;
+			let y = WAVEFORMS_Y + WAVEFORM_PIN_Y + row * WAVEFORM_SPACING;
+			lcd_str(0, y, text, LCD_WHITE, LCD_BLACK, &TINYFONT);
 		}
 	}
 
 	fn sidebar_render(&self)
 	{
-		for i in 0..8
+		for i in 0..BANK_SIZE
 		{
 			let y = WAVEFORMS_Y + i * WAVEFORM_SPACING;
-			lcd_char(CHANNEL_LABEL_WIDTH / 2, y, '0' as u32 + i, LCD_WHITE, LCD_BLACK, &TERMINUS16);
+			let ch = self.bank * BANK_SIZE + i;
+			let label = SELECT_PIN_LIST[ch as usize];
+			let fg = if self.buf.is_enabled(ch) { LCD_WHITE } else { COLOR_DISABLED };
+			lcd_str(CHANNEL_LABEL_WIDTH - 5 - TERMINUS16.width(label), y, label,
+				fg, LCD_BLACK, &TERMINUS16);
 			lcd_hline(0, y, CHANNEL_LABEL_WIDTH, LCD_WHITE);
 		}
 
@@ -1316,15 +2713,22 @@ impl Gui
 			LCD_HEIGHT - ((ICON_BOX + 1) * 2), LCD_WHITE);
 	}
 
-	fn waveform_render(&mut self, s: usize, e: usize, ch: u32)
+	// Disabled channels were not recorded and stay empty
+	fn waveform_render(&mut self, s: usize, e: usize, row: u32)
 	{
+		let ch = self.bank * BANK_SIZE + row;
+		if !self.buf.is_enabled(ch)
+		{
+			return;
+		}
+
 		let mut prev = self.buf.get(s, ch);
 		for i in s..=e
 		{
 			let cur = self.buf.get(i, ch);
 			let x0 = t_to_x(prev.1, self.t_start, self.t_end);
 			let x1 = t_to_x(cur.1, self.t_start, self.t_end);
-			self.wf.line(ch, x0, x1, prev.0);
+			self.wf.line(row, x0, x1, prev.0);
 			prev = cur;
 		}
 	}
@@ -1340,9 +2744,9 @@ impl Gui
 
 		let s = self.buf.find_start(self.t_start);
 		let e = self.buf.find_end(self.t_end);
-		for ch in 0..8
+		for row in 0..BANK_SIZE
 		{
-			self.waveform_render(s, e, ch);
+			self.waveform_render(s, e, row);
 		}
 
 		self.wf.update();
@@ -1350,10 +2754,12 @@ impl Gui
 
 	fn ma_render(&mut self, i: u32, sel: bool)
 	{
-		const ICONS: [Icon; MA_ICONS as usize] = [ Icon::Start, Icon::Add, Icon::Info ];
+		let mode = if self.repeat { Icon::Repeat } else { Icon::Single };
+		let icons: [Icon; MA_ICONS as usize] = [ Icon::Start, mode, Icon::Add, Icon::Trigger,
+			Icon::Generator, Icon::Info ];
 		let fg = if sel { COLOR_SEL } else { LCD_WHITE };
 		let x = LCD_WIDTH - (MA_ICONS - i) * (ICON_BOX + 1) + ICON_PADDING;
-		lcd_icon_color(x, ICON_PADDING, ICONS[i as usize], fg, LCD_BLACK);
+		lcd_icon_color(x, ICON_PADDING, icons[i as usize], fg, LCD_BLACK);
 	}
 
 	fn ma_top_box(&mut self)
@@ -1374,7 +2780,8 @@ impl Gui
 
 	fn update_indicator(&mut self)
 	{
-		self.pi.show(self.t_start, self.t_end, self.last_ts());
+		self.pi.show(self.t_start, self.t_end, self.last_ts(), self.buf.event,
+			self.buf.timebase.ticks_per_us());
 	}
 
 	fn ma_open(&mut self)
@@ -1386,6 +2793,7 @@ impl Gui
 		self.sidebar_render();
 		self.waveforms_render();
 		self.zoomlevel_draw();
+		self.resolution_draw();
 		self.update_indicator();
 	}
 
@@ -1394,6 +2802,7 @@ impl Gui
 		self.decoder_framebuf.clear();
 		self.sidebar_clear();
 		self.zoomlevel_undraw();
+		self.resolution_undraw();
 		self.wf.undraw();
 		self.pi.hide();
 		for i in 0..MA_ICONS
@@ -1408,8 +2817,12 @@ impl Gui
 
 	fn ma_running(&mut self)
 	{
+		let text = if self.trigger.is_armed() { "ARMED" }
+			else if self.capture_mode == CaptureMode::Stream { "STREAM" }
+			else if self.capture_mode == CaptureMode::Sump { "SUMP" }
+			else { "RUNNING" };
 		lcd_icon_color(4, ACTION_ICONS_Y, Icon::Dot, LCD_GREEN, LCD_BLACK);
-		lcd_str(MA_BOTTOM_TEXT_X, ACTION_ICONS_Y + 1, "RUNNING",
+		lcd_str(MA_BOTTOM_TEXT_X, ACTION_ICONS_Y + 1, text,
 			LCD_WHITE, LCD_BLACK, &TERMINUS16_BOLD);
 	}
 
@@ -1422,43 +2835,115 @@ impl Gui
 	fn run_decoder(&mut self)
 	{
 		self.sec_buf.clear();
-		let decoder: &dyn Decoder = match &self.cur_decoder
+		let Some(decoder) = self.cur_decoder.decoder() else
 		{
-			DecoderUnion::None => return,
-			DecoderUnion::Uart(dcd) => dcd,
-			DecoderUnion::SPI(dcd) => dcd,
-			DecoderUnion::I2C(dcd) => dcd,
-			DecoderUnion::OneWire(dcd) => dcd
+			return;
 		};
 
 		let _ = decoder.decode(&self.buf, &mut self.sec_buf);
 	}
 
-	fn ma_run(&mut self)
+	fn ma_stream(&mut self)
 	{
+		self.resolution_undraw();
 		self.ma_running();
 		self.actions_set(&ACTIONS_SAMPLING);
-		sampler::sample_blocking(&mut self.buf);
+		let r = sampler::stream_blocking(&self.trigger, self.timebase, self.channels);
 		self.actions_set(&ACTIONS_MAIN);
 		self.ma_running_undraw();
+		self.stream_result_draw(&r);
+	}
+
+	// One capture, shows it and counts it for a repeated capture
+	fn ma_capture(&mut self) -> CaptureEnd
+	{
+		self.resolution_undraw();
+		self.ma_running();
+		let frames = FrameCheck::new(&self.stop, self.timebase, self.cur_decoder.decoder(), &mut self.sec_buf);
+		let end = sampler::sample_blocking(&mut self.buf, &self.trigger, &self.filter,
+			&self.stop, frames, self.timebase, self.channels);
+		self.ma_running_undraw();
 		self.run_decoder();
-		self.zoom = 0;
-		self.t_start = 0;
+		if self.stop.cut_frames(&mut self.buf, &self.sec_buf)
+		{
+			self.run_decoder();
+		}
+
+		if end != CaptureEnd::Aborted
+		{
+			self.captures += 1;
+			if self.sec_buf.has_errors()
+			{
+				self.error_captures += 1;
+			}
+		}
+
+		self.resolution_draw();
+		self.ma_fit_capture();
+		end
+	}
+
+	// Zoom out until the whole capture fits
+	fn ma_fit_capture(&mut self)
+	{
+		self.zoom = ZOOM_DEFAULT;
+		while self.zoom > 0 && self.zoomlevel_to_ticks() < self.last_ts()
+		{
+			self.zoom -= 1;
+		}
+
+		self.t_start = match self.buf.event
+		{
+			/* Center the event */
+			Some(t) => t.saturating_sub(self.zoomlevel_to_ticks() / 2),
+			None => 0
+		};
 		self.zoomlevel_update();
 		self.update_indicator();
-		self.write_buf_as_csv();
 	}
 
-	fn write_buf_as_csv(&mut self)
+	// Serves a SUMP client until Stop, the last capture is shown
+	fn ma_sump(&mut self)
 	{
-		writeln!(self.hw.tx, "Timestamp,Data").unwrap();
-		for i in 0..self.buf.len
+		self.resolution_undraw();
+		self.ma_running();
+		self.actions_set(&ACTIONS_SAMPLING);
+		let runs = sampler::sump_blocking(&mut self.buf, &self.filter, self.channels);
+		self.actions_set(&ACTIONS_MAIN);
+		self.ma_running_undraw();
+		if runs > 0
 		{
-			let sample_data = self.buf.samples[i];
-			let sample_ts = self.buf.timestamps[i];
+			self.run_decoder();
+			self.ma_fit_capture();
+		}
+
+		self.sump_result_draw(runs);
+	}
 
-			writeln!(self.hw.tx, "{},{}", sample_ts, sample_data).unwrap();
+	fn ma_run(&mut self)
+	{
+		match self.capture_mode
+		{
+			CaptureMode::Stream => { self.ma_stream(); return; }
+			CaptureMode::Sump => { self.ma_sump(); return; }
+			CaptureMode::Buffer => {}
 		}
+
+		/* Repeat mode rearms after each capture until Stop is pressed */
+		self.captures = 0;
+		self.error_captures = 0;
+		self.actions_set(&ACTIONS_SAMPLING);
+		while self.ma_capture() == CaptureEnd::Done && self.repeat {}
+		self.actions_set(&ACTIONS_MAIN);
+		sampler::transfer_blocking(&self.buf, &self.sec_buf);
+	}
+
+	fn ma_toggle_repeat(&mut self)
+	{
+		self.repeat = !self.repeat;
+		self.ma_render(1, true);
+		self.resolution_undraw();
+		self.resolution_draw();
 	}
 
 	fn ma_enter(&mut self)
@@ -1466,20 +2951,24 @@ impl Gui
 		match self.ma_selected
 		{
 			0 => { self.ma_run(); }
-			1 => { self.mode_switch(Mode::DecoderAdd); }
-			2 => { self.mode_switch(Mode::Info); }
+			1 => { self.ma_toggle_repeat(); }
+			2 => { self.mode_switch(Mode::DecoderAdd); }
+			3 => { self.mode_switch(Mode::Trigger); }
+			4 => { self.mode_switch(Mode::Generator); }
+			5 => { self.mode_switch(Mode::Info); }
 			_ => {}
 		}
 	}
 
-	fn zoomlevel_to_ticks(&self) -> u32
+	fn zoomlevel_to_ticks(&self) -> Timestamp
 	{
 		let l = &ZOOM_LEVELS[self.zoom];
-		l.value * match l.unit
+		let ticks_per_us = self.buf.timebase.ticks_per_us() as Timestamp;
+		l.value as Timestamp * match l.unit
 		{
-			TimeUnit::Second => hw::TICKS_PER_US * 1_000_000,
-			TimeUnit::Millisecond => hw::TICKS_PER_US * 1_000,
-			TimeUnit::Microsecond => hw::TICKS_PER_US
+			TimeUnit::Second => ticks_per_us * 1_000_000,
+			TimeUnit::Millisecond => ticks_per_us * 1_000,
+			TimeUnit::Microsecond => ticks_per_us
 		}
 	}
 
@@ -1490,18 +2979,18 @@ impl Gui
 		self.waveforms_render();
 	}
 
-	fn last_ts(&self) -> u32
+	fn last_ts(&self) -> Timestamp
 	{
-		if self.buf.len == 0 { 0 } else { self.buf.timestamps[self.buf.len - 1] }
+		if self.buf.len == 0 { 0 } else { self.buf.timestamp(self.buf.len - 1) }
 	}
 
-	fn max_horizontal_scroll(&self) -> u32
+	fn max_horizontal_scroll(&self) -> Timestamp
 	{
 		let last = self.last_ts();
 		last.saturating_sub(self.t_end)
 	}
 
-	fn horizontal_scroll_amount(&self) -> u32
+	fn horizontal_scroll_amount(&self) -> Timestamp
 	{
 		(self.t_end - self.t_start) / 4
 	}
@@ -1516,6 +3005,11 @@ impl Gui
 			}
 
 			self.cursors.action(action, &self.wf, &self.buf);
+			if !self.cursors.en
+			{
+				self.resolution_draw();
+			}
+
 			return;
 		}
 
@@ -1523,12 +3017,13 @@ impl Gui
 		{
 			Action::Cursors =>
 			{
-				self.cursors.show(self.t_start, self.t_end);
+				self.resolution_undraw();
+				self.cursors.show(self.t_start, self.t_end, self.buf.timebase.ticks_per_us());
 				self.actions_set(&ACTIONS_CURSORS);
 			}
 			Action::Left =>
 			{
-				let amount = u32::min(self.horizontal_scroll_amount(), self.t_start);
+				let amount = Timestamp::min(self.horizontal_scroll_amount(), self.t_start);
 				self.t_start -= amount;
 				self.t_end -= amount;
 				self.waveforms_render();
@@ -1536,7 +3031,7 @@ impl Gui
 			}
 			Action::Right =>
 			{
-				let amount = u32::min(self.horizontal_scroll_amount(), self.max_horizontal_scroll());
+				let amount = Timestamp::min(self.horizontal_scroll_amount(), self.max_horizontal_scroll());
 				self.t_start += amount;
 				self.t_end += amount;
 				self.waveforms_render();
@@ -1560,6 +3055,13 @@ impl Gui
 				self.ma_selected = cycle_fwd(self.ma_selected, MA_ICONS);
 				self.ma_update(prev);
 			}
+			Action::Bank =>
+			{
+				self.bank = cycle_fwd(self.bank, CHANNELS / BANK_SIZE);
+				self.sidebar_clear();
+				self.sidebar_render();
+				self.waveforms_render();
+			}
 			Action::Enter =>
 			{
 				self.ma_enter();
@@ -1575,16 +3077,17 @@ impl Gui
 		{
 			0 => { self.mode_switch(Mode::DecoderUart);    },
 			1 => { self.mode_switch(Mode::DecoderSpi);     },
-			2 => { self.mode_switch(Mode::DecoderI2C);     },
-			3 => { self.mode_switch(Mode::DecoderOneWire); },
-			4 => { self.decoder_done(DecoderUnion::None);  },
+			2 => { self.mode_switch(Mode::DecoderQspi);    },
+			3 => { self.mode_switch(Mode::DecoderI2C);     },
+			4 => { self.mode_switch(Mode::DecoderOneWire); },
+			5 => { self.decoder_done(DecoderUnion::None);  },
 			_ => {}
 		}
 	}
 
 	fn da_button(&self, idx: u32) -> Button
 	{
-		const LABELS: [&str; DECODER_COUNT as usize] = [ "UART", "SPI", "I2C", "OneWire", "None" ];
+		const LABELS: [&str; DECODER_COUNT as usize] = [ "UART", "SPI", "QSPI", "I2C", "OneWire", "None" ];
 		Button
 		{
 			x: DA_PADDING,
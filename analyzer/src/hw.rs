@@ -3,11 +3,15 @@ use stm32f4xx_hal::spi::{Polarity, Mode, Phase, Spi};
 use stm32f4xx_hal::pac::RCC;
 use stm32f4xx_hal::uart::{Config, Serial};
 use core::fmt::Write;
-use stm32f4xx_hal::pac::{GPIOA, GPIOD, GPIOE, GPIOF, GPIOG, TIM2, USART3, Peripherals};
+use stm32f4xx_hal::pac::{GPIOA, GPIOD, GPIOE, GPIOF, GPIOG, TIM2, USART3, Interrupt, Peripherals};
 use stm32f4xx_hal::serial::Tx;
-use core::ptr::{read_volatile, write_volatile};
+use stm32f4xx_hal::interrupt;
+use cortex_m::peripheral::NVIC;
+use core::ptr::{addr_of_mut, read_volatile, write_volatile};
+use core::sync::atomic::{AtomicU8, AtomicUsize, Ordering};
 use crate::delay::delay_us;
 use crate::userflash::UserFlash;
+use crate::arena::Arena;
 
 const LCD_RST: u32 = 12;
 const LCD_DC: u32 = 13;
@@ -37,6 +41,22 @@ const SPI_SR_RXNE     : u32 = 1 << 0;
 const SPI_SR_TXE      : u32 = 1 << 1;
 const SPI_SR_BSY      : u32 = 1 << 7;
 
+const USART3_BASE     : u32 = PERIPH_BASE + 0x4800;
+const USART3_SR       : u32 = USART3_BASE + 0x00;
+const USART3_DR       : u32 = USART3_BASE + 0x04;
+
+const USART_SR_TC     : u32 = 1 << 6;
+const USART_SR_TXE    : u32 = 1 << 7;
+
+// Received bytes kept while the main loop is busy, e.g. redrawing the LCD
+// or decoding, several command frames
+const RX_LEN: usize = 256;
+
+// Filled by the USART3 interrupt, emptied by serial_try_read
+static RX_BUF: [AtomicU8; RX_LEN] = [const { AtomicU8::new(0) }; RX_LEN];
+static RX_HEAD: AtomicUsize = AtomicUsize::new(0);
+static RX_TAIL: AtomicUsize = AtomicUsize::new(0);
+
 pub fn hw_init() -> HW
 {
 	let dp = Peripherals::take().unwrap();
@@ -132,16 +152,20 @@ pub fn hw_init() -> HW
 		&clocks,
 	);
 
+	/* RX is buffered by the USART3 interrupt, USART DR holds only one byte */
 	let tx_pin = gpiod.pd8.into_alternate();
-	let mut tx = Serial::tx(
+	let rx_pin = gpiod.pd9.into_alternate();
+	let (mut tx, mut rx) = Serial::new(
 		dp.USART3,
-		tx_pin,
+		(tx_pin, rx_pin),
 		Config::default()
 			.baudrate(115200.bps())
 			.wordlength_8()
 			.parity_none(),
 		&clocks,
-	).unwrap();
+	).unwrap().split();
+	rx.listen();
+	unsafe { NVIC::unmask(Interrupt::USART3); }
 
 	let raw_flash   = dp.FLASH;
 	let locked_flash = LockedFlash::new(raw_flash);
@@ -153,6 +177,19 @@ pub fn hw_init() -> HW
 	HW { user_flash, spi, tx }
 }
 
+extern "C"
+{
+	/* Free part of the capture region, see memory.x */
+	static mut _scapture: u8;
+	static mut _ecapture: u8;
+}
+
+// Memory for the capture buffers, may only be taken once
+pub fn capture_memory() -> Arena
+{
+	unsafe { Arena::new(addr_of_mut!(_scapture), addr_of_mut!(_ecapture)) }
+}
+
 pub fn timer_get() -> u32
 {
 	unsafe { (*TIM2::ptr()).cnt().read().bits() }
@@ -163,6 +200,17 @@ pub fn timer_set(v: u32)
 	unsafe { (*TIM2::ptr()).cnt().write(|w| w.bits(v)); }
 }
 
+// Divide the timer clock by psc + 1, TICKS_PER_US only holds for 0.
+// The update event loads the prescaler at once and resets the counter.
+pub fn timer_prescale(psc: u32)
+{
+	unsafe
+	{
+		(*TIM2::ptr()).psc().write(|w| w.bits(psc));
+		(*TIM2::ptr()).egr().write(|w| w.bits(1));
+	}
+}
+
 pub fn spi_xchg(val: u8) -> u8
 {
 	unsafe
@@ -178,6 +226,54 @@ pub fn spi_xchg(val: u8) -> u8
 	}
 }
 
+// Send a byte over USART3 without waiting, false if the transmitter is busy
+pub fn serial_try_write(val: u8) -> bool
+{
+	unsafe
+	{
+		if (read_volatile(USART3_SR as *mut u32) & USART_SR_TXE) == 0
+		{
+			return false;
+		}
+
+		write_volatile(USART3_DR as *mut u32, val.into());
+		true
+	}
+}
+
+// Oldest received byte of USART3 if there is one
+pub fn serial_try_read() -> Option<u8>
+{
+	let tail = RX_TAIL.load(Ordering::Relaxed);
+	if tail == RX_HEAD.load(Ordering::Acquire)
+	{
+		return None;
+	}
+
+	let b = RX_BUF[tail].load(Ordering::Relaxed);
+	RX_TAIL.store((tail + 1) % RX_LEN, Ordering::Release);
+	Some(b)
+}
+
+// Reading DR clears RXNE and an overrun, bytes that find the buffer full are lost
+#[interrupt]
+fn USART3()
+{
+	let b = unsafe { read_volatile(USART3_DR as *mut u32) as u8 };
+	let head = RX_HEAD.load(Ordering::Relaxed);
+	let next = (head + 1) % RX_LEN;
+	if next != RX_TAIL.load(Ordering::Acquire)
+	{
+		RX_BUF[head].store(b, Ordering::Relaxed);
+		RX_HEAD.store(next, Ordering::Release);
+	}
+}
+
+pub fn serial_flush()
+{
+	unsafe { while (read_volatile(USART3_SR as *mut u32) & USART_SR_TC) == 0 {} }
+}
+
 pub fn buttons_read() -> u8
 {
 	(unsafe { (*GPIOF::ptr()).idr().read().bits() } & 0xFF) as u8
@@ -200,7 +296,31 @@ pub fn blueinput()
 
 pub fn yellowinput()
 {
-	unsafe { (*GPIOE::ptr()).moder().modify(|r, w| w.bits(r.bits() & !0xFFFF)); }
+	unsafe
+	{
+		(*GPIOE::ptr()).moder().modify(|r, w| w.bits(r.bits() & !0xFFFF));
+		(*GPIOE::ptr()).otyper().modify(|r, w| w.bits(r.bits() & !0xFF));
+	}
+}
+
+// Drive the pins in mask, open drain pins only pull low
+pub fn yellowoutput(mask: u8, open_drain: bool)
+{
+	let mut moder = 0;
+	for i in 0..8
+	{
+		if mask & (1 << i) != 0
+		{
+			moder |= 1 << (2 * i);
+		}
+	}
+
+	unsafe
+	{
+		(*GPIOE::ptr()).otyper().modify(|r, w|
+			w.bits(if open_drain { r.bits() | mask as u32 } else { r.bits() & !(mask as u32) }));
+		(*GPIOE::ptr()).moder().modify(|r, w| w.bits((r.bits() & !0xFFFF) | moder));
+	}
 }
 
 pub fn blueset(val: u8)
@@ -223,6 +343,14 @@ pub fn yellowclear(val: u8)
 	unsafe { (*GPIOE::ptr()).bsrr().write(|w| w.bits((val as u32) << OFFSET_CLEAR)); }
 }
 
+// Set the pins in mask to level with a single write
+pub fn yellowwrite(mask: u8, level: u8)
+{
+	let set = (level & mask) as u32;
+	let clear = (!level & mask) as u32;
+	unsafe { (*GPIOE::ptr()).bsrr().write(|w| w.bits(set | (clear << OFFSET_CLEAR))); }
+}
+
 pub fn lcd_rst_0()
 {
 	unsafe { (*GPIOF::ptr()).bsrr().write(|w| w.bits(1 << (LCD_RST + OFFSET_CLEAR))); }
@@ -0,0 +1,43 @@
+#![cfg_attr(not(any(test, feature = "std")), no_std)]
+// Same rules as in the firmware, where these types were never public
+#![allow(clippy::new_without_default, clippy::result_unit_err, clippy::missing_safety_doc)]
+
+// Everything that does not touch the hardware or the screen: the sample
+// buffers, the protocol decoders and the capture file formats. The firmware
+// and the simulator build on it, host tools can use it with the std feature.
+
+pub mod sample;
+pub mod decoder;
+pub mod decoder_spi;
+pub mod decoder_qspi;
+pub mod decoder_i2c;
+pub mod decoder_onewire;
+pub mod decoder_uart;
+pub mod bit_reader;
+pub mod trigger;
+pub mod glitch;
+pub mod stream;
+pub mod transfer;
+pub mod sump;
+pub mod remote;
+pub mod stop;
+pub mod generator;
+pub mod selftest;
+pub mod samplering;
+pub mod arena;
+
+#[cfg_attr(not(feature = "compress"), path="timestamps.rs")]
+#[cfg_attr(feature = "compress", path="timestamps_packed.rs")]
+pub mod timestamps;
+
+#[cfg(test)]
+pub mod test_utils;
+
+#[cfg(any(test, feature = "std"))]
+pub mod vcd;
+
+#[cfg(any(test, feature = "std"))]
+pub mod capture_file;
+
+#[cfg(any(test, feature = "std"))]
+pub mod sigrok;
@@ -5,15 +5,9 @@
 mod macro_utils;
 mod font;
 mod gui;
-mod sample;
-mod decoder;
 mod terminus16;
 mod terminus16_bold;
 mod bytewriter;
-mod decoder_spi;
-mod decoder_i2c;
-mod decoder_onewire;
-mod decoder_uart;
 mod tinyfont;
 mod timeindicator;
 mod timeline;
@@ -22,7 +16,13 @@ mod cursors;
 mod positionindicator;
 mod decoder_framebuffer;
 mod waveform;
-mod bit_reader;
+
+// Shared with the host tools
+use itsboard_rust::{sample, decoder, decoder_spi, decoder_qspi, decoder_i2c, decoder_onewire, decoder_uart,
+	trigger, glitch, stream, transfer, sump, remote, stop, generator, selftest, arena};
+
+#[cfg(not(feature = "simulator"))]
+use itsboard_rust::{samplering, timestamps};
 
 #[cfg_attr(not(feature = "simulator"), path="delay.rs")]
 #[cfg_attr(feature = "simulator", path="sim_delay.rs")]
@@ -40,6 +40,13 @@ mod lcd;
 #[cfg_attr(feature = "simulator", path="sim_sampler.rs")]
 mod sampler;
 
+#[cfg(not(feature = "simulator"))]
+mod capture;
+
+#[cfg_attr(not(feature = "simulator"), path="player.rs")]
+#[cfg_attr(feature = "simulator", path="sim_player.rs")]
+mod player;
+
 #[cfg_attr(not(feature = "simulator"), path="userflash.rs")]
 #[cfg_attr(feature = "simulator", path="sim_userflash.rs")]
 mod userflash;
@@ -54,14 +61,14 @@ mod sim_main;
 #[cfg(feature = "simulator")]
 mod graphics;
 
-#[cfg(any(test, feature = "simulator"))]
-mod test_utils;
+#[cfg(feature = "simulator")]
+use itsboard_rust::{capture_file, sigrok};
 
 #[cfg_attr(not(feature = "simulator"), path="touch.rs")]
 mod touch;
 
 #[cfg(not(feature = "simulator"))]
-use crate::hw::{hw_init, blueinput, yellowinput, buttons_read, timer_get, TICKS_PER_US};
+use crate::hw::{hw_init, blueinput, yellowinput, buttons_read, serial_try_read, timer_get, TICKS_PER_US};
 
 #[cfg(not(feature = "simulator"))]
 use crate::lcd::{lcd_init, LCD_BLACK};
@@ -100,6 +107,11 @@ fn start() -> !
 	let mut last_check = 0;
 	loop
 	{
+		while let Some(b) = serial_try_read()
+		{
+			gui.remote(b);
+		}
+
 		let t = timer_get();
 		if (t - last_check) >= TICKS_PER_US * 1000
 		{
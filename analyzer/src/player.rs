@@ -0,0 +1,25 @@
+use crate::generator::Generator;
+use crate::hw::{timer_get, yellowinput, yellowoutput, yellowwrite};
+
+// Play the frames on the yellow port, busy waiting on the timer.
+// The deadlines are absolute so the time spent in the generator does not add up.
+// idle is called while waiting and should return well within the shortest step.
+pub fn play(gen: &Generator, data: &[u8], idle: &mut impl FnMut())
+{
+	let mask = gen.pins();
+	yellowwrite(mask, gen.idle());
+	yellowoutput(mask, gen.is_open_drain());
+
+	let mut deadline = timer_get();
+	gen.generate(data, &mut |level, ticks|
+	{
+		yellowwrite(mask, level);
+		deadline = deadline.wrapping_add(ticks);
+		while (timer_get().wrapping_sub(deadline) as i32) < 0
+		{
+			idle();
+		}
+	});
+
+	yellowinput();
+}
@@ -1,5 +1,6 @@
 use crate::timeindicator::TimeIndicator;
 use crate::timeline::TimeLine;
+use crate::sample::Timestamp;
 
 pub struct PositionIndicator
 {
@@ -13,8 +14,8 @@ impl PositionIndicator
 	{
 		PositionIndicator
 		{
-			timeindicator: TimeIndicator::new(290, 6),
-			timeline: TimeLine::new(160, 14, 120, 5)
+			timeindicator: TimeIndicator::new(194, 6),
+			timeline: TimeLine::new(128, 14, 58, 5)
 		}
 	}
 
@@ -24,9 +25,16 @@ impl PositionIndicator
 		self.timeline.hide();
 	}
 
-	pub fn show(&mut self, start: u32, end: u32, max: u32)
+	// event: Timestamp shown as t=0
+	pub fn show(&mut self, start: Timestamp, end: Timestamp, max: Timestamp, event: Option<Timestamp>,
+		ticks_per_us: u32)
 	{
-		self.timeindicator.show(start);
-		self.timeline.show(start, end, max);
+		match event
+		{
+			Some(t) => self.timeindicator.show_signed(start as i64 - t as i64, ticks_per_us),
+			None => self.timeindicator.show(start, ticks_per_us)
+		}
+
+		self.timeline.show(start, end, max, event);
 	}
 }
@@ -0,0 +1,320 @@
+use crate::decoder::DecoderUnion;
+
+/*
+ * Remote control over the serial port, so lab scripts can automate captures.
+ * The PC sends commands, the board answers each with one reply. Both are
+ * postcard encoded and COBS framed like the stream frames, so every frame
+ * ends with a zero byte. A capture sent for Run or Fetch comes in the
+ * transfer format (transfer.rs) in front of the reply.
+ */
+
+#[derive(serde::Serialize, serde::Deserialize)]
+pub enum Command
+{
+	// Current state of the board
+	Status,
+
+	// Capture like the Run button, in buffer mode the capture is sent when done
+	Run,
+
+	// Ends a capture started by Run. Commands received during that capture
+	// are answered after Run, Stop with Ok and all others with Busy
+	Stop,
+
+	// Send the last capture and its decoded sections again
+	Fetch,
+
+	// Use and save these decoder settings, decodes the last capture again
+	SetDecoder(DecoderUnion),
+
+	// Press a button, numbered like the keys from 0 to 7
+	Key(u8)
+}
+
+#[derive(serde::Serialize, serde::Deserialize, Clone, Copy, PartialEq, Eq, Debug)]
+pub enum RemoteError
+{
+	// The frame is no command
+	Invalid,
+
+	// The frame does not fit into the receive buffer
+	TooLong,
+
+	// Only possible on the main screen, or not in SUMP mode
+	Busy,
+
+	// Invalid decoder settings or button
+	Argument
+}
+
+#[derive(serde::Serialize, serde::Deserialize, Clone, Copy, PartialEq, Eq, Default, Debug)]
+pub struct Status
+{
+	// On the main screen, commands other than Status and Key are accepted
+	pub ready: bool,
+
+	// Size of the last capture
+	pub samples: u32,
+	pub sections: u32,
+
+	// Captures of the last run and how many had decoder errors
+	pub captures: u32,
+	pub error_captures: u32
+}
+
+#[derive(serde::Serialize, serde::Deserialize, Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Reply
+{
+	Ok,
+	Status(Status),
+	Error(RemoteError)
+}
+
+// Largest command frame, a decoder configuration takes less than 20 bytes
+pub const COMMAND_MAX: usize = 64;
+
+// Largest encoded reply including COBS overhead and delimiter
+const REPLY_MAX: usize = 32;
+
+// Replies kept for commands received during a capture, later ones are dropped
+const DEFERRED_MAX: usize = 8;
+
+// Collects received bytes into frames and decodes them
+pub struct CommandParser
+{
+	frame: [u8; COMMAND_MAX],
+	len: usize,
+	overflow: bool
+}
+
+impl CommandParser
+{
+	pub fn new() -> Self
+	{
+		CommandParser
+		{
+			frame: [0; COMMAND_MAX],
+			len: 0,
+			overflow: false
+		}
+	}
+
+	// The command once its frame is complete
+	pub fn push(&mut self, b: u8) -> Option<Result<Command, RemoteError>>
+	{
+		if b != 0
+		{
+			if self.len < COMMAND_MAX
+			{
+				self.frame[self.len] = b;
+				self.len += 1;
+			}
+			else
+			{
+				self.overflow = true;
+			}
+
+			return None;
+		}
+
+		let len = core::mem::replace(&mut self.len, 0);
+		if core::mem::replace(&mut self.overflow, false)
+		{
+			return Some(Err(RemoteError::TooLong));
+		}
+
+		/* Empty frames only resync */
+		if len == 0
+		{
+			return None;
+		}
+
+		Some(postcard::from_bytes_cobs(&mut self.frame[..len]).map_err(|_| RemoteError::Invalid))
+	}
+}
+
+// The commands from the serial port. While a capture started by Run is
+// running, Stop ends it and everything else is busy. These replies wait until
+// Run is answered, so the replies keep the order of the commands and never
+// come between the frames of a stream.
+pub struct RemoteLink
+{
+	parser: CommandParser,
+	deferred: [Reply; DEFERRED_MAX],
+	deferred_len: usize,
+	stop: bool
+}
+
+impl RemoteLink
+{
+	pub fn new() -> Self
+	{
+		RemoteLink
+		{
+			parser: CommandParser::new(),
+			deferred: [Reply::Ok; DEFERRED_MAX],
+			deferred_len: 0,
+			stop: false
+		}
+	}
+
+	// The command once its frame is complete
+	pub fn push(&mut self, b: u8) -> Option<Result<Command, RemoteError>>
+	{
+		self.parser.push(b)
+	}
+
+	// A byte received during a capture started by Run
+	pub fn push_running(&mut self, b: u8)
+	{
+		let reply = match self.parser.push(b)
+		{
+			None => return,
+			Some(Ok(Command::Stop)) =>
+			{
+				self.stop = true;
+				Reply::Ok
+			}
+			Some(Ok(_)) => Reply::Error(RemoteError::Busy),
+			Some(Err(e)) => Reply::Error(e)
+		};
+
+		if self.deferred_len < DEFERRED_MAX
+		{
+			self.deferred[self.deferred_len] = reply;
+			self.deferred_len += 1;
+		}
+	}
+
+	// Stop was received during the capture
+	pub fn stopped(&self) -> bool
+	{
+		self.stop
+	}
+
+	// Replies for the commands received during the capture
+	pub fn deferred(&self) -> &[Reply]
+	{
+		&self.deferred[..self.deferred_len]
+	}
+
+	// The deferred replies are sent, ready for the next capture
+	pub fn done(&mut self)
+	{
+		self.deferred_len = 0;
+		self.stop = false;
+	}
+}
+
+pub fn encode_reply(reply: &Reply, out: &mut impl FnMut(u8))
+{
+	let mut tmp = [0; REPLY_MAX];
+	let Ok(bytes) = postcard::to_slice_cobs(reply, &mut tmp) else
+	{
+		return;
+	};
+
+	/* Delimiter first, the host may have received a capture before */
+	out(0);
+	bytes.iter().for_each(|&b| out(b));
+}
+
+#[cfg(test)]
+mod tests
+{
+	use crate::remote::{encode_reply, Command, CommandParser, RemoteError, RemoteLink, Reply, Status, COMMAND_MAX};
+	use crate::decoder::DecoderUnion;
+	use crate::decoder_i2c::DecoderI2C;
+
+	fn feed(parser: &mut CommandParser, bytes: &[u8]) -> Vec<Result<Command, RemoteError>>
+	{
+		bytes.iter().filter_map(|&b| parser.push(b)).collect()
+	}
+
+	fn feed_link(link: &mut RemoteLink, bytes: &[u8]) -> Option<Result<Command, RemoteError>>
+	{
+		bytes.iter().filter_map(|&b| link.push(b)).last()
+	}
+
+	fn frame(cmd: &Command) -> Vec<u8>
+	{
+		let mut tmp = [0; COMMAND_MAX];
+		postcard::to_slice_cobs(cmd, &mut tmp).unwrap().to_vec()
+	}
+
+	#[test]
+	fn test_parse()
+	{
+		let mut parser = CommandParser::new();
+		let mut bytes = vec![0, 0];
+		bytes.extend(frame(&Command::Key(3)));
+		bytes.extend(frame(&Command::Run));
+
+		let cmds = feed(&mut parser, &bytes);
+		assert_eq!(cmds.len(), 2);
+		assert!(matches!(cmds[0], Ok(Command::Key(3))));
+		assert!(matches!(cmds[1], Ok(Command::Run)));
+
+		let decoder = DecoderUnion::I2C(DecoderI2C { sda_pin: 2, scl_pin: 5 });
+		let cmds = feed(&mut parser, &frame(&Command::SetDecoder(decoder)));
+		assert!(matches!(cmds[0], Ok(Command::SetDecoder(DecoderUnion::I2C(DecoderI2C { sda_pin: 2, scl_pin: 5 })))));
+	}
+
+	#[test]
+	fn test_errors()
+	{
+		let mut parser = CommandParser::new();
+
+		/* Unknown command, then the parser continues with the next frame */
+		let mut bytes = vec![0x02, 0x63, 0x00];
+		bytes.extend(frame(&Command::Status));
+		let cmds = feed(&mut parser, &bytes);
+		assert!(matches!(cmds[0], Err(RemoteError::Invalid)));
+		assert!(matches!(cmds[1], Ok(Command::Status)));
+
+		let mut bytes = vec![1; COMMAND_MAX + 1];
+		bytes.push(0);
+		assert!(matches!(feed(&mut parser, &bytes)[..], [Err(RemoteError::TooLong)]));
+	}
+
+	#[test]
+	fn test_running()
+	{
+		let mut link = RemoteLink::new();
+		let mut bytes = frame(&Command::Status);
+		bytes.extend([0x02, 0x63, 0x00]);
+		bytes.extend(frame(&Command::Stop));
+		bytes.extend(&frame(&Command::Fetch)[..1]);
+		bytes.iter().for_each(|&b| link.push_running(b));
+
+		assert!(link.stopped());
+		assert_eq!(link.deferred(), [Reply::Error(RemoteError::Busy), Reply::Error(RemoteError::Invalid), Reply::Ok]);
+
+		/* A frame that started during the capture ends afterwards */
+		link.done();
+		assert!(!link.stopped() && link.deferred().is_empty());
+		assert!(matches!(feed_link(&mut link, &frame(&Command::Fetch)[1..]), Some(Ok(Command::Fetch))));
+
+		for _ in 0..10
+		{
+			frame(&Command::Run).iter().for_each(|&b| link.push_running(b));
+		}
+
+		assert_eq!(link.deferred().len(), 8);
+		assert!(!link.stopped());
+	}
+
+	#[test]
+	fn test_reply()
+	{
+		let mut out = Vec::new();
+		encode_reply(&Reply::Ok, &mut |b| out.push(b));
+		assert_eq!(out, [0, 0x01, 0x01, 0x00]);
+
+		let status = Reply::Status(Status { ready: true, samples: 300, ..Default::default() });
+		out.clear();
+		encode_reply(&status, &mut |b| out.push(b));
+		let reply: Reply = postcard::from_bytes_cobs(&mut out[1..]).unwrap();
+		assert_eq!(reply, status);
+	}
+}
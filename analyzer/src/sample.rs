@@ -1,17 +1,127 @@
+use core::mem::size_of;
+use crate::arena::Arena;
+use crate::timestamps::TimestampBuffer;
+use crate::decoder::TIMER_CLOCK_RATE;
+
 // Type used to store one sample containing multiple channels in
-pub type Sample = u8;
+// Bits 0-7: Blue port, bits 8-15: Yellow port
+pub type Sample = u16;
+
+pub const CHANNELS: u32 = 16;
+
+// Channel mask with every channel enabled
+pub const ALL_CHANNELS: Sample = Sample::MAX;
+
+// Time in timer ticks, extended beyond the 32 bit hardware timer
+pub type Timestamp = u64;
+
+// Capacity of buffers on the host, the firmware uses the whole capture region
+#[cfg(any(test, feature = "std"))]
+pub const BUF_SIZE: usize = 1024;
+
+// Clock of the timestamps, TIM2 divided down from the timer clock.
+// Slower clocks sample less often, so long captures fit into the
+// timestamps and the CPU has more time to scan for level changes.
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default)]
+pub enum Timebase
+{
+	// Sample every 9 ticks (10 MHz)
+	#[default]
+	Mhz90,
 
-pub const BUF_SIZE: usize = 1000;
+	// Sample every tick
+	Mhz10,
+	Mhz1
+}
+
+impl Timebase
+{
+	pub const fn clock_rate(self) -> u32
+	{
+		match self
+		{
+			Timebase::Mhz90 => 90_000_000,
+			Timebase::Mhz10 => 10_000_000,
+			Timebase::Mhz1 => 1_000_000
+		}
+	}
+
+	pub const fn ticks_per_us(self) -> u32
+	{
+		self.clock_rate() / 1_000_000
+	}
+
+	// Timer clock cycles per tick
+	pub const fn divider(self) -> u32
+	{
+		TIMER_CLOCK_RATE / self.clock_rate()
+	}
+
+	// Ticks between two samples of the capture engine
+	pub const fn sample_period(self) -> u32
+	{
+		match self
+		{
+			Timebase::Mhz90 => 9,
+			_ => 1
+		}
+	}
+
+	// Ticks to timer clock cycles, the unit of the CSV dump
+	pub const fn cycles(self, ticks: Timestamp) -> Timestamp
+	{
+		ticks * self.divider() as Timestamp
+	}
+
+	// Timer clock cycles to ticks, rounded up
+	pub const fn ticks(self, cycles: Timestamp) -> Timestamp
+	{
+		cycles.div_ceil(self.divider() as Timestamp)
+	}
+}
+
+// How a capture into a SampleBuffer ended
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum CaptureEnd
+{
+	// Buffer full, time limit reached or samples lost
+	Done,
+
+	// Stop was pressed during the capture
+	Stopped,
+
+	// Stop was pressed while armed, the previous capture was kept
+	Aborted
+}
 
 // Buffer containing samples
 pub struct SampleBuffer
 {
 	// SoA to avoid padding while maintaining speed
-	pub samples: [Sample; BUF_SIZE],
-	pub timestamps: [u32; BUF_SIZE],
-	pub len: usize
+	pub samples: &'static mut [Sample],
+	pub timestamps: TimestampBuffer,
+	pub len: usize,
+
+	// Timestamp of the trigger or stop event, shown as t=0
+	pub event: Option<Timestamp>,
+
+	// Clock of the timestamps
+	pub timebase: Timebase,
+
+	// Worst-case time between two samples in ticks, 0 if unknown
+	pub resolution: u32,
+
+	// The capture ended early because samples were lost
+	pub overrun: bool,
+
+	// Pulses removed by the glitch filter
+	pub glitches: u32,
+
+	// Recorded channels, the others always read low
+	pub channels: Sample
 }
 
+#[cfg(any(test, feature = "std"))]
 impl Default for SampleBuffer
 {
 	fn default() -> Self
@@ -22,40 +132,150 @@ impl Default for SampleBuffer
 
 impl SampleBuffer
 {
-	pub fn new() -> Self
+	// Bytes of storage per entry
+	pub const ENTRY_SIZE: usize = size_of::<Sample>() + TimestampBuffer::ENTRY_SIZE;
+
+	// The capacity is rounded down to whole blocks of timestamps
+	pub fn alloc(mem: &mut Arena, len: usize) -> Option<Self>
 	{
-		SampleBuffer
+		let len = TimestampBuffer::round(len);
+		Some(SampleBuffer
 		{
-			samples: [0; BUF_SIZE],
-			timestamps: [0; BUF_SIZE],
-			len: 0
-		}
+			samples: mem.alloc(len, 0)?,
+			timestamps: TimestampBuffer::alloc(mem, len)?,
+			len: 0,
+			event: None,
+			timebase: Timebase::Mhz90,
+			resolution: 0,
+			overrun: false,
+			glitches: 0,
+			channels: ALL_CHANNELS
+		})
+	}
+
+	#[cfg(any(test, feature = "std"))]
+	pub fn new() -> Self
+	{
+		let mut mem = Arena::leak(BUF_SIZE * Self::ENTRY_SIZE + 16);
+		Self::alloc(&mut mem, BUF_SIZE).unwrap()
+	}
+
+	pub fn capacity(&self) -> usize
+	{
+		self.samples.len()
+	}
+
+	// Only room for the final entry of a capture is left
+	pub fn is_full(&self) -> bool
+	{
+		self.len + 1 >= self.capacity() || self.timestamps.is_full()
+	}
+
+	pub fn timestamp(&self, idx: usize) -> Timestamp
+	{
+		self.timestamps.get(idx)
 	}
 
 	pub fn clear(&mut self)
 	{
 		self.len = 0;
+		self.timestamps.clear();
+		self.event = None;
+		self.timebase = Timebase::Mhz90;
+		self.resolution = 0;
+		self.overrun = false;
+		self.glitches = 0;
+		self.channels = ALL_CHANNELS;
 	}
 
-	pub fn push(&mut self, port: Sample, ts: u32)
+	pub fn push(&mut self, port: Sample, ts: Timestamp)
 	{
 		self.samples[self.len] = port;
-		self.timestamps[self.len] = ts;
+		self.timestamps.push(self.len, ts);
 		self.len += 1;
 	}
 
-	pub fn get(&self, idx: usize, ch: u32) -> (bool, u32)
+	// Drop all samples before idx, sample idx becomes t=0
+	pub fn rebase(&mut self, idx: usize)
+	{
+		let t0 = self.timestamp(idx);
+		self.samples.copy_within(idx..self.len, 0);
+		self.len = self.timestamps.retain(self.len, t0, |i| i >= idx);
+
+		self.event = self.event.map(|e| e.saturating_sub(t0));
+	}
+
+	// Drop everything after end, the capture then ends at end
+	pub fn cut(&mut self, end: Timestamp)
+	{
+		let mut n = self.len;
+		while n > 1 && self.timestamp(n - 1) > end
+		{
+			n -= 1;
+		}
+
+		self.len = n;
+		self.timestamps.truncate(n);
+		if n > 0 && self.timestamp(n - 1) < end
+		{
+			self.push(self.samples[n - 1], end);
+		}
+	}
+
+	// Drop entries that change no channel, the first and last entry are kept
+	// as they mark the start and end of the capture
+	pub fn remove_duplicates(&mut self)
+	{
+		if self.len < 3
+		{
+			return;
+		}
+
+		let last = self.len - 1;
+		let samples = &mut self.samples;
+		let mut w = 0;
+		self.len = self.timestamps.retain(self.len, 0, |r|
+		{
+			let keep = r == 0 || r == last || samples[r] != samples[w - 1];
+			if keep
+			{
+				samples[w] = samples[r];
+				w += 1;
+			}
+
+			keep
+		});
+	}
+
+	// Forget the channels outside of mask, like a capture with only these enabled
+	pub fn keep_channels(&mut self, mask: Sample)
+	{
+		for s in self.samples[..self.len].iter_mut()
+		{
+			*s &= mask;
+		}
+
+		self.channels &= mask;
+		self.remove_duplicates();
+	}
+
+	pub fn is_enabled(&self, ch: u32) -> bool
+	{
+		self.channels & (1 << ch) != 0
+	}
+
+	pub fn get(&self, idx: usize, ch: u32) -> (bool, Timestamp)
 	{
-		(self.samples[idx] & (1 << ch) != 0, self.timestamps[idx])
+		(self.samples[idx] & (1 << ch) != 0, self.timestamp(idx))
 	}
 
 	// TODO: maybe change name or merge it with get
-	pub fn get_content(&self, idx: usize, ch: u32) -> Option<(bool, u32)>
+	pub fn get_content(&self, idx: usize, ch: u32) -> Option<(bool, Timestamp)>
 	{
 		if idx >= self.len {
 			return None;
 		}
-		Some((self.samples[idx] & (1 << ch) != 0, self.timestamps[idx]))
+		Some((self.samples[idx] & (1 << ch) != 0, self.timestamp(idx)))
 	}
 
 	pub fn edge_iter(&self, ch: u32) -> EdgeWiseIterator<'_>
@@ -65,7 +285,7 @@ impl SampleBuffer
 
 	// start: Timstamp of window start
 	// Returns sample index
-	pub fn find_prev(&self, start: u32) -> usize
+	pub fn find_prev(&self, start: Timestamp) -> usize
 	{
 		if self.len == 0
 		{
@@ -78,7 +298,7 @@ impl SampleBuffer
 		while left <= right
 		{
 			let mid = (left + right) / 2;
-			if self.timestamps[mid as usize] < start
+			if self.timestamp(mid as usize) < start
 			{
 				closest_index = mid as usize;
 				left = mid + 1;
@@ -94,7 +314,7 @@ impl SampleBuffer
 
 	// start: Timstamp of window start
 	// Returns sample index
-	pub fn find_start(&self, start: u32) -> usize
+	pub fn find_start(&self, start: Timestamp) -> usize
 	{
 		let mut left = 0;
 		let mut right = self.len as isize - 1;
@@ -102,7 +322,7 @@ impl SampleBuffer
 		while left <= right
 		{
 			let mid = (left + right) / 2;
-			if self.timestamps[mid as usize] <= start
+			if self.timestamp(mid as usize) <= start
 			{
 				closest_index = mid as usize;
 				left = mid + 1;
@@ -118,7 +338,7 @@ impl SampleBuffer
 
 	// start: Timstamp of window end
 	// Returns sample index
-	pub fn find_end(&self, end: u32) -> usize
+	pub fn find_end(&self, end: Timestamp) -> usize
 	{
 		let mut left = 0;
 		let mut right = self.len as isize - 1;
@@ -126,7 +346,7 @@ impl SampleBuffer
 		while left <= right
 		{
 			let mid = (left + right) / 2;
-			if self.timestamps[mid as usize] >= end
+			if self.timestamp(mid as usize) >= end
 			{
 				closest_index = mid as usize;
 				right = mid - 1;
@@ -142,7 +362,7 @@ impl SampleBuffer
 
 	// start: Timstamp of window end
 	// Returns sample index
-	pub fn find_next(&self, end: u32) -> usize
+	pub fn find_next(&self, end: Timestamp) -> usize
 	{
 		if self.len == 0
 		{
@@ -155,7 +375,7 @@ impl SampleBuffer
 		while left <= right
 		{
 			let mid = (left + right) / 2;
-			if self.timestamps[mid as usize] > end
+			if self.timestamp(mid as usize) > end
 			{
 				closest_index = mid as usize;
 				right = mid - 1;
@@ -172,7 +392,7 @@ impl SampleBuffer
 
 impl<'a> IntoIterator for &'a SampleBuffer
 {
-	type Item = (u32, Sample);
+	type Item = (Timestamp, Sample);
 	type IntoIter = SampleBufferIterator<'a>;
 
 	fn into_iter(self) -> Self::IntoIter
@@ -193,15 +413,15 @@ pub struct SampleBufferIterator<'a>
 
 impl<'a> Iterator for SampleBufferIterator<'a>
 {
-	type Item = (u32, Sample);
-	fn next(&mut self) -> Option<(u32, Sample)>
+	type Item = (Timestamp, Sample);
+	fn next(&mut self) -> Option<(Timestamp, Sample)>
 	{
 		if self.idx >= self.buf.len
 		{
 			return None;
 		}
 
-		let result = (self.buf.timestamps[self.idx], self.buf.samples[self.idx]);
+		let result = (self.buf.timestamp(self.idx), self.buf.samples[self.idx]);
 		self.idx += 1;
 		Some(result)
 	}
@@ -263,6 +483,11 @@ impl<'a> EdgeWiseIterator<'a>
 		self.idx
 	}
 
+	pub fn timebase(&self) -> Timebase
+	{
+		self.buffer.timebase
+	}
+
 	pub fn set_index(&mut self, idx: usize) -> Result<(), ()>
 	{
 		if idx >= self.buffer.len
@@ -273,13 +498,13 @@ impl<'a> EdgeWiseIterator<'a>
 		Ok(())
 	}
 
-	pub fn current_time(&self) -> u32
+	pub fn current_time(&self) -> Timestamp
 	{
 		if self.buffer.len == 0
 		{
 			return 0
 		}
-		self.buffer.timestamps[self.idx]
+		self.buffer.timestamp(self.idx)
 	}
 }
 
@@ -302,13 +527,13 @@ impl<'a> Iterator for EdgeWiseIterator<'a>
 pub struct BitSignal
 {
 	pub high: bool,
-	pub end: u32,
-	pub start: u32,
+	pub end: Timestamp,
+	pub start: Timestamp,
 }
 
 impl BitSignal
 {
-	pub fn duration(&self) -> u32
+	pub fn duration(&self) -> Timestamp
 	{
 		self.end - self.start
 	}
@@ -377,6 +602,38 @@ mod tests
 		assert_eq!(buf.get_content(7, 0), Some((true, 65141112)))
 	}
 
+	#[test]
+	fn test_rebase()
+	{
+		let mut buf = sample_buffer();
+		buf.rebase(2);
+		assert_eq!(buf.len, 6);
+		assert_eq!(buf.get_content(0, 0), Some((true, 0)));
+		assert_eq!(buf.get_content(1, 0), Some((false, 39672860 - 39372990)));
+		assert_eq!(buf.get_content(5, 0), Some((true, 65141112 - 39372990)));
+		assert_eq!(buf.get_content(6, 0), None);
+	}
+
+	#[test]
+	fn test_keep_channels()
+	{
+		let mut buf = SampleBuffer::new();
+		buf.push(0x0101, 0);
+		buf.push(0x0100, 10);
+		buf.push(0x0000, 20);
+		buf.push(0x0001, 30);
+		buf.push(0x0001, 40);
+
+		/* Only the changes on channel 8 are left */
+		buf.keep_channels(0xFF00);
+		assert_eq!(buf.len, 3);
+		assert_eq!(&buf.samples[..buf.len], &[0x0100, 0x0000, 0x0000]);
+		assert_eq!(buf.timestamp(1), 20);
+		assert_eq!(buf.timestamp(2), 40);
+		assert!(buf.is_enabled(8));
+		assert!(!buf.is_enabled(0));
+	}
+
 	#[test]
 	fn test_edge_iterator()
 	{
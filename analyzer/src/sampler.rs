@@ -1,45 +1,393 @@
-use crate::hw::{buttons_read, timer_set, timer_get, blueread};
-use crate::sample::{Sample, SampleBuffer};
-use crate::decoder::TIMER_CLOCK_RATE;
+use crate::hw::{buttons_read, serial_flush, serial_try_read, serial_try_write};
+use crate::sample::{CaptureEnd, Sample, SampleBuffer, Timebase, Timestamp, ALL_CHANNELS};
+use crate::trigger::Trigger;
+use crate::glitch::GlitchFilter;
+use crate::stream::{StreamResult, Streamer, TxQueue};
+use crate::stop::{FrameCheck, StopCheck, StopCondition};
+use crate::samplering::SampleRing;
+use crate::capture::Capture;
+use crate::timestamps::TimestampBuffer;
+use crate::decoder::{SectionBuffer, TIMER_CLOCK_RATE};
+use crate::generator::Generator;
+use crate::player;
+use crate::transfer;
+use crate::sump::{self, SumpLink};
+use crate::remote::{self, RemoteLink, Reply};
+use core::ptr::null_mut;
+use core::sync::atomic::{AtomicPtr, Ordering};
 
-fn sample() -> Sample
+// Captures end before their timestamps no longer fit into the buffer,
+// with headroom for the final sample (1 s at 90 MHz)
+const MAX_SPAN: Timestamp = TimestampBuffer::MAX - TIMER_CLOCK_RATE as Timestamp;
+
+// Bytes buffered in front of the serial port while streaming
+const STREAM_QUEUE: usize = 4096;
+
+// Commands of a capture started over the serial port, a Stop command
+// received meanwhile stops it like the Stop button
+static REMOTE: AtomicPtr<RemoteLink> = AtomicPtr::new(null_mut());
+
+// Run f with the commands going to link
+pub fn remote_capture<R>(link: &mut RemoteLink, f: impl FnOnce() -> R) -> R
 {
-	blueread() as Sample
+	REMOTE.store(link, Ordering::Relaxed);
+	let r = f();
+	REMOTE.store(null_mut(), Ordering::Relaxed);
+	r
 }
 
-pub fn sample_blocking(buf: &mut SampleBuffer)
+fn stop_pressed() -> bool
 {
-	let mut prev = sample();
-	buf.clear();
-	buf.push(sample(), 0);
-	timer_set(0);
+	/* Nothing else uses the link until remote_capture returns */
+	let remote_stop = match unsafe { REMOTE.load(Ordering::Relaxed).as_mut() }
+	{
+		Some(link) =>
+		{
+			while let Some(b) = serial_try_read()
+			{
+				link.push_running(b);
+			}
+
+			link.stopped()
+		}
+		None => false
+	};
+
+	buttons_read() & 0x80 != 0x80 || remote_stop
+}
+
+// Waits until the trigger fires, returns the triggering sample and its timestamp
+// or None if the capture was stopped while armed
+fn wait_trigger(cap: &mut Capture, trigger: &Trigger) -> Option<(Sample, Timestamp)>
+{
+	let mut prev = cap.level();
+	if trigger.is_met(prev, prev)
+	{
+		return Some((prev, 0));
+	}
+
 	loop
 	{
-		let buttons = buttons_read() & 0x80;
-		if buttons != 0x80
+		if stop_pressed() || cap.overrun
+		{
+			return None;
+		}
+
+		while let Some((port, ts)) = cap.poll()
+		{
+			if trigger.is_met(prev, port)
+			{
+				return Some((port, ts));
+			}
+
+			prev = port;
+		}
+	}
+}
+
+// frames: Decodes the capture while it runs if the stop condition counts frames
+pub fn sample_blocking(buf: &mut SampleBuffer, trigger: &Trigger, filter: &GlitchFilter,
+	stop: &StopCondition, frames: Option<FrameCheck>, timebase: Timebase, channels: Sample) -> CaptureEnd
+{
+	let mut cap = Capture::start(timebase, channels);
+	let captured = if trigger.has_pre_trigger()
+	{
+		sample_ring(&mut cap, buf, trigger, stop, timebase);
+		true
+	}
+	else
+	{
+		sample_linear(&mut cap, buf, trigger, stop, frames, timebase)
+	};
+
+	cap.stop();
+	if !captured
+	{
+		return CaptureEnd::Aborted;
+	}
+
+	buf.channels = channels;
+	filter.apply(buf);
+
+	/* Every capture ends right when Stop is pressed, it is still held */
+	if stop_pressed() { CaptureEnd::Stopped } else { CaptureEnd::Done }
+}
+
+// Record from the trigger until the buffer is full or the stop condition is met,
+// returns false if the previous buffer was kept
+fn sample_linear(cap: &mut Capture, buf: &mut SampleBuffer, trigger: &Trigger,
+	stop: &StopCondition, mut frames: Option<FrameCheck>, timebase: Timebase) -> bool
+{
+	let Some((first, t0)) = wait_trigger(cap, trigger) else
+	{
+		if cap.overrun
+		{
+			buf.clear();
+			buf.timebase = timebase;
+			buf.overrun = true;
+			return true;
+		}
+
+		return false;
+	};
+
+	buf.clear();
+	buf.timebase = timebase;
+	if trigger.is_armed()
+	{
+		buf.event = Some(0);
+	}
+
+	buf.push(first, 0);
+	let mut check = StopCheck::new(stop, timebase);
+	let end = 'capture: loop
+	{
+		let now = cap.now() - t0;
+		if stop_pressed() || cap.overrun || now > MAX_SPAN
+		{
+			break None;
+		}
+
+		while let Some((port, ts)) = cap.poll()
+		{
+			if buf.is_full()
+			{
+				break 'capture None;
+			}
+
+			buf.push(port, ts - t0);
+			if check.edge(port, ts - t0)
+			{
+				break 'capture None;
+			}
+		}
+
+		/* All changes until now are read */
+		if let Some(end) = check.idle(now)
+		{
+			break Some(end);
+		}
+
+		if frames.as_mut().is_some_and(|f| f.check(buf, now))
+		{
+			break Some(now);
+		}
+	};
+
+	buf.push(cap.level(), end.unwrap_or_else(|| cap.now() - t0));
+	buf.resolution = timebase.sample_period();
+	buf.overrun = cap.overrun;
+	true
+}
+
+// Record into a ring until the trigger fires or Stop is pressed,
+// then keep the configured amount of data after the event,
+// the stop condition may end it earlier.
+// Patterns only fire on a change, as the history before it is wanted.
+fn sample_ring(cap: &mut Capture, buf: &mut SampleBuffer, trigger: &Trigger,
+	stop: &StopCondition, timebase: Timebase)
+{
+	let mut ring = SampleRing::new(buf);
+	let post_len = trigger.post_len(ring.capacity());
+
+	let mut prev = cap.level();
+	ring.push(prev, 0);
+
+	/* Pre-trigger: Overwrite the oldest edges */
+	let event = 'pre: loop
+	{
+		while let Some((port, ts)) = cap.poll()
+		{
+			ring.push(port, ts);
+			let fired = trigger.is_armed() && trigger.is_met(prev, port);
+			prev = port;
+			if fired
+			{
+				break 'pre ts;
+			}
+		}
+
+		if stop_pressed() || cap.overrun || cap.now() > MAX_SPAN
+		{
+			break cap.now();
+		}
+	};
+
+	/* Post-trigger: Stop must be released before it ends the capture */
+	let mut released = false;
+	let mut count = 1;
+	let mut check = StopCheck::new(stop, timebase);
+	let mut end = None;
+	'post: while count < post_len
+	{
+		let pressed = stop_pressed();
+		if pressed && released
 		{
 			break;
 		}
 
-		let port = sample();
-		let ts = timer_get();
+		released |= !pressed;
 
-		if ts > 45 * TIMER_CLOCK_RATE
+		let now = cap.now();
+		if cap.overrun || now > MAX_SPAN
 		{
 			break;
 		}
 
-		if port != prev
+		while let Some((port, ts)) = cap.poll()
 		{
-			prev = port;
-			if buf.len >= buf.samples.len() - 1
+			ring.push(port, ts);
+			count += 1;
+			if count >= post_len || check.edge(port, ts - event)
 			{
-				break;
+				break 'post;
 			}
+		}
 
+		if let Some(t) = check.idle(now - event)
+		{
+			end = Some(event + t);
+			break;
+		}
+	}
+
+	ring.push(cap.level(), end.unwrap_or_else(|| cap.now()));
+	ring.finish(event);
+	buf.timebase = timebase;
+	buf.resolution = timebase.sample_period();
+	buf.overrun = cap.overrun;
+}
+
+// Record while the generator plays the frames, for the loopback self test.
+// A full buffer ends the recording, the length shows it.
+pub fn sample_generator(buf: &mut SampleBuffer, gen: &Generator, data: &[u8])
+{
+	let timebase = Timebase::Mhz90;
+	let mut cap = Capture::start(timebase, ALL_CHANNELS);
+	buf.clear();
+	buf.push(cap.level(), 0);
+
+	let mut end = 0;
+	player::play(gen, data, &mut ||
+	{
+		while !buf.is_full()
+		{
+			let Some((port, ts)) = cap.poll() else { break; };
 			buf.push(port, ts);
 		}
+
+		end = cap.now();
+	});
+
+	buf.push(cap.level(), end);
+	cap.stop();
+	buf.timebase = timebase;
+	buf.resolution = timebase.sample_period();
+	buf.overrun = cap.overrun;
+}
+
+// Send level changes over the serial port as they are captured until Stop is
+// pressed. Changes that do not fit into the queue are dropped and reported.
+pub fn stream_blocking(trigger: &Trigger, timebase: Timebase, channels: Sample) -> StreamResult
+{
+	let mut cap = Capture::start(timebase, channels);
+	let Some((first, t0)) = wait_trigger(&mut cap, trigger) else
+	{
+		cap.stop();
+		return StreamResult { overrun: cap.overrun, ..Default::default() };
+	};
+
+	let mut s = Streamer::<STREAM_QUEUE>::new(first, timebase);
+	while !stop_pressed() && !cap.overrun
+	{
+		while let Some((port, ts)) = cap.poll()
+		{
+			s.edge(port, ts - t0);
+			serial_service(&mut s.queue);
+		}
+
+		serial_service(&mut s.queue);
+	}
+
+	let end = cap.now() - t0;
+	cap.stop();
+	while !s.end(end, cap.overrun)
+	{
+		serial_service(&mut s.queue);
+	}
+
+	while !s.queue.is_empty()
+	{
+		serial_service(&mut s.queue);
+	}
+
+	serial_flush();
+	s.result
+}
+
+// Send a finished capture with its decoded sections in the binary transfer
+// format, waits for the transmitter
+pub fn transfer_blocking(buf: &SampleBuffer, sections: &SectionBuffer)
+{
+	transfer::encode(buf, sections, &mut |b| while !serial_try_write(b) {});
+	serial_flush();
+}
+
+// Answer a remote command, after a capture sent for it
+pub fn remote_reply(reply: &Reply)
+{
+	remote::encode_reply(reply, &mut |b| while !serial_try_write(b) {});
+	serial_flush();
+}
+
+// USART3 for the SUMP client, Stop ends the session
+struct SerialLink;
+
+impl SumpLink for SerialLink
+{
+	fn read(&mut self) -> Option<u8>
+	{
+		loop
+		{
+			if stop_pressed()
+			{
+				return None;
+			}
+
+			if let Some(b) = serial_try_read()
+			{
+				return Some(b);
+			}
+		}
 	}
 
-	buf.push(sample(), timer_get());
+	fn write(&mut self, b: u8)
+	{
+		while !serial_try_write(b) {}
+	}
+}
+
+// Act as a SUMP analyzer on the blue port until Stop is pressed,
+// returns the number of captures sent, the last one stays in buf
+pub fn sump_blocking(buf: &mut SampleBuffer, filter: &GlitchFilter, channels: Sample) -> u32
+{
+	let runs = sump::serve(&mut SerialLink, buf, &mut |buf, config|
+	{
+		sample_blocking(buf, &config.trigger(), filter, &config.stop(), None, config.timebase(),
+			channels) != CaptureEnd::Aborted
+	});
+
+	serial_flush();
+	runs
+}
+
+fn serial_service<const N: usize>(queue: &mut TxQueue<N>)
+{
+	if let Some(b) = queue.peek()
+	{
+		if serial_try_write(b)
+		{
+			queue.pop();
+		}
+	}
 }
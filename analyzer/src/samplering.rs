@@ -0,0 +1,118 @@
+use crate::sample::{Sample, SampleBuffer, Timestamp};
+
+// Circular recorder on top of the storage of a SampleBuffer,
+// overwrites the oldest entries when full, a block of timestamps at once.
+// Timestamps are rebased to the oldest entry by finish.
+pub struct SampleRing<'a>
+{
+	buf: &'a mut SampleBuffer,
+
+	// Index of the oldest entry
+	head: usize,
+	len: usize
+}
+
+impl<'a> SampleRing<'a>
+{
+	pub fn new(buf: &'a mut SampleBuffer) -> SampleRing<'a>
+	{
+		buf.clear();
+		SampleRing
+		{
+			buf,
+			head: 0,
+			len: 0
+		}
+	}
+
+	pub fn capacity(&self) -> usize
+	{
+		self.buf.capacity()
+	}
+
+	pub fn push(&mut self, port: Sample, ts: Timestamp)
+	{
+		let cap = self.capacity();
+		self.buf.samples[(self.head + self.len) % cap] = port;
+		let dropped = self.buf.timestamps.ring_push(self.head, self.len, ts);
+		self.head = (self.head + dropped) % cap;
+		self.len = self.len + 1 - dropped;
+	}
+
+	// Linearize the ring into the SampleBuffer, the oldest entry becomes t=0.
+	// event: Capture timestamp of the stop event
+	pub fn finish(self, event: Timestamp)
+	{
+		self.buf.samples.rotate_left(self.head);
+		self.buf.timestamps.linearize(self.head);
+
+		let t0 = if self.len == 0 { event } else { self.buf.timestamp(0) };
+		self.buf.len = self.buf.timestamps.retain(self.len, t0, |_| true);
+
+		self.buf.event = Some(event.saturating_sub(t0));
+	}
+}
+
+#[cfg(test)]
+mod tests
+{
+	use crate::sample::{Sample, SampleBuffer, Timestamp};
+	use crate::samplering::SampleRing;
+	use crate::timestamps::TimestampBuffer;
+
+	#[test]
+	fn test_no_wrap()
+	{
+		let mut buf = SampleBuffer::new();
+		let mut ring = SampleRing::new(&mut buf);
+		ring.push(1, 100);
+		ring.push(0, 150);
+		ring.push(1, 400);
+		ring.finish(150);
+
+		assert_eq!(buf.len, 3);
+		assert_eq!(buf.event, Some(50));
+		assert_eq!(buf.get_content(0, 0), Some((true, 0)));
+		assert_eq!(buf.get_content(1, 0), Some((false, 50)));
+		assert_eq!(buf.get_content(2, 0), Some((true, 300)));
+	}
+
+	#[test]
+	fn test_overwrite()
+	{
+		let mut buf = SampleBuffer::new();
+		let cap = buf.capacity();
+		let mut ring = SampleRing::new(&mut buf);
+		for i in 0..(cap + 10)
+		{
+			ring.push((i % 2) as Sample, i as Timestamp * 10);
+		}
+
+		ring.finish(500);
+
+		/* Without packed timestamps exactly the 10 oldest are gone */
+		let first = cap + 10 - buf.len;
+		assert!(buf.len > cap - TimestampBuffer::BLOCK);
+		assert_eq!(buf.event, Some(500 - first as Timestamp * 10));
+		assert_eq!(buf.samples[0], (first % 2) as Sample);
+		assert_eq!(buf.timestamp(0), 0);
+		assert_eq!(buf.timestamp(buf.len - 1), (buf.len as Timestamp - 1) * 10);
+	}
+
+	#[test]
+	fn test_past_timer_wrap()
+	{
+		let wrap = u32::MAX as Timestamp + 1;
+		let mut buf = SampleBuffer::new();
+		let mut ring = SampleRing::new(&mut buf);
+		ring.push(1, wrap - 1000);
+		ring.push(0, wrap + 100);
+		ring.push(1, 3 * wrap);
+		ring.finish(3 * wrap);
+
+		assert_eq!(buf.len, 3);
+		assert_eq!(buf.event, Some(2 * wrap + 1000));
+		assert_eq!(buf.get_content(1, 0), Some((false, 1100)));
+		assert_eq!(buf.get_content(2, 0), Some((true, 2 * wrap + 1000)));
+	}
+}
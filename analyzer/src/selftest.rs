@@ -0,0 +1,313 @@
+use crate::generator::{GenProtocol, Generator};
+use crate::sample::{SampleBuffer, Timestamp};
+use crate::decoder::{Decoder, DecoderPin, SectionBuffer, SectionContent, TIMER_CLOCK_RATE};
+use crate::decoder_uart::{DecoderUart, DataBits, Parity, StopBits};
+use crate::decoder_spi::{BitOrder, CsPolarity, DecoderSPI};
+use crate::decoder_i2c::DecoderI2C;
+use crate::decoder_onewire::DecoderOneWire;
+use crate::decoder_onewire::rom_cmd::ROMCmd;
+
+/*
+ * Loopback test of the sampler and the decoders: the generator plays known
+ * frames on yellow pins 0-2, which are wired to blue pins 0-2. The capture is
+ * decoded from the blue channels and its edges are compared to the generated ones.
+ */
+
+// Largest deviation of an edge that still passes (1 µs)
+const MAX_ERROR: Timestamp = TIMER_CLOCK_RATE as Timestamp / 1_000_000;
+
+const BITRATE: u32 = 9600;
+
+pub struct LoopbackTest
+{
+	pub name: &'static str,
+	pub gen: Generator,
+	pub data: &'static [u8],
+
+	// Decoded words in order, framing and bits are not compared
+	pub expect: &'static [SectionContent]
+}
+
+const fn generator(protocol: GenProtocol) -> Generator
+{
+	Generator
+	{
+		protocol,
+		data_pin: 0,
+		clock_pin: 1,
+		cs_pin: 2,
+		bitrate: BITRATE
+	}
+}
+
+pub const LOOPBACK_TESTS: [LoopbackTest; 4] =
+[
+	LoopbackTest
+	{
+		name: "UART",
+		gen: generator(GenProtocol::Uart),
+		data: b"Hallo",
+		expect: &[
+			SectionContent::Data(0x48), SectionContent::Data(0x61), SectionContent::Data(0x6C),
+			SectionContent::Data(0x6C), SectionContent::Data(0x6F)
+		]
+	},
+	LoopbackTest
+	{
+		name: "SPI",
+		gen: generator(GenProtocol::Spi),
+		data: b"Hallo",
+		expect: &[
+			SectionContent::TxByte(0x48), SectionContent::TxByte(0x61), SectionContent::TxByte(0x6C),
+			SectionContent::TxByte(0x6C), SectionContent::TxByte(0x6F)
+		]
+	},
+	LoopbackTest
+	{
+		name: "I2C",
+		gen: generator(GenProtocol::I2c),
+		data: &[0xA0, 0x12, 0x34],
+		expect: &[
+			SectionContent::I2cAddress(0x50), SectionContent::I2cWrite,
+			SectionContent::Byte(0x12), SectionContent::Byte(0x34)
+		]
+	},
+	LoopbackTest
+	{
+		name: "1-Wire",
+		gen: generator(GenProtocol::OneWire),
+		data: &[0xCC, 0xBE, 0x12, 0x34],
+		expect: &[
+			SectionContent::ROMCmd(ROMCmd::SkipROM), SectionContent::FunctionCmd(0xBE),
+			SectionContent::Byte(0x12), SectionContent::Byte(0x34)
+		]
+	}
+];
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum TestResult
+{
+	// Largest deviation of an edge in ns
+	Pass(u32),
+
+	// Decoded correctly, but the timing is off by this many ns
+	Timing(u32),
+
+	// The decoder did not find the sent data
+	Decode,
+
+	// Edges are missing, e.g. without the jumper wires
+	Edges,
+
+	// The capture could not keep up or the buffer was full
+	Capture
+}
+
+// Contents compared with the expected words, MISO and acknowledge
+// depend on devices that are not there
+fn is_word(content: &SectionContent) -> bool
+{
+	matches!(content,
+		SectionContent::Data(_) | SectionContent::TxByte(_) | SectionContent::Byte(_) |
+		SectionContent::I2cAddress(_) | SectionContent::I2cWrite | SectionContent::I2cRead |
+		SectionContent::ROMCmd(_) | SectionContent::FunctionCmd(_))
+}
+
+fn cycles_to_ns(cycles: Timestamp) -> u32
+{
+	(cycles * 1_000_000_000 / TIMER_CLOCK_RATE as Timestamp) as u32
+}
+
+impl LoopbackTest
+{
+	// Decoder for the blue channels the generator pins are wired to
+	pub fn decode(&self, buf: &SampleBuffer, sections: &mut SectionBuffer)
+	{
+		let (data, clock, cs) = (self.gen.data_pin as DecoderPin,
+			self.gen.clock_pin as DecoderPin, self.gen.cs_pin as DecoderPin);
+		let _ = match self.gen.protocol
+		{
+			GenProtocol::Uart => DecoderUart
+			{
+				rx_pin: data,
+				tx_pin: clock,
+				databits: DataBits::Eight,
+				parity: Parity::None,
+				stopbits: StopBits::One,
+				baudrate: self.gen.bitrate
+			}.decode(buf, sections),
+			GenProtocol::Spi => DecoderSPI
+			{
+				mosi_pin: data,
+				miso_pin: 3,
+				sck_pin: clock,
+				cs_pin: cs,
+				cs_polarity: CsPolarity::ActiveLow,
+				mode: 0,
+				bitorder: BitOrder::MsbFirst,
+				word_size: 8
+			}.decode(buf, sections),
+			GenProtocol::I2c => DecoderI2C { sda_pin: data, scl_pin: clock }.decode(buf, sections),
+			GenProtocol::OneWire => DecoderOneWire { onewire_pin: data }.decode(buf, sections)
+		};
+	}
+
+	// Largest deviation of the captured edges on the blue port from the generated ones
+	// in timer clock cycles, both start at their first edge.
+	// None if an edge is missing or there are more.
+	pub fn timing_error(&self, buf: &SampleBuffer) -> Option<Timestamp>
+	{
+		let mask = self.gen.pins();
+		let mut edges = (1..buf.len)
+			.filter(|&i| (buf.samples[i] ^ buf.samples[i - 1]) as u8 & mask != 0);
+
+		let mut level = self.gen.idle();
+		let mut t: Timestamp = 0;
+		let mut first = None;
+		let mut error = Some(0);
+		self.gen.generate(self.data, &mut |l, ticks|
+		{
+			if l != level
+			{
+				level = l;
+				match edges.next()
+				{
+					Some(i) if buf.samples[i] as u8 & mask == l =>
+					{
+						let captured = buf.timebase.cycles(buf.timestamp(i));
+						let (t0, c0) = *first.get_or_insert((t, captured));
+						let deviation = (captured - c0).abs_diff(t - t0);
+						error = error.map(|e: Timestamp| e.max(deviation));
+					}
+					_ => error = None
+				}
+			}
+
+			t += ticks as Timestamp;
+		});
+
+		if edges.next().is_some() { None } else { error }
+	}
+
+	// Result of a capture made while the generator was playing
+	pub fn check(&self, buf: &SampleBuffer, sections: &SectionBuffer) -> TestResult
+	{
+		if buf.overrun || buf.is_full()
+		{
+			return TestResult::Capture;
+		}
+
+		let Some(error) = self.timing_error(buf) else
+		{
+			return TestResult::Edges;
+		};
+
+		let mut words = sections.sections[..sections.len].iter()
+			.map(|s| &s.content)
+			.filter(|&c| is_word(c));
+
+		if sections.has_errors() ||
+			!self.expect.iter().eq(words.by_ref().take(self.expect.len())) ||
+			words.next().is_some()
+		{
+			return TestResult::Decode;
+		}
+
+		if error > MAX_ERROR
+		{
+			TestResult::Timing(cycles_to_ns(error))
+		}
+		else
+		{
+			TestResult::Pass(cycles_to_ns(error))
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests
+{
+	use crate::selftest::{LoopbackTest, TestResult, LOOPBACK_TESTS};
+	use crate::sample::{Sample, SampleBuffer, Timestamp};
+	use crate::decoder::SectionBuffer;
+
+	// Capture through jumpers from yellow pin n to blue pin n,
+	// every edge is late by delay(edge number) cycles
+	fn loopback(test: &LoopbackTest, delay: impl Fn(usize) -> Timestamp) -> SampleBuffer
+	{
+		let mut buf = SampleBuffer::new();
+		let mut t: Timestamp = 0;
+		buf.push(test.gen.idle() as Sample * 0x101, 0);
+		test.gen.generate(test.data, &mut |level, ticks|
+		{
+			let s = level as Sample * 0x101;
+			if buf.samples[buf.len - 1] != s
+			{
+				buf.push(s, t + delay(buf.len));
+			}
+
+			t += ticks as Timestamp;
+		});
+
+		let last = buf.samples[buf.len - 1];
+		buf.push(last, t + 1000);
+		buf
+	}
+
+	fn check(test: &LoopbackTest, buf: &SampleBuffer) -> TestResult
+	{
+		let mut sections = SectionBuffer::new();
+		test.decode(buf, &mut sections);
+		test.check(buf, &sections)
+	}
+
+	#[test]
+	fn test_pass()
+	{
+		for test in &LOOPBACK_TESTS
+		{
+			let buf = loopback(test, |_| 1000);
+			assert_eq!(check(test, &buf), TestResult::Pass(0), "{}", test.name);
+
+			/* 90 cycles are 1 µs */
+			let buf = loopback(test, |i| if i == 5 { 27 } else { 0 });
+			assert_eq!(check(test, &buf), TestResult::Pass(300), "{}", test.name);
+		}
+	}
+
+	#[test]
+	fn test_timing()
+	{
+		let test = &LOOPBACK_TESTS[0];
+		let buf = loopback(test, |i| if i == 5 { 180 } else { 0 });
+		assert_eq!(check(test, &buf), TestResult::Timing(2000));
+	}
+
+	#[test]
+	fn test_edges()
+	{
+		/* No jumpers, the blue port stays high */
+		let test = &LOOPBACK_TESTS[1];
+		let buf = loopback(test, |_| 0);
+		for s in buf.samples[..buf.len].iter_mut()
+		{
+			*s |= 0xFF;
+		}
+
+		assert_eq!(check(test, &buf), TestResult::Edges);
+
+		let mut buf = loopback(test, |_| 0);
+		buf.overrun = true;
+		assert_eq!(check(test, &buf), TestResult::Capture);
+	}
+
+	#[test]
+	fn test_decode()
+	{
+		/* Captured fine, but sent other data than expected */
+		let test = LoopbackTest { data: b"Hallx", ..LOOPBACK_TESTS[0] };
+		let buf = loopback(&test, |_| 0);
+		assert_eq!(check(&LOOPBACK_TESTS[0], &buf), TestResult::Edges);
+		assert_eq!(check(&test, &buf), TestResult::Decode);
+	}
+}
@@ -0,0 +1,210 @@
+use std::fmt::Write as _;
+use std::io::{self, Seek, Write};
+use zip::write::SimpleFileOptions;
+use zip::{CompressionMethod, ZipWriter};
+use crate::sample::{SampleBuffer, Timestamp, CHANNELS};
+use crate::decoder::{SectionBuffer, SectionContent};
+use crate::decoder_spi::{transaction_words, write_transaction};
+use crate::vcd::CHANNEL_NAMES;
+
+/*
+ * sigrok session (.sr) for PulseView: a zip archive with the version, an ini
+ * style metadata file and the samples at a fixed rate in logic-1-n chunks.
+ * The decoded sections are stored as text in the annotations file next to them,
+ * one line per section like the output of sigrok-cli: "start-end row: text"
+ * with start and end in samples.
+ */
+
+// Samples per logic chunk, 2 bytes each
+const CHUNK: usize = 1 << 20;
+
+// Longer captures would not fit into memory when PulseView opens them
+const MAX_SAMPLES: u64 = 1 << 28;
+
+fn samplerate_string(rate: u32) -> String
+{
+	match rate
+	{
+		r if r % 1_000_000 == 0 => format!("{} MHz", r / 1_000_000),
+		r if r % 1_000 == 0 => format!("{} kHz", r / 1_000),
+		r => format!("{} Hz", r)
+	}
+}
+
+// Sample every resolution ticks of the timebase, the recorded resolution
+pub fn samplerate(buf: &SampleBuffer) -> (u32, Timestamp)
+{
+	let ticks = buf.resolution.max(1);
+	(buf.timebase.clock_rate() / ticks, ticks as Timestamp)
+}
+
+pub fn metadata(buf: &SampleBuffer) -> String
+{
+	let mut text = String::new();
+	writeln!(text, "[global]\nsigrok version=0.5.2\n").unwrap();
+	writeln!(text, "[device 1]\ncapturefile=logic-1\ntotal probes={}", CHANNELS).unwrap();
+	writeln!(text, "samplerate={}\ntotal analog=0", samplerate_string(samplerate(buf).0)).unwrap();
+	for (i, name) in CHANNEL_NAMES.iter().enumerate()
+	{
+		writeln!(text, "probe{}={}", i + 1, name).unwrap();
+	}
+
+	writeln!(text, "unitsize=2").unwrap();
+	text
+}
+
+pub fn annotations(buf: &SampleBuffer, sections: &SectionBuffer) -> String
+{
+	let (_, ticks) = samplerate(buf);
+	let mut text = String::new();
+	for s in sections.iter()
+	{
+		write!(text, "{}-{} {}: ", s.start / ticks, s.end / ticks, s.content.row()).unwrap();
+		match s.content
+		{
+			SectionContent::Transaction(_) => write_transaction(&mut text, transaction_words(sections, s)).unwrap(),
+			_ => write!(text, "{}", s.content).unwrap()
+		}
+
+		writeln!(text).unwrap();
+	}
+
+	text
+}
+
+// Level of all channels at every sample, from the first to the last entry
+pub fn expand(buf: &SampleBuffer, mut out: impl FnMut(&[u8]) -> io::Result<()>) -> io::Result<()>
+{
+	let (_, ticks) = samplerate(buf);
+	let count = if buf.len == 0 { 0 } else { buf.timestamp(buf.len - 1) / ticks + 1 };
+	if count > MAX_SAMPLES
+	{
+		return Err(io::Error::new(io::ErrorKind::InvalidInput, "capture too long for a .sr file"));
+	}
+
+	let mut chunk = Vec::with_capacity(CHUNK * 2);
+	let mut idx = 0;
+	for n in 0..count
+	{
+		while idx + 1 < buf.len && buf.timestamp(idx + 1) <= n * ticks
+		{
+			idx += 1;
+		}
+
+		chunk.extend_from_slice(&buf.samples[idx].to_le_bytes());
+		if chunk.len() == CHUNK * 2
+		{
+			out(&chunk)?;
+			chunk.clear();
+		}
+	}
+
+	if !chunk.is_empty()
+	{
+		out(&chunk)?;
+	}
+
+	Ok(())
+}
+
+pub fn write_sr<W: Write + Seek>(out: W, buf: &SampleBuffer, sections: &SectionBuffer) -> io::Result<()>
+{
+	write_sr_annotations(out, buf, &annotations(buf, sections))
+}
+
+// For host tools that only have the sections as received, not the decoder output
+pub fn write_sr_annotations<W: Write + Seek>(out: W, buf: &SampleBuffer, annotations: &str) -> io::Result<()>
+{
+	let options = SimpleFileOptions::default().compression_method(CompressionMethod::Deflated);
+	let mut zip = ZipWriter::new(out);
+
+	zip.start_file("version", options)?;
+	zip.write_all(b"2")?;
+	zip.start_file("metadata", options)?;
+	zip.write_all(metadata(buf).as_bytes())?;
+
+	let mut n = 0;
+	expand(buf, |chunk|
+	{
+		n += 1;
+		zip.start_file(format!("logic-1-{}", n), options)?;
+		zip.write_all(chunk)
+	})?;
+
+	zip.start_file("annotations", options)?;
+	zip.write_all(annotations.as_bytes())?;
+	zip.finish()?;
+	Ok(())
+}
+
+#[cfg(test)]
+mod tests
+{
+	use std::io::Read;
+	use crate::sigrok::{annotations, expand, metadata, write_sr};
+	use crate::sample::{SampleBuffer, Timebase};
+	use crate::decoder::{Section, SectionBuffer, SectionContent};
+
+	fn buffer() -> SampleBuffer
+	{
+		let mut buf = SampleBuffer::new();
+		buf.push(0x0001, 0);
+		buf.push(0x0002, 27);
+		buf.push(0x0003, 40);
+		buf.push(0x0003, 45);
+		buf.timebase = Timebase::Mhz90;
+		buf.resolution = Timebase::Mhz90.sample_period();
+		buf
+	}
+
+	#[test]
+	fn test_expand()
+	{
+		let mut out = Vec::new();
+		expand(&buffer(), |chunk| { out.extend_from_slice(chunk); Ok(()) }).unwrap();
+
+		/* 10 MHz, one sample every 9 ticks */
+		assert_eq!(out, [1, 0, 1, 0, 1, 0, 2, 0, 2, 0, 3, 0]);
+	}
+
+	#[test]
+	fn test_metadata()
+	{
+		let text = metadata(&buffer());
+		assert!(text.starts_with("[global]\nsigrok version=0.5.2\n\n[device 1]\ncapturefile=logic-1\n"));
+		assert!(text.contains("\nsamplerate=10 MHz\n"));
+		assert!(text.contains("\nprobe16=CH15\nunitsize=2\n"));
+	}
+
+	#[test]
+	fn test_archive()
+	{
+		let buf = buffer();
+		let mut sections = SectionBuffer::new();
+		sections.push(Section { start: 0, end: 18, content: SectionContent::StartBit });
+		sections.push(Section { start: 18, end: 45, content: SectionContent::Byte(0x48) });
+		assert_eq!(annotations(&buf, &sections), "0-2 data: Start\n2-5 data: $48 H\n");
+
+		/* Transactions list their words */
+		let mut spi = SectionBuffer::new();
+		spi.push(Section { start: 0, end: 45, content: SectionContent::Transaction(2) });
+		spi.push(Section { start: 9, end: 18, content: SectionContent::RxByte(0xFF) });
+		spi.push(Section { start: 9, end: 18, content: SectionContent::TxByte(0x9F) });
+		spi.push(Section { start: 27, end: 36, content: SectionContent::RxByte(0x01) });
+		spi.push(Section { start: 27, end: 36, content: SectionContent::TxByte(0x00) });
+		assert!(annotations(&buf, &spi).starts_with("0-5 transactions: 2 words, MOSI: 9F 0, MISO: FF 1\n"));
+
+		let mut file = std::io::Cursor::new(Vec::new());
+		write_sr(&mut file, &buf, &sections).unwrap();
+
+		let mut zip = zip::ZipArchive::new(file).unwrap();
+		let mut version = String::new();
+		zip.by_name("version").unwrap().read_to_string(&mut version).unwrap();
+		assert_eq!(version, "2");
+
+		let mut logic = Vec::new();
+		zip.by_name("logic-1-1").unwrap().read_to_end(&mut logic).unwrap();
+		assert_eq!(logic.len(), 12);
+		assert!(zip.by_name("annotations").is_ok());
+	}
+}
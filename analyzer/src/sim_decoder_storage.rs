@@ -1,14 +1,12 @@
 use crate::userflash::UserFlash;
+use crate::decoder::DecoderUnion;
+use crate::trigger::Trigger;
+use crate::glitch::GlitchFilter;
+use crate::stop::StopCondition;
+use crate::sample::{Sample, ALL_CHANNELS};
 
-#[derive(serde::Serialize, serde::Deserialize)]
-pub enum DecoderUnion
-{
-	None,
-	Uart(crate::decoder_uart::DecoderUart),
-	SPI(crate::decoder_spi::DecoderSPI),
-	I2C(crate::decoder_i2c::DecoderI2C),
-	OneWire(crate::decoder_onewire::DecoderOneWire),
-}
+// Menu selections of the decoder with the most inputs
+pub const SELS_LEN: usize = 12;
 
 pub struct DecoderStorage
 {
@@ -16,12 +14,14 @@ pub struct DecoderStorage
 
 impl DecoderStorage
 {
-	pub fn load(_flash: &UserFlash) -> (DecoderUnion, [u8; 8])
+	pub fn load(_flash: &UserFlash) -> (DecoderUnion, [u8; SELS_LEN], Trigger, GlitchFilter, StopCondition, Sample)
 	{
-		(DecoderUnion::None, [0; 8])
+		(DecoderUnion::None, [0; SELS_LEN], Trigger::default(), GlitchFilter::default(),
+			StopCondition::default(), ALL_CHANNELS)
 	}
 
-	pub fn save(_flash: &mut UserFlash, _decoder: &DecoderUnion, _a: &[u8])
+	pub fn save(_flash: &mut UserFlash, _decoder: &DecoderUnion, _a: &[u8], _trigger: &Trigger,
+		_filter: &GlitchFilter, _stop: &StopCondition, _channels: Sample)
 	{
 	}
 }
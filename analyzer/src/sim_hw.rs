@@ -1,7 +1,11 @@
 use crate::userflash::UserFlash;
+use crate::arena::Arena;
 
 pub const TICKS_PER_US: u32 = 90;
 
+// Size of the capture region of the board, the SRAM without the DMA buffers
+const CAPTURE_MEMORY: usize = 176 * 1024;
+
 pub struct HW
 {
 	pub tx: String,
@@ -19,3 +23,8 @@ impl HW
 		}
 	}
 }
+
+pub fn capture_memory() -> Arena
+{
+	Arena::leak(CAPTURE_MEMORY)
+}
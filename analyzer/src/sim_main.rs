@@ -1,15 +1,18 @@
 use sdl2::event::Event;
 use sdl2::keyboard::Keycode;
+use std::io::Read;
 use std::sync::mpsc;
 use std::thread;
 use crate::hw::HW;
 use crate::graphics::Graphics;
 use crate::gui::{Gui, Action};
 
+#[allow(clippy::enum_variant_names)]
 enum EventMessage
 {
 	EventKey(i32),
-	EventAction(Action)
+	EventAction(Action),
+	EventRemote(u8)
 }
 
 pub fn simulator() -> Result<(), String>
@@ -30,12 +33,31 @@ pub fn simulator() -> Result<(), String>
 				{
 					EventMessage::EventKey(key) => gui.key(key),
 					EventMessage::EventAction(action) => gui.action(action),
+					EventMessage::EventRemote(b) => gui.remote(b),
 				}
 				Err(_) => { return; }
 			}
 		}
 	});
 
+	/* Remote commands on stdin like on the serial port */
+	let remote_tx = tx.clone();
+	thread::spawn(move ||
+	{
+		for b in std::io::stdin().lock().bytes()
+		{
+			let Ok(b) = b else
+			{
+				return;
+			};
+
+			if remote_tx.send(EventMessage::EventRemote(b)).is_err()
+			{
+				return;
+			}
+		}
+	});
+
 	let mut gfx = Graphics::init()?;
 	'running: loop {
 		for event in gfx.events.poll_iter()
@@ -0,0 +1,12 @@
+use crate::generator::Generator;
+use crate::decoder::TIMER_CLOCK_RATE;
+use crate::delay::delay_ms;
+
+// The simulator has no yellow port, only take as long as the board would
+pub fn play(gen: &Generator, data: &[u8], idle: &mut impl FnMut())
+{
+	let mut ticks: u64 = 0;
+	gen.generate(data, &mut |_, t| ticks += t as u64);
+	delay_ms((ticks * 1000 / TIMER_CLOCK_RATE as u64) as u32);
+	idle();
+}
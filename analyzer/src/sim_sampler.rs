@@ -1,11 +1,184 @@
-use crate::sample::SampleBuffer;
+use crate::sample::{CaptureEnd, Sample, SampleBuffer, Timebase, Timestamp};
 use crate::delay::delay_ms;
-use crate::test_utils::load_sample_buffer;
+use crate::capture_file;
+use crate::trigger::Trigger;
+use crate::glitch::GlitchFilter;
+use crate::stream::{StreamResult, Streamer};
+use crate::stop::{FrameCheck, StopCondition};
+use crate::generator::Generator;
+use crate::player;
+use crate::transfer;
+use crate::sigrok;
+use crate::sump::{self, SumpLink};
+use crate::decoder::SectionBuffer;
+use crate::remote::{self, RemoteLink, Reply};
+use std::io::Write;
 
-pub fn sample_blocking(buf: &mut SampleBuffer)
+// Copy into the capture buffer, which has a different capacity,
+// the recordings are in timer clock cycles
+fn copy_buffer(buf: &mut SampleBuffer, samplebuf: &SampleBuffer, timebase: Timebase)
 {
 	buf.clear();
-	let samplebuf = load_sample_buffer("1Wire/OneWireSearchROM.csv");
-	*buf = samplebuf;
+	for (ts, port) in samplebuf
+	{
+		buf.push(port, timebase.ticks(ts));
+	}
+
+	buf.channels = samplebuf.channels;
+	buf.timebase = timebase;
+	buf.resolution = timebase.sample_period();
+}
+
+// Every simulated capture replays this recording, relative to the analyzer directory
+const SIM_CAPTURE: &str = "../sample_data/1Wire/OneWireSearchROM.csv";
+
+fn load_capture() -> SampleBuffer
+{
+	let mut buf = SampleBuffer::new();
+	capture_file::load_csv(SIM_CAPTURE, &mut buf).expect("Failed to load the simulated capture");
+	buf
+}
+
+// The simulator has no Stop key during a capture,
+// every capture counts as stopped so a repeated capture ends
+pub fn sample_blocking(buf: &mut SampleBuffer, trigger: &Trigger, filter: &GlitchFilter,
+	stop: &StopCondition, _frames: Option<FrameCheck>, timebase: Timebase, channels: Sample) -> CaptureEnd
+{
+	let mut samplebuf = load_capture();
+	samplebuf.keep_channels(channels);
+	delay_ms(1500);
+	if trigger.has_pre_trigger()
+	{
+		/* Without trigger the event is Stop, pressed after the last sample */
+		let event = match trigger.find(&samplebuf)
+		{
+			Some(idx) if trigger.is_armed() => samplebuf.timestamp(idx),
+			_ => samplebuf.timestamp(samplebuf.len - 1)
+		};
+
+		copy_buffer(buf, &samplebuf, timebase);
+		buf.event = Some(timebase.ticks(event));
+		filter.apply(buf);
+		CaptureEnd::Stopped
+	}
+	else if let Some(idx) = trigger.find(&samplebuf)
+	{
+		copy_buffer(buf, &samplebuf, timebase);
+		buf.rebase(idx);
+		if trigger.is_armed()
+		{
+			buf.event = Some(0);
+		}
+
+		stop.cut(buf);
+		filter.apply(buf);
+		CaptureEnd::Stopped
+	}
+	else
+	{
+		CaptureEnd::Aborted
+	}
+}
+
+// As if yellow pin n was wired to blue pin n
+pub fn sample_generator(buf: &mut SampleBuffer, gen: &Generator, data: &[u8])
+{
+	buf.clear();
+	buf.push(gen.idle() as Sample * 0x101, 0);
+
+	let mut t: Timestamp = 0;
+	gen.generate(data, &mut |level, ticks|
+	{
+		let port = level as Sample * 0x101;
+		if buf.samples[buf.len - 1] != port
+		{
+			buf.push(port, t);
+		}
+
+		t += ticks as Timestamp;
+	});
+
+	player::play(gen, data, &mut || {});
+	buf.push(buf.samples[buf.len - 1], t);
+	buf.resolution = buf.timebase.sample_period();
+}
+
+// The simulator has no serial link, the frames are encoded and discarded
+pub fn stream_blocking(trigger: &Trigger, timebase: Timebase, channels: Sample) -> StreamResult
+{
+	let mut samplebuf = load_capture();
+	samplebuf.keep_channels(channels);
 	delay_ms(1500);
+	let Some(idx) = trigger.find(&samplebuf) else
+	{
+		return StreamResult::default();
+	};
+
+	let (t0, first) = (samplebuf.timestamp(idx), samplebuf.samples[idx]);
+	let mut s = Streamer::<256>::new(first, timebase);
+	for i in (idx + 1)..samplebuf.len
+	{
+		s.edge(samplebuf.samples[i], timebase.ticks(samplebuf.timestamp(i) - t0));
+		while s.queue.pop().is_some() {}
+	}
+
+	s.end(timebase.ticks(samplebuf.timestamp(samplebuf.len - 1) - t0), false);
+	s.result
+}
+
+// The simulator has no serial link, the capture is encoded and discarded,
+// but written to capture.vcd for GTKWave and with the decoded sections
+// to capture.sr for PulseView
+pub fn transfer_blocking(buf: &SampleBuffer, sections: &SectionBuffer)
+{
+	transfer::encode(buf, sections, &mut |_| {});
+
+	let mut vcd = String::new();
+	if capture_file::write_vcd(buf, &mut vcd).is_ok()
+	{
+		let _ = std::fs::write("capture.vcd", vcd);
+	}
+
+	if let Ok(file) = std::fs::File::create("capture.sr")
+	{
+		let _ = sigrok::write_sr(file, buf, sections);
+	}
+}
+
+// The simulator captures from a file, there is nothing to stop
+pub fn remote_capture<R>(_link: &mut RemoteLink, f: impl FnOnce() -> R) -> R
+{
+	f()
+}
+
+// Remote commands come from stdin, the replies go to stdout
+pub fn remote_reply(reply: &Reply)
+{
+	let mut out = std::io::stdout().lock();
+	remote::encode_reply(reply, &mut |b| { let _ = out.write_all(&[b]); });
+	let _ = out.flush();
+}
+
+// The simulator has no serial link, the session ends right away
+struct NoLink;
+
+impl SumpLink for NoLink
+{
+	fn read(&mut self) -> Option<u8>
+	{
+		None
+	}
+
+	fn write(&mut self, _b: u8)
+	{
+	}
+}
+
+pub fn sump_blocking(buf: &mut SampleBuffer, filter: &GlitchFilter, channels: Sample) -> u32
+{
+	sump::serve(&mut NoLink, buf, &mut |buf, config|
+	{
+		sample_blocking(buf, &config.trigger(), filter, &config.stop(), None, config.timebase(),
+			channels) != CaptureEnd::Aborted
+	})
 }
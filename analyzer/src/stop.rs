@@ -0,0 +1,391 @@
+use crate::decoder::{Decoder, SectionBuffer};
+use crate::sample::{Sample, SampleBuffer, Timebase, Timestamp};
+
+// A capture counting frames is decoded after this many new edges,
+// or once no edge came for FRAME_QUIET_US
+const FRAME_EDGES: usize = 64;
+const FRAME_QUIET_US: u32 = 1000;
+
+#[derive(serde::Serialize, serde::Deserialize, Clone, Copy, PartialEq, Eq, Debug, Default)]
+pub enum StopKind
+{
+	// Only Stop or a full buffer end the capture
+	#[default]
+	Off,
+	// Time after the start or the trigger
+	Duration,
+	// Number of level changes
+	Edges,
+	// Number of data words of the protocol decoder. The capture is decoded
+	// while it runs and then cut after the last word. With a pre-trigger it
+	// runs into a ring that can not be decoded, so it only gets cut.
+	Frames,
+	// All channels in mask have the level given in value
+	Pattern,
+	// No level change for the given time
+	Idle
+}
+
+// Ends a capture before the buffer is full
+#[derive(serde::Serialize, serde::Deserialize, Clone, Copy, PartialEq, Eq, Debug, Default)]
+pub struct StopCondition
+{
+	pub kind: StopKind,
+
+	// Duration and Idle in microseconds
+	pub time_us: u32,
+
+	// Edges and Frames
+	pub count: u32,
+
+	// Pattern, like the trigger pattern
+	pub mask: Sample,
+	pub value: Sample
+}
+
+impl StopCondition
+{
+	pub fn is_valid(&self) -> bool
+	{
+		match self.kind
+		{
+			StopKind::Pattern => self.mask != 0,
+			StopKind::Edges | StopKind::Frames => self.count != 0,
+			StopKind::Duration | StopKind::Idle => self.time_us != 0,
+			StopKind::Off => true
+		}
+	}
+
+	fn ticks(&self, timebase: Timebase) -> Timestamp
+	{
+		self.time_us as Timestamp * timebase.ticks_per_us() as Timestamp
+	}
+
+	// Apply the live conditions to a recorded buffer starting at t=0
+	pub fn cut(&self, buf: &mut SampleBuffer)
+	{
+		let mut check = StopCheck::new(self, buf.timebase);
+		for i in 1..buf.len
+		{
+			let ts = buf.timestamp(i);
+			if let Some(end) = check.idle(ts)
+			{
+				buf.cut(end);
+				return;
+			}
+
+			if check.edge(buf.samples[i], ts)
+			{
+				buf.cut(ts);
+				return;
+			}
+		}
+	}
+
+	// Cut the finished capture after the given number of decoded data words
+	pub fn cut_frames(&self, buf: &mut SampleBuffer, sections: &SectionBuffer) -> bool
+	{
+		match self.frames_end(sections)
+		{
+			Some(end) => { buf.cut(end); true }
+			None => false
+		}
+	}
+
+	// End of the last data word that is counted,
+	// the Rx and Tx section of an SPI word cover the same time and count once
+	fn frames_end(&self, sections: &SectionBuffer) -> Option<Timestamp>
+	{
+		if self.kind != StopKind::Frames
+		{
+			return None;
+		}
+
+		let n = (self.count as usize).checked_sub(1)?;
+		let mut last = None;
+		sections.sections[..sections.len].iter()
+			.filter(|s| s.content.is_frame())
+			.filter(|s|
+			{
+				let word = Some((s.start, s.end));
+				word != core::mem::replace(&mut last, word)
+			})
+			.nth(n)
+			.map(|s| s.end)
+	}
+}
+
+// Checks the live conditions while capturing, times relative to the start
+pub struct StopCheck<'a>
+{
+	cond: &'a StopCondition,
+	ticks: Timestamp,
+	edges: u32,
+	last: Timestamp
+}
+
+impl<'a> StopCheck<'a>
+{
+	pub fn new(cond: &'a StopCondition, timebase: Timebase) -> Self
+	{
+		StopCheck
+		{
+			cond,
+			ticks: cond.ticks(timebase),
+			edges: 0,
+			last: 0
+		}
+	}
+
+	// A level change was recorded, true if the capture ends with it
+	pub fn edge(&mut self, level: Sample, ts: Timestamp) -> bool
+	{
+		self.edges += 1;
+		self.last = ts;
+		match self.cond.kind
+		{
+			StopKind::Edges => self.edges >= self.cond.count,
+			StopKind::Pattern => (level & self.cond.mask) == (self.cond.value & self.cond.mask),
+			_ => false
+		}
+	}
+
+	// Time has passed without a change until now,
+	// returns when the capture should have ended if that is before now
+	pub fn idle(&self, now: Timestamp) -> Option<Timestamp>
+	{
+		let end = match self.cond.kind
+		{
+			StopKind::Duration => self.ticks,
+			StopKind::Idle => self.last + self.ticks,
+			_ => return None
+		};
+
+		if now >= end { Some(end) } else { None }
+	}
+}
+
+// Decodes a linear capture while it runs, for StopKind::Frames
+pub struct FrameCheck<'a>
+{
+	cond: &'a StopCondition,
+	decoder: &'a dyn Decoder,
+	sections: &'a mut SectionBuffer,
+	quiet: Timestamp,
+
+	// Entries of the buffer at the last decoding
+	decoded: usize
+}
+
+impl<'a> FrameCheck<'a>
+{
+	// None unless the condition counts the frames of a decoder
+	pub fn new(cond: &'a StopCondition, timebase: Timebase, decoder: Option<&'a dyn Decoder>,
+		sections: &'a mut SectionBuffer) -> Option<Self>
+	{
+		if cond.kind != StopKind::Frames
+		{
+			return None;
+		}
+
+		Some(FrameCheck
+		{
+			cond,
+			decoder: decoder?,
+			sections,
+			quiet: FRAME_QUIET_US as Timestamp * timebase.ticks_per_us() as Timestamp,
+			decoded: 0
+		})
+	}
+
+	// All changes until now are in buf, true if it holds the frames.
+	// Decoding takes long, so it only runs after a burst of edges or a pause.
+	pub fn check(&mut self, buf: &mut SampleBuffer, now: Timestamp) -> bool
+	{
+		/* Changes read after now are checked the next time */
+		let last = buf.timestamp(buf.len - 1);
+		if buf.len == self.decoded || last >= now
+			|| (buf.len - self.decoded < FRAME_EDGES && now - last < self.quiet)
+		{
+			return false;
+		}
+
+		/* The level holds until now, the end lets the decoder finish the last frame,
+		 * a full buffer keeps room for it */
+		self.decoded = buf.len;
+		buf.push(buf.samples[buf.len - 1], now);
+		self.sections.clear();
+		let _ = self.decoder.decode(buf, self.sections);
+		buf.cut(last);
+
+		self.cond.frames_end(self.sections).is_some()
+	}
+}
+
+#[cfg(test)]
+mod tests
+{
+	use crate::stop::{FrameCheck, StopCondition, StopKind};
+	use crate::sample::{SampleBuffer, Timebase};
+	use crate::decoder_uart::{DecoderUart, DataBits, Parity, StopBits};
+	use crate::decoder_spi::{BitOrder, CsPolarity, DecoderSPI};
+	use crate::decoder::{Decoder, SectionBuffer};
+	use crate::test_utils::load_sample_buffer;
+
+	fn buffer() -> SampleBuffer
+	{
+		let mut buf = SampleBuffer::new();
+		buf.push(0b00, 0);
+		buf.push(0b01, 90);
+		buf.push(0b11, 180);
+		buf.push(0b10, 9000);
+		buf.push(0b00, 9090);
+		buf.push(0b00, 90000);
+		buf
+	}
+
+	fn cut(cond: StopCondition) -> SampleBuffer
+	{
+		let mut buf = buffer();
+		cond.cut(&mut buf);
+		buf
+	}
+
+	#[test]
+	fn test_live()
+	{
+		let buf = cut(StopCondition { kind: StopKind::Edges, count: 2, ..Default::default() });
+		assert_eq!(buf.len, 3);
+		assert_eq!(buf.timestamp(2), 180);
+
+		let buf = cut(StopCondition { kind: StopKind::Duration, time_us: 50, ..Default::default() });
+		assert_eq!(buf.len, 4);
+		assert_eq!(buf.get_content(3, 0), Some((true, 4500)));
+
+		let buf = cut(StopCondition { kind: StopKind::Idle, time_us: 20, ..Default::default() });
+		assert_eq!(buf.len, 4);
+		assert_eq!(buf.timestamp(3), 180 + 1800);
+
+		let buf = cut(StopCondition { kind: StopKind::Pattern, mask: 0b11, value: 0b10, ..Default::default() });
+		assert_eq!(buf.len, 4);
+		assert_eq!(buf.samples[3], 0b10);
+
+		let buf = cut(StopCondition::default());
+		assert_eq!(buf.len, 6);
+
+		/* Same ticks at 1 MHz are 90 times longer */
+		let mut buf = buffer();
+		buf.timebase = Timebase::Mhz1;
+		StopCondition { kind: StopKind::Duration, time_us: 4500, ..Default::default() }.cut(&mut buf);
+		assert_eq!(buf.len, 4);
+		assert_eq!(buf.get_content(3, 0), Some((true, 4500)));
+	}
+
+	#[test]
+	fn test_frames()
+	{
+		let uart = DecoderUart
+		{
+			rx_pin: 0,
+			tx_pin: 1,
+			databits: DataBits::Eight,
+			parity: Parity::None,
+			stopbits: StopBits::One,
+			baudrate: 300
+		};
+
+		let mut buf = load_sample_buffer("UART/UART_8N1_300_Hallo.csv");
+		let mut sections = SectionBuffer::new();
+		uart.decode(&buf, &mut sections).unwrap();
+
+		let cond = StopCondition { kind: StopKind::Frames, count: 2, ..Default::default() };
+		assert!(cond.cut_frames(&mut buf, &sections));
+
+		sections.clear();
+		uart.decode(&buf, &mut sections).unwrap();
+		assert_eq!(sections.sections[..sections.len].iter()
+			.filter(|s| s.content.is_frame()).count(), 2);
+
+		/* Invalid count from the flash */
+		let cond = StopCondition { kind: StopKind::Frames, count: 0, ..Default::default() };
+		assert!(!cond.cut_frames(&mut buf, &sections));
+	}
+
+	#[test]
+	fn test_frames_live()
+	{
+		let uart = DecoderUart
+		{
+			rx_pin: 0,
+			tx_pin: 1,
+			databits: DataBits::Eight,
+			parity: Parity::None,
+			stopbits: StopBits::One,
+			baudrate: 300
+		};
+
+		/* Replay the capture edge by edge, checking just before each edge */
+		let src = load_sample_buffer("UART/UART_8N1_300_Hallo.csv");
+		let mut buf = SampleBuffer::new();
+		buf.timebase = src.timebase;
+		buf.push(src.samples[0], src.timestamp(0));
+
+		let cond = StopCondition { kind: StopKind::Frames, count: 2, ..Default::default() };
+		let mut sections = SectionBuffer::new();
+		let mut check = FrameCheck::new(&cond, src.timebase, Some(&uart), &mut sections).unwrap();
+		let stop = (1..src.len).find(|&i|
+		{
+			if check.check(&mut buf, src.timestamp(i) - 1)
+			{
+				return true;
+			}
+
+			buf.push(src.samples[i], src.timestamp(i));
+			false
+		});
+
+		/* Stopped in the pause after the second frame, the final entry is gone again */
+		let i = stop.unwrap();
+		assert!(i < src.len - 1);
+		assert_eq!(buf.len, i);
+		assert_eq!(buf.timestamp(i - 1), src.timestamp(i - 1));
+
+		/* The sampler ends the capture at the time of the check */
+		buf.push(src.samples[i - 1], src.timestamp(i) - 1);
+		sections.clear();
+		uart.decode(&buf, &mut sections).unwrap();
+		assert_eq!(sections.sections[..sections.len].iter().filter(|s| s.content.is_frame()).count(), 2);
+
+		let mut sections = SectionBuffer::new();
+		assert!(FrameCheck::new(&cond, src.timebase, None, &mut sections).is_none());
+	}
+
+	#[test]
+	fn test_frames_spi()
+	{
+		let spi = DecoderSPI
+		{
+			mosi_pin: 0,
+			miso_pin: 1,
+			sck_pin: 2,
+			cs_pin: 3,
+			cs_polarity: CsPolarity::ActiveLow,
+			mode: 0,
+			bitorder: BitOrder::MsbFirst,
+			word_size: 8
+		};
+
+		/* Each word is an Rx and a Tx section */
+		let mut buf = load_sample_buffer("SPI/SPI_Mode0_Hallo.csv");
+		let mut sections = SectionBuffer::new();
+		spi.decode(&buf, &mut sections).unwrap();
+
+		let cond = StopCondition { kind: StopKind::Frames, count: 2, ..Default::default() };
+		assert!(cond.cut_frames(&mut buf, &sections));
+
+		sections.clear();
+		spi.decode(&buf, &mut sections).unwrap();
+		assert_eq!(sections.sections[..sections.len].iter()
+			.filter(|s| s.content.is_frame()).count(), 4);
+	}
+}
@@ -0,0 +1,249 @@
+use crate::sample::{Sample, Timebase, Timestamp};
+
+// Frames of a streaming capture, postcard encoded and COBS framed,
+// so every frame ends with a zero byte and the host can resync on it.
+// The dumper decodes them with this type as well.
+#[derive(serde::Serialize, serde::Deserialize, Clone, Copy, PartialEq, Eq, Debug)]
+pub enum StreamFrame
+{
+	// Streaming started at t=0 with the level of all channels,
+	// ticks run at clock_rate Hz, one sample every resolution ticks
+	Start { clock_rate: u32, resolution: u32, level: Sample },
+
+	// Level change, delta in ticks since the previous Start or Edge
+	Edge { delta: Timestamp, level: Sample },
+
+	// Level changes dropped because the serial link was too slow,
+	// the current level is sent with the next Edge
+	Lost { count: u32 },
+
+	// Streaming stopped, overrun if the capture engine lost samples
+	End { delta: Timestamp, overrun: bool }
+}
+
+// Largest encoded frame including COBS overhead and delimiter
+const FRAME_MAX: usize = 20;
+
+// Bytes waiting for the serial port, filled by the capture loop
+// and drained whenever the transmitter is ready
+pub struct TxQueue<const N: usize>
+{
+	buf: [u8; N],
+	head: usize,
+	len: usize
+}
+
+impl<const N: usize> TxQueue<N>
+{
+	pub fn new() -> Self
+	{
+		TxQueue
+		{
+			buf: [0; N],
+			head: 0,
+			len: 0
+		}
+	}
+
+	pub fn free(&self) -> usize
+	{
+		N - self.len
+	}
+
+	pub fn is_empty(&self) -> bool
+	{
+		self.len == 0
+	}
+
+	pub fn peek(&self) -> Option<u8>
+	{
+		if self.len == 0 { None } else { Some(self.buf[self.head]) }
+	}
+
+	pub fn pop(&mut self) -> Option<u8>
+	{
+		if self.len == 0
+		{
+			return None;
+		}
+
+		let b = self.buf[self.head];
+		self.head = (self.head + 1) % N;
+		self.len -= 1;
+		Some(b)
+	}
+
+	fn push(&mut self, bytes: &[u8])
+	{
+		for &b in bytes
+		{
+			self.buf[(self.head + self.len) % N] = b;
+			self.len += 1;
+		}
+	}
+}
+
+// Counters of a finished streaming capture
+#[derive(Clone, Copy, Default)]
+pub struct StreamResult
+{
+	pub edges: u32,
+	pub lost: u32,
+	pub overrun: bool
+}
+
+// Turns level changes into frames, drops them instead of blocking
+// the capture when the queue is full
+pub struct Streamer<const N: usize>
+{
+	pub queue: TxQueue<N>,
+	last: Timestamp,
+	pending_lost: u32,
+	pub result: StreamResult
+}
+
+impl<const N: usize> Streamer<N>
+{
+	pub fn new(level: Sample, timebase: Timebase) -> Self
+	{
+		let mut s = Streamer
+		{
+			queue: TxQueue::new(),
+			last: 0,
+			pending_lost: 0,
+			result: StreamResult::default()
+		};
+
+		/* Delimiter first, the host may have received text before */
+		s.queue.push(&[0]);
+		s.send(&StreamFrame::Start
+		{
+			clock_rate: timebase.clock_rate(),
+			resolution: timebase.sample_period(),
+			level
+		});
+		s
+	}
+
+	// Queue a frame, false if it does not fit
+	fn send(&mut self, frame: &StreamFrame) -> bool
+	{
+		let mut tmp = [0; FRAME_MAX];
+		let Ok(bytes) = postcard::to_slice_cobs(frame, &mut tmp) else
+		{
+			return false;
+		};
+
+		if bytes.len() > self.queue.free()
+		{
+			return false;
+		}
+
+		self.queue.push(bytes);
+		true
+	}
+
+	fn send_lost(&mut self) -> bool
+	{
+		if self.pending_lost > 0
+		{
+			if !self.send(&StreamFrame::Lost { count: self.pending_lost })
+			{
+				return false;
+			}
+
+			self.pending_lost = 0;
+		}
+
+		true
+	}
+
+	// ts: Ticks since the start
+	pub fn edge(&mut self, level: Sample, ts: Timestamp)
+	{
+		if self.send_lost() &&
+			self.send(&StreamFrame::Edge { delta: ts - self.last, level })
+		{
+			self.last = ts;
+			self.result.edges += 1;
+			return;
+		}
+
+		self.pending_lost += 1;
+		self.result.lost += 1;
+	}
+
+	// False until the final frames fit, drain the queue in between
+	pub fn end(&mut self, ts: Timestamp, overrun: bool) -> bool
+	{
+		self.result.overrun = overrun;
+		self.send_lost() &&
+			self.send(&StreamFrame::End { delta: ts - self.last, overrun })
+	}
+}
+
+#[cfg(test)]
+mod tests
+{
+	use crate::stream::{StreamFrame, Streamer};
+	use crate::sample::Timebase;
+
+	fn frames<const N: usize>(s: &mut Streamer<N>) -> Vec<StreamFrame>
+	{
+		let mut bytes = Vec::new();
+		while let Some(b) = s.queue.pop()
+		{
+			bytes.push(b);
+		}
+
+		bytes.split_mut(|&b| b == 0)
+			.filter(|f| !f.is_empty())
+			.map(|f| postcard::from_bytes_cobs(f).unwrap())
+			.collect()
+	}
+
+	#[test]
+	fn test_frames()
+	{
+		let mut s = Streamer::<256>::new(0x0101, Timebase::Mhz90);
+		s.edge(0x0100, 90);
+		s.edge(0x0000, 1u64 << 40);
+		assert!(s.end((1u64 << 40) + 5, false));
+		assert_eq!(frames(&mut s),
+		[
+			StreamFrame::Start { clock_rate: 90_000_000, resolution: 9, level: 0x0101 },
+			StreamFrame::Edge { delta: 90, level: 0x0100 },
+			StreamFrame::Edge { delta: (1u64 << 40) - 90, level: 0 },
+			StreamFrame::End { delta: 5, overrun: false }
+		]);
+		assert_eq!(s.result.edges, 2);
+		assert_eq!(s.result.lost, 0);
+	}
+
+	#[test]
+	fn test_lost()
+	{
+		let mut s = Streamer::<14>::new(0, Timebase::Mhz1);
+		s.edge(1, 100);
+		s.edge(0, 200);
+		s.edge(1, 300);
+		assert_eq!(s.result.edges, 1);
+		assert_eq!(s.result.lost, 2);
+		assert!(!s.end(400, false));
+
+		/* Host catches up, the next edge is timed from the last one sent */
+		let mut sent = frames(&mut s);
+		s.edge(0, 500);
+		sent.extend(frames(&mut s));
+		assert!(s.end(600, true));
+		sent.extend(frames(&mut s));
+		assert_eq!(sent,
+		[
+			StreamFrame::Start { clock_rate: 1_000_000, resolution: 1, level: 0 },
+			StreamFrame::Edge { delta: 100, level: 1 },
+			StreamFrame::Lost { count: 2 },
+			StreamFrame::Edge { delta: 400, level: 0 },
+			StreamFrame::End { delta: 100, overrun: true }
+		]);
+	}
+}
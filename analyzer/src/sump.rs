@@ -0,0 +1,396 @@
+use crate::sample::{Sample, SampleBuffer, Timebase};
+use crate::trigger::{Trigger, TriggerKind};
+use crate::stop::{StopCondition, StopKind};
+
+/*
+ * SUMP / Openbench Logic Sniffer protocol on the serial port, so sigrok,
+ * PulseView and the OLS client can use the board as an 8 channel analyzer.
+ *
+ * Short commands are one byte, long commands have bit 7 set and are followed
+ * by a 32 bit argument, lowest byte first. The samples are sent after the
+ * capture at the requested rate, the newest first, one byte per enabled group.
+ */
+
+const CMD_RESET: u8 = 0x00;
+const CMD_RUN: u8 = 0x01;
+const CMD_ID: u8 = 0x02;
+const CMD_METADATA: u8 = 0x04;
+const CMD_DIVIDER: u8 = 0x80;
+const CMD_COUNT: u8 = 0x81;
+const CMD_FLAGS: u8 = 0x82;
+
+// Trigger stage 0, the other stages are accepted but not used
+const CMD_TRIGGER_MASK: u8 = 0xC0;
+const CMD_TRIGGER_VALUE: u8 = 0xC1;
+
+// Sample rate is CLOCK / (divider + 1)
+const CLOCK: u32 = 100_000_000;
+
+// Highest rate with a sample period of 100 ns
+const MAX_RATE: u32 = 10_000_000;
+
+// The read count is sent as a multiple of 4 in 16 bit
+const MAX_SAMPLES: u32 = 256 * 1024;
+
+const PROBES: u32 = 8;
+
+// Channel groups 0-3 disabled
+const FLAGS_GROUPS_SHIFT: u32 = 2;
+
+// Settings sent by the client before Run
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct SumpConfig
+{
+	pub mask: u32,
+	pub value: u32,
+	pub divider: u32,
+
+	// Samples sent, delay of them after the trigger
+	pub read_count: u32,
+	pub delay_count: u32,
+	pub flags: u32
+}
+
+impl Default for SumpConfig
+{
+	fn default() -> Self
+	{
+		SumpConfig
+		{
+			mask: 0,
+			value: 0,
+			divider: CLOCK / MAX_RATE - 1,
+			read_count: 4096,
+			delay_count: 4096,
+			flags: 0
+		}
+	}
+}
+
+impl SumpConfig
+{
+	pub fn rate(&self) -> u32
+	{
+		CLOCK / (self.divider + 1)
+	}
+
+	// Exact timestamps for fast rates, the coarsest clock that still fits for slow ones
+	pub fn timebase(&self) -> Timebase
+	{
+		if self.rate() <= 1_000_000 { Timebase::Mhz1 } else { Timebase::Mhz90 }
+	}
+
+	// Stage 0 fires while all channels in mask have the level in value,
+	// also right at the start, an empty mask fires immediately
+	pub fn trigger(&self) -> Trigger
+	{
+		let mask = (self.mask & 0xFF) as Sample;
+		if mask == 0
+		{
+			return Trigger::default();
+		}
+
+		let pre = self.read_count.saturating_sub(self.delay_count);
+		let post = 100 - (pre as u64 * 100 / self.read_count.max(1) as u64) as u8;
+		Trigger
+		{
+			kind: TriggerKind::Match,
+			channel: 0,
+			mask,
+			value: self.value as Sample & mask,
+			post_trigger: post.clamp(1, 100)
+		}
+	}
+
+	// The capture ends when all samples after the trigger are recorded
+	pub fn stop(&self) -> StopCondition
+	{
+		let us = (self.delay_count as u64 * 1_000_000).div_ceil(self.rate() as u64);
+		StopCondition
+		{
+			kind: StopKind::Duration,
+			time_us: us.clamp(1, u32::MAX as u64) as u32,
+			..Default::default()
+		}
+	}
+
+	fn groups(&self) -> impl Iterator<Item = u32> + '_
+	{
+		(0..4).filter(|g| self.flags & (1 << (FLAGS_GROUPS_SHIFT + g)) == 0)
+	}
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum SumpRequest
+{
+	Reset,
+	Run,
+	Id,
+	Metadata
+}
+
+pub struct Sump
+{
+	cmd: [u8; 5],
+	len: usize,
+	pub config: SumpConfig
+}
+
+impl Sump
+{
+	pub fn new() -> Self
+	{
+		Sump
+		{
+			cmd: [0; 5],
+			len: 0,
+			config: SumpConfig::default()
+		}
+	}
+
+	// Settings are taken over directly, the others are returned
+	pub fn feed(&mut self, b: u8) -> Option<SumpRequest>
+	{
+		self.cmd[self.len] = b;
+		self.len += 1;
+		if self.cmd[0] & 0x80 != 0
+		{
+			if self.len < self.cmd.len()
+			{
+				return None;
+			}
+
+			self.len = 0;
+			let arg = u32::from_le_bytes([self.cmd[1], self.cmd[2], self.cmd[3], self.cmd[4]]);
+			self.set(self.cmd[0], arg);
+			return None;
+		}
+
+		self.len = 0;
+		match b
+		{
+			CMD_RESET => Some(SumpRequest::Reset),
+			CMD_RUN => Some(SumpRequest::Run),
+			CMD_ID => Some(SumpRequest::Id),
+			CMD_METADATA => Some(SumpRequest::Metadata),
+			_ => None
+		}
+	}
+
+	fn set(&mut self, cmd: u8, arg: u32)
+	{
+		let c = &mut self.config;
+		match cmd
+		{
+			CMD_DIVIDER => c.divider = (arg & 0xFF_FFFF).max(CLOCK / MAX_RATE - 1),
+			CMD_COUNT =>
+			{
+				c.read_count = ((arg & 0xFFFF) + 1) * 4;
+				c.delay_count = ((arg >> 16) + 1) * 4;
+			}
+			CMD_FLAGS => c.flags = arg,
+			CMD_TRIGGER_MASK => c.mask = arg,
+			CMD_TRIGGER_VALUE => c.value = arg,
+			_ => {}
+		}
+	}
+}
+
+pub fn id(out: &mut impl FnMut(u8))
+{
+	b"1ALS".iter().for_each(|&b| out(b));
+}
+
+pub fn metadata(out: &mut impl FnMut(u8))
+{
+	let mut string = |key: u8, s: &str|
+	{
+		out(key);
+		s.bytes().for_each(&mut *out);
+		out(0);
+	};
+	string(0x01, "ITS-Board Logic Analyzer");
+	string(0x02, env!("CARGO_PKG_VERSION"));
+
+	for (key, value) in [(0x20, PROBES), (0x21, MAX_SAMPLES), (0x23, MAX_RATE), (0x24, 2)]
+	{
+		out(key);
+		value.to_be_bytes().iter().for_each(|&b| out(b));
+	}
+
+	out(0x00);
+}
+
+// Level of the channels at the requested rate around the trigger,
+// from the newest sample to the oldest
+pub fn send_samples(buf: &SampleBuffer, config: &SumpConfig, out: &mut impl FnMut(u8))
+{
+	let t0 = buf.event.unwrap_or(0) as i128;
+	let clock_rate = buf.timebase.clock_rate() as i128;
+	let rate = config.rate() as i128;
+	let pre = config.read_count.saturating_sub(config.delay_count) as i128;
+
+	let mut idx = buf.len.saturating_sub(1);
+	for k in (0..config.read_count as i128).rev()
+	{
+		let t = t0 + (k - pre) * clock_rate / rate;
+		while idx > 0 && buf.timestamp(idx) as i128 > t
+		{
+			idx -= 1;
+		}
+
+		let level = if buf.len == 0 { 0 } else { buf.samples[idx] };
+		for g in config.groups()
+		{
+			out(if g < 2 { (level >> (g * 8)) as u8 } else { 0 });
+		}
+	}
+}
+
+// The serial port as seen by the protocol
+pub trait SumpLink
+{
+	// Next received byte, None ends the session
+	fn read(&mut self) -> Option<u8>;
+	fn write(&mut self, b: u8);
+}
+
+// Answer the client until the link ends, returns the number of captures sent.
+// capture records into the buffer, false if it was aborted.
+pub fn serve(link: &mut impl SumpLink, buf: &mut SampleBuffer,
+	capture: &mut impl FnMut(&mut SampleBuffer, &SumpConfig) -> bool) -> u32
+{
+	let mut sump = Sump::new();
+	let mut runs = 0;
+	while let Some(b) = link.read()
+	{
+		match sump.feed(b)
+		{
+			Some(SumpRequest::Id) => id(&mut |b| link.write(b)),
+			Some(SumpRequest::Metadata) => metadata(&mut |b| link.write(b)),
+			Some(SumpRequest::Run) =>
+			{
+				if capture(buf, &sump.config)
+				{
+					send_samples(buf, &sump.config, &mut |b| link.write(b));
+					runs += 1;
+				}
+			}
+			Some(SumpRequest::Reset) | None => {}
+		}
+	}
+
+	runs
+}
+
+#[cfg(test)]
+mod tests
+{
+	use crate::sump::{serve, SumpConfig, SumpLink};
+	use crate::sample::{SampleBuffer, Timebase, Timestamp};
+	use crate::trigger::{Trigger, TriggerKind};
+	use crate::stop::StopKind;
+
+	struct FakeLink<'a>
+	{
+		rx: std::slice::Iter<'a, u8>,
+		tx: Vec<u8>
+	}
+
+	impl SumpLink for FakeLink<'_>
+	{
+		fn read(&mut self) -> Option<u8>
+		{
+			self.rx.next().copied()
+		}
+
+		fn write(&mut self, b: u8)
+		{
+			self.tx.push(b);
+		}
+	}
+
+	// Runs a session, the capture sees the config and gets the edges
+	fn session(rx: &[u8], edges: &[(Timestamp, u16)], event: Option<Timestamp>)
+		-> (Vec<u8>, Vec<SumpConfig>)
+	{
+		let mut link = FakeLink { rx: rx.iter(), tx: Vec::new() };
+		let mut buf = SampleBuffer::new();
+		let mut configs = Vec::new();
+		serve(&mut link, &mut buf, &mut |buf, config|
+		{
+			configs.push(*config);
+			buf.clear();
+			for &(ts, s) in edges
+			{
+				buf.push(s, ts);
+			}
+
+			buf.timebase = config.timebase();
+			buf.event = event;
+			true
+		});
+
+		(link.tx, configs)
+	}
+
+	#[test]
+	fn test_id()
+	{
+		/* Clients reset with five zero bytes first */
+		let (tx, _) = session(&[0, 0, 0, 0, 0, 0x02], &[], None);
+		assert_eq!(tx, b"1ALS");
+
+		let (tx, _) = session(&[0x04], &[], None);
+		assert!(tx.starts_with(b"\x01ITS-Board Logic Analyzer\x00\x02"));
+		assert!(tx.ends_with(&[0x20, 0, 0, 0, 8, 0x21, 0, 4, 0, 0, 0x23, 0, 0x98, 0x96, 0x80, 0x24, 0, 0, 0, 2, 0]));
+	}
+
+	#[test]
+	fn test_config()
+	{
+		let rx = [
+			0x80, 99, 0, 0, 0,		/* 1 MHz */
+			0x81, 3, 0, 1, 0,		/* Read 16, 8 after the trigger */
+			0xC0, 0x03, 0, 0, 0,
+			0xC1, 0x01, 0, 0, 0,
+			0x82, 0x38, 0, 0, 0,	/* Only group 0 */
+			0x01
+		];
+		let (_, configs) = session(&rx, &[(0, 0)], None);
+		let c = configs[0];
+		assert_eq!((c.rate(), c.read_count, c.delay_count), (1_000_000, 16, 8));
+		assert_eq!(c.timebase(), Timebase::Mhz1);
+		assert_eq!(c.trigger(), Trigger
+		{
+			kind: TriggerKind::Match,
+			channel: 0,
+			mask: 0x03,
+			value: 0x01,
+			post_trigger: 50
+		});
+		assert_eq!((c.stop().kind, c.stop().time_us), (StopKind::Duration, 8));
+
+		/* No mask, recording starts right away */
+		assert_eq!(SumpConfig::default().trigger(), Trigger::default());
+	}
+
+	#[test]
+	fn test_samples()
+	{
+		let rx = [
+			0x80, 99, 0, 0, 0,
+			0x81, 1, 0, 0, 0,		/* Read 8, 4 after the trigger */
+			0x82, 0x30, 0, 0, 0,	/* Groups 0 and 1 */
+			0x01
+		];
+
+		/* Triggered at 5 µs, a pulse on channel 0 from 3 to 6 µs, yellow channel 8 high */
+		let edges = [(0, 0x0100), (3, 0x0101), (6, 0x0100), (9, 0x0100)];
+		let (tx, _) = session(&rx, &edges, Some(5));
+		assert_eq!(tx, [
+			0x00, 0x01, 0x00, 0x01, 0x00, 0x01, 0x01, 0x01,
+			0x01, 0x01, 0x01, 0x01, 0x00, 0x01, 0x00, 0x01
+		]);
+	}
+}
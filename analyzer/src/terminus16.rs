@@ -1,6 +1,6 @@
 use crate::font::Font;
 
-const TERMINUS16_BITMAP: [u8; 2288] =
+const TERMINUS16_BITMAP: [u8; 2448] =
 [
 	0x00,
 	0x00,
@@ -2479,6 +2479,186 @@ const TERMINUS16_BITMAP: [u8; 2288] =
 	0x00,
 	0x00,
 	0x00,
+	0x00,
+
+	/* ICON_TRIGGER part 1 */
+	0x00,
+	0x00,
+	0x00,
+	0x00,
+	0x01,
+	0x02,
+	0x04,
+	0x00,
+	0x00,
+	0x00,
+	0x00,
+	0x00,
+	0x00,
+	0x00,
+	0xff,
+	0x00,
+
+	/* ICON_TRIGGER part 2 */
+	0x00,
+	0xff,
+	0x80,
+	0x80,
+	0xc0,
+	0xa0,
+	0x90,
+	0x80,
+	0x80,
+	0x80,
+	0x80,
+	0x80,
+	0x80,
+	0x80,
+	0x80,
+	0x00,
+
+	/* ICON_BANK part 1 */
+	0x00,
+	0x00,
+	0x73,
+	0x52,
+	0x52,
+	0xde,
+	0x00,
+	0x00,
+	0x00,
+	0x00,
+	0x73,
+	0x52,
+	0x52,
+	0xde,
+	0x00,
+	0x00,
+
+	/* ICON_BANK part 2 */
+	0x00,
+	0x08,
+	0x9c,
+	0xaa,
+	0x88,
+	0xc8,
+	0x08,
+	0x08,
+	0x08,
+	0x08,
+	0x88,
+	0x88,
+	0xaa,
+	0xdc,
+	0x08,
+	0x00,
+
+	/* ICON_SINGLE part 1 */
+	0x00,
+	0x00,
+	0x00,
+	0x07,
+	0x04,
+	0x04,
+	0x04,
+	0x04,
+	0x04,
+	0x04,
+	0x04,
+	0x04,
+	0xfc,
+	0x00,
+	0x00,
+	0x00,
+
+	/* ICON_SINGLE part 2 */
+	0x00,
+	0x00,
+	0x00,
+	0xc4,
+	0x44,
+	0x44,
+	0x44,
+	0x44,
+	0x44,
+	0x44,
+	0x44,
+	0x44,
+	0x7c,
+	0x04,
+	0x00,
+	0x00,
+
+	/* ICON_REPEAT part 1 */
+	0x00,
+	0x00,
+	0x00,
+	0x3c,
+	0x24,
+	0x24,
+	0x24,
+	0x24,
+	0x24,
+	0x24,
+	0x24,
+	0x24,
+	0xe7,
+	0x00,
+	0x00,
+	0x00,
+
+	/* ICON_REPEAT part 2 */
+	0x00,
+	0x00,
+	0x00,
+	0xf3,
+	0x92,
+	0x92,
+	0x92,
+	0x92,
+	0x92,
+	0x92,
+	0x92,
+	0x92,
+	0x9e,
+	0x00,
+	0x00,
+	0x00,
+
+	/* ICON_GENERATOR part 1 */
+	0x00,
+	0x00,
+	0x00,
+	0x00,
+	0x3c,
+	0x24,
+	0x24,
+	0x24,
+	0x24,
+	0x24,
+	0x24,
+	0xe7,
+	0x00,
+	0x00,
+	0x00,
+	0x00,
+
+	/* ICON_GENERATOR part 2 */
+	0x00,
+	0x00,
+	0x00,
+	0x00,
+	0x00,
+	0x04,
+	0x06,
+	0xfe,
+	0x06,
+	0x04,
+	0x00,
+	0x00,
+	0x00,
+	0x00,
+	0x00,
 	0x00
 ];
 
@@ -2504,7 +2684,12 @@ pub enum Icon
 	RightFast = 166,
 	TimeExpand = 168,
 	Cycle = 170,
-	Start = 172
+	Start = 172,
+	Trigger = 175,
+	Bank = 177,
+	Single = 179,
+	Repeat = 181,
+	Generator = 183
 }
 
 pub const CHAR_DELTA: u32 = 174;
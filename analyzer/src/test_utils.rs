@@ -1,26 +1,10 @@
 use crate::bit_reader::{BitOrder, BitReader};
-use crate::sample::{SampleBuffer, BUF_SIZE};
-use crate::decoder::{Decoder, Section, SectionBuffer, SectionBufferIter, SectionContent, SECBUF_SIZE};
+use crate::sample::SampleBuffer;
+use crate::capture_file;
+use crate::decoder::{Decoder, Section, SectionBuffer, SectionBufferIter, SectionContent};
 
 const BASE_PATH: &str = "../sample_data/";
 
-pub fn load_buf_from_csv(filename: &str, buf: &mut SampleBuffer) -> Result<(), csv::Error>
-{
-	let mut rdr = csv::ReaderBuilder::new().has_headers(true).from_path(filename)?;
-
-	for result in rdr.records()
-	{
-		let record = result?;
-
-		let timestamp = &record[0].parse::<u32>().unwrap();
-		let data = &record[1].parse::<u16>().unwrap();
-
-		buf.push(*data as u8, *timestamp);
-	}
-
-	Ok(())
-}
-
 pub fn assert_top_layer_eq(actual: &SectionBuffer, expected: &[SectionContent]) {
 	assert_eq!(&top_layer(actual), &expected);
 }
@@ -28,10 +12,7 @@ pub fn assert_top_layer_eq(actual: &SectionBuffer, expected: &[SectionContent])
 pub fn decode_sections(file: &str, decoder: impl Decoder) -> SectionBuffer {
 	let buf = load_sample_buffer(file);
 
-	let mut out_sections = SectionBuffer {
-		sections: [Section::default(); SECBUF_SIZE],
-		len: 0,
-	};
+	let mut out_sections = SectionBuffer::new();
 
 	let result  = decoder.decode(&buf, &mut out_sections);
 	assert!(result.is_ok());
@@ -110,6 +91,8 @@ fn top_layer(buf: &SectionBuffer) -> Vec<SectionContent> {
 	.collect()
 }
 
+// Helper for decoder tests, none uses it right now
+#[allow(dead_code)]
 fn bit_layer(buf: &SectionBuffer) -> Vec<bool> {
 	buf
 	.iter()
@@ -121,13 +104,7 @@ fn bit_layer(buf: &SectionBuffer) -> Vec<bool> {
 }
 
 pub fn load_sample_buffer(path: &str) -> SampleBuffer {
-	let mut buf = SampleBuffer {
-		samples: [0; BUF_SIZE],
-		timestamps: [0; BUF_SIZE],
-		len: 0,
-	};
-
-	load_buf_from_csv(&format!("{BASE_PATH}{path}"), &mut buf).expect("Failed to load buffer from CSV");
-
+	let mut buf = SampleBuffer::new();
+	capture_file::load(&format!("{BASE_PATH}{path}"), &mut buf).expect("Failed to load the sample buffer");
 	buf
-}
\ No newline at end of file
+}
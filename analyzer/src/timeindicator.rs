@@ -1,13 +1,16 @@
 use crate::tinyfont::TINYFONT;
-use crate::hw;
 use crate::font::{lcd_str_undraw, lcd_char, lcd_str};
 use crate::lcd::{LCD_WHITE, LCD_BLACK};
 use crate::bytewriter::ByteMutWriter;
 use core::fmt::Write;
+use crate::sample::Timestamp;
 
 const LABELS: [&str; 4] = [ "s", "ms", "µs", "ns" ];
 
 const NUM_DIGITS: usize = 12;
+
+// Largest value that fits into the digits, ~16 minutes
+const MAX_NS: u64 = 999_999_999_999;
 const PI_Y_OFF: u32 = 8;
 
 pub struct TimeIndicator
@@ -15,7 +18,8 @@ pub struct TimeIndicator
 	x: u32,
 	y: u32,
 	pub visible: bool,
-	digits: [u8; NUM_DIGITS]
+	digits: [u8; NUM_DIGITS],
+	negative: bool
 }
 
 impl TimeIndicator
@@ -27,7 +31,8 @@ impl TimeIndicator
 			x,
 			y,
 			visible: false,
-			digits: [0; NUM_DIGITS]
+			digits: [0; NUM_DIGITS],
+			negative: false
 		}
 	}
 
@@ -36,6 +41,29 @@ impl TimeIndicator
 		(3 - (len as u32)) * (TINYFONT.width + 1)
 	}
 
+	/* Minus sign left of the digits */
+	fn sign_x(&self) -> u32
+	{
+		self.x - (TINYFONT.width + 1)
+	}
+
+	fn sign_update(&mut self, negative: bool)
+	{
+		if self.negative != negative
+		{
+			self.negative = negative;
+			if negative
+			{
+				lcd_char(self.sign_x(), self.y, '-' as u32,
+					LCD_WHITE, LCD_BLACK, &TINYFONT);
+			}
+			else
+			{
+				lcd_str_undraw(self.sign_x(), self.y, 1, &TINYFONT);
+			}
+		}
+	}
+
 	pub fn hide(&mut self)
 	{
 		self.visible = false;
@@ -53,9 +81,15 @@ impl TimeIndicator
 		}
 
 		lcd_str_undraw(self.x, self.y, NUM_DIGITS + 3, &TINYFONT);
+		self.sign_update(false);
 	}
 
-	pub fn show(&mut self, start: u32)
+	pub fn show(&mut self, start: Timestamp, ticks_per_us: u32)
+	{
+		self.show_signed(start as i64, ticks_per_us);
+	}
+
+	pub fn show_signed(&mut self, t: i64, ticks_per_us: u32)
 	{
 		if !self.visible
 		{
@@ -77,9 +111,11 @@ impl TimeIndicator
 			}
 		}
 
+		self.sign_update(t < 0);
+
 		let mut digits: [u8; NUM_DIGITS] = [0x30; NUM_DIGITS];
 		let mut buf = ByteMutWriter::new(&mut digits);
-		let ns = (start as u64) * 1000 / hw::TICKS_PER_US as u64;
+		let ns = u64::min(t.unsigned_abs() * 1000 / ticks_per_us as u64, MAX_NS);
 		write!(buf, "{:0>12}", ns).unwrap();
 
 		let mut x = self.x;
@@ -1,4 +1,5 @@
-use crate::lcd::{LCD_WHITE, LCD_BLACK, lcd_vline, lcd_hline};
+use crate::sample::Timestamp;
+use crate::lcd::{LCD_WHITE, LCD_BLACK, LCD_RED, lcd_vline, lcd_hline};
 
 pub struct TimeLine
 {
@@ -8,6 +9,9 @@ pub struct TimeLine
 	h: u32,
 	x0: u32,
 	x1: u32,
+
+	// Position of the event marker
+	xm: u32,
 	pub visible: bool
 }
 
@@ -23,11 +27,12 @@ impl TimeLine
 			h,
 			x0: u32::MAX,
 			x1: u32::MAX,
+			xm: u32::MAX,
 			visible: false
 		}
 	}
 
-	fn tline_x(&self, t: u32, max: u32, def: u32) -> u32
+	fn tline_x(&self, t: Timestamp, max: Timestamp, def: u32) -> u32
 	{
 		if max == 0
 		{
@@ -53,11 +58,13 @@ impl TimeLine
 		lcd_hline(self.x, self.y, self.w, LCD_BLACK);
 		self.tline(self.x0, LCD_BLACK);
 		if self.x1 != self.x0 { self.tline(self.x1, LCD_BLACK); }
+		if self.xm != self.x0 && self.xm != self.x1 { self.tline(self.xm, LCD_BLACK); }
 		self.x0 = u32::MAX;
 		self.x1 = u32::MAX;
+		self.xm = u32::MAX;
 	}
 
-	pub fn show(&mut self, start: u32, end: u32, max: u32)
+	pub fn show(&mut self, start: Timestamp, end: Timestamp, max: Timestamp, event: Option<Timestamp>)
 	{
 		if !self.visible
 		{
@@ -68,6 +75,14 @@ impl TimeLine
 		/* Timeline */
 		let x0 = self.tline_x(start, max, 0);
 		let x1 = self.tline_x(end, max, self.w - 1);
+		let xm = match event
+		{
+			Some(t) => self.tline_x(t, max, 0),
+			None => u32::MAX
+		};
+
+		/* Undraw marker if moved and not covered by the new lines */
+		if self.xm != xm && self.xm != x0 && self.xm != x1 { self.tline(self.xm, LCD_BLACK); }
 
 		/* Undraw x0, only if != to any of new positons */
 		if self.x0 != x0 && self.x0 != x1 { self.tline(self.x0, LCD_BLACK); }
@@ -81,7 +96,11 @@ impl TimeLine
 		/* Draw x0, only if != to any of prev positions AND was not already drawn */
 		if x1 != self.x0 && x1 != self.x1 && x1 != x0 { self.tline(x1, LCD_WHITE); }
 
+		/* Marker is behind the lines, redraw as the lines may have undrawn it */
+		if xm != x0 && xm != x1 { self.tline(xm, LCD_RED); }
+
 		self.x0 = x0;
 		self.x1 = x1;
+		self.xm = xm;
 	}
 }
@@ -0,0 +1,88 @@
+use core::mem::size_of;
+use crate::arena::Arena;
+use crate::sample::Timestamp;
+
+// Timestamps of a SampleBuffer, stored as they are
+pub struct TimestampBuffer
+{
+	ts: &'static mut [Timestamp]
+}
+
+impl TimestampBuffer
+{
+	// Largest timestamp that can be stored
+	pub const MAX: Timestamp = Timestamp::MAX;
+
+	// Entries a full ring drops at once
+	pub const BLOCK: usize = 1;
+
+	// Bytes of storage per entry
+	pub const ENTRY_SIZE: usize = size_of::<Timestamp>();
+
+	// Usable part of a capacity of len entries
+	pub fn round(len: usize) -> usize
+	{
+		len
+	}
+
+	pub fn alloc(mem: &mut Arena, len: usize) -> Option<TimestampBuffer>
+	{
+		Some(TimestampBuffer { ts: mem.alloc(len, 0)? })
+	}
+
+	pub fn get(&self, idx: usize) -> Timestamp
+	{
+		self.ts[idx]
+	}
+
+	pub fn clear(&mut self)
+	{
+	}
+
+	// Every entry has room, the SampleBuffer checks the count
+	pub fn is_full(&self) -> bool
+	{
+		false
+	}
+
+	pub fn push(&mut self, idx: usize, ts: Timestamp)
+	{
+		self.ts[idx] = ts;
+	}
+
+	pub fn truncate(&mut self, _len: usize)
+	{
+	}
+
+	// Append to a ring of len entries starting at head,
+	// returns how many of the oldest entries were dropped to make room
+	pub fn ring_push(&mut self, head: usize, len: usize, ts: Timestamp) -> usize
+	{
+		let cap = self.ts.len();
+		self.ts[(head + len) % cap] = ts;
+		if len == cap { 1 } else { 0 }
+	}
+
+	// Move the ring starting at head to the start of the buffer
+	pub fn linearize(&mut self, head: usize)
+	{
+		self.ts.rotate_left(head);
+	}
+
+	// Keep the first len entries for which keep returns true, moved back by t0,
+	// returns how many are left
+	pub fn retain(&mut self, len: usize, t0: Timestamp, mut keep: impl FnMut(usize) -> bool) -> usize
+	{
+		let mut kept = 0;
+		for i in 0..len
+		{
+			if keep(i)
+			{
+				self.ts[kept] = self.ts[i] - t0;
+				kept += 1;
+			}
+		}
+
+		kept
+	}
+}
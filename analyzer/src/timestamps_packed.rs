@@ -0,0 +1,344 @@
+use core::mem::size_of;
+use crate::arena::Arena;
+use crate::sample::Timestamp;
+
+// Timestamps of a SampleBuffer as varint deltas to the previous entry,
+// most edges of a busy bus are less than 128 ticks apart and take 1 byte.
+// Every BLOCK entries a checkpoint holds the full timestamp and the offset
+// of the delta bytes, so random access only decodes up to BLOCK deltas.
+// The delta of the first entry is never read, the checkpoint gives it.
+// A ring of entries drops its oldest block at once, the delta bytes
+// wrap around the end of their buffer until it is linearized.
+pub struct TimestampBuffer
+{
+	bases: &'static mut [Timestamp],
+	offsets: &'static mut [u32],
+	deltas: &'static mut [u8],
+
+	// Where the next delta goes and the timestamp it is relative to
+	pos: usize,
+	last: Timestamp
+}
+
+// Longest varint of a Timestamp
+const MAX_DELTA_LEN: usize = 10;
+
+// Checkpoint of a block, the full timestamp and the offset
+const CHECKPOINT_SIZE: usize = size_of::<Timestamp>() + size_of::<u32>();
+
+impl TimestampBuffer
+{
+	// Largest timestamp that can be stored
+	pub const MAX: Timestamp = Timestamp::MAX;
+
+	// Entries per checkpoint, a ring drops this many at once
+	pub const BLOCK: usize = 16;
+
+	// Bytes of storage per entry, the delta bytes get what the checkpoints leave
+	pub const ENTRY_SIZE: usize = 3;
+
+	// Usable part of a capacity of len entries, whole blocks only
+	pub fn round(len: usize) -> usize
+	{
+		len - len % Self::BLOCK
+	}
+
+	pub fn alloc(mem: &mut Arena, len: usize) -> Option<TimestampBuffer>
+	{
+		let blocks = len / Self::BLOCK;
+		Some(TimestampBuffer
+		{
+			bases: mem.alloc(blocks, 0)?,
+			offsets: mem.alloc(blocks, 0)?,
+			deltas: mem.alloc(len * Self::ENTRY_SIZE - blocks * CHECKPOINT_SIZE, 0)?,
+			pos: 0,
+			last: 0
+		})
+	}
+
+	fn capacity(&self) -> usize
+	{
+		self.bases.len() * Self::BLOCK
+	}
+
+	fn read_delta(&self, pos: &mut usize) -> Timestamp
+	{
+		let mut delta = 0;
+		let mut shift = 0;
+		loop
+		{
+			let b = self.deltas[*pos];
+			*pos = (*pos + 1) % self.deltas.len();
+			delta |= ((b & 0x7F) as Timestamp) << shift;
+			if b & 0x80 == 0
+			{
+				return delta;
+			}
+
+			shift += 7;
+		}
+	}
+
+	fn write_delta(&mut self, mut delta: Timestamp)
+	{
+		loop
+		{
+			let b = (delta & 0x7F) as u8;
+			delta >>= 7;
+			self.deltas[self.pos] = if delta == 0 { b } else { b | 0x80 };
+			self.pos = (self.pos + 1) % self.deltas.len();
+			if delta == 0
+			{
+				return;
+			}
+		}
+	}
+
+	// Timestamp of entry idx and the offset after its delta
+	fn seek(&self, idx: usize) -> (Timestamp, usize)
+	{
+		let block = idx / Self::BLOCK;
+		let mut ts = self.bases[block];
+		let mut pos = self.offsets[block] as usize;
+		self.read_delta(&mut pos);
+		for _ in 0..idx % Self::BLOCK
+		{
+			ts += self.read_delta(&mut pos);
+		}
+
+		(ts, pos)
+	}
+
+	pub fn get(&self, idx: usize) -> Timestamp
+	{
+		self.seek(idx).0
+	}
+
+	pub fn clear(&mut self)
+	{
+		self.pos = 0;
+		self.last = 0;
+	}
+
+	// No room for the next and a final entry of a linear buffer
+	pub fn is_full(&self) -> bool
+	{
+		self.deltas.len() - self.pos < 2 * MAX_DELTA_LEN
+	}
+
+	// Append entry idx, all entries before it are written
+	pub fn push(&mut self, idx: usize, ts: Timestamp)
+	{
+		if idx.is_multiple_of(Self::BLOCK)
+		{
+			self.bases[idx / Self::BLOCK] = ts;
+			self.offsets[idx / Self::BLOCK] = self.pos as u32;
+		}
+
+		self.write_delta(ts - self.last);
+		self.last = ts;
+	}
+
+	// Forget all entries from len on
+	pub fn truncate(&mut self, len: usize)
+	{
+		match len
+		{
+			0 => self.clear(),
+			_ => (self.last, self.pos) = self.seek(len - 1)
+		}
+	}
+
+	// Append to a ring of len entries starting at head,
+	// returns how many of the oldest entries were dropped to make room
+	pub fn ring_push(&mut self, head: usize, len: usize, ts: Timestamp) -> usize
+	{
+		let cap = self.capacity();
+		let slot = (head + len) % cap;
+
+		/* A full ring continues at its oldest block */
+		let mut dropped = if len == cap { Self::BLOCK } else { 0 };
+		if slot.is_multiple_of(Self::BLOCK)
+		{
+			self.bases[slot / Self::BLOCK] = ts;
+			self.offsets[slot / Self::BLOCK] = self.pos as u32;
+		}
+
+		/* The delta bytes must not reach the oldest block that is kept */
+		let delta = ts - self.last;
+		let size = (64 - delta.leading_zeros() as usize).div_ceil(7).max(1);
+		loop
+		{
+			let oldest = (head + dropped) % cap / Self::BLOCK;
+			let start = self.offsets[oldest] as usize;
+			let used = (self.pos + self.deltas.len() - start) % self.deltas.len();
+			if used + size < self.deltas.len() || oldest == slot / Self::BLOCK
+			{
+				break;
+			}
+
+			dropped += Self::BLOCK;
+		}
+
+		self.write_delta(delta);
+		self.last = ts;
+		dropped
+	}
+
+	// Move the ring starting at head to the start of the buffer
+	pub fn linearize(&mut self, head: usize)
+	{
+		let block = head / Self::BLOCK;
+		self.bases.rotate_left(block);
+		self.offsets.rotate_left(block);
+
+		let start = self.offsets[0] as usize;
+		let len = self.deltas.len();
+		self.deltas.rotate_left(start);
+		for offset in self.offsets.iter_mut()
+		{
+			*offset = ((*offset as usize + len - start) % len) as u32;
+		}
+
+		self.pos = (self.pos + len - start) % len;
+	}
+
+	// Keep the first len entries for which keep returns true, moved back by t0,
+	// returns how many are left. The deltas of the kept entries are never longer
+	// than the ones they replace, so they are written over the entries already read.
+	pub fn retain(&mut self, len: usize, t0: Timestamp, mut keep: impl FnMut(usize) -> bool) -> usize
+	{
+		if len == 0
+		{
+			self.clear();
+			return 0;
+		}
+
+		let mut ts = self.bases[0];
+		let mut read = self.offsets[0] as usize;
+		self.read_delta(&mut read);
+		self.clear();
+
+		let mut kept = 0;
+		for i in 0..len
+		{
+			if i > 0
+			{
+				ts += self.read_delta(&mut read);
+			}
+
+			if keep(i)
+			{
+				if kept == 0
+				{
+					self.last = ts - t0;
+				}
+
+				self.push(kept, ts - t0);
+				kept += 1;
+			}
+		}
+
+		kept
+	}
+}
+
+#[cfg(test)]
+mod tests
+{
+	use crate::arena::Arena;
+	use crate::timestamps::TimestampBuffer;
+
+	fn buffer(len: usize) -> TimestampBuffer
+	{
+		TimestampBuffer::alloc(&mut Arena::leak(len * TimestampBuffer::ENTRY_SIZE + 16), len).unwrap()
+	}
+
+	#[test]
+	fn test_pack()
+	{
+		let mut ts = buffer(64);
+		let values = [5, 5, 200, (1 << 32) + 7, TimestampBuffer::MAX];
+		for (i, &t) in values.iter().enumerate()
+		{
+			ts.push(i, t);
+		}
+
+		for (i, &t) in values.iter().enumerate()
+		{
+			assert_eq!(ts.get(i), t);
+		}
+
+		ts.truncate(3);
+		ts.push(3, 300);
+		assert_eq!(ts.get(2), 200);
+		assert_eq!(ts.get(3), 300);
+	}
+
+	#[test]
+	fn test_blocks()
+	{
+		let mut ts = buffer(64);
+		for i in 0..64
+		{
+			ts.push(i, i as u64 * i as u64 * 10);
+		}
+
+		assert_eq!(ts.get(17), 2890);
+		assert_eq!(ts.get(63), 39690);
+
+		/* Drop the odd entries and move the rest to 0 */
+		let len = ts.retain(64, 40, |i| i >= 2 && i % 2 == 0);
+		assert_eq!(len, 31);
+		assert_eq!(ts.get(0), 0);
+		assert_eq!(ts.get(16), 34 * 34 * 10 - 40);
+		assert_eq!(ts.get(30), 62 * 62 * 10 - 40);
+
+		/* Room for a gap of any length and the final entry */
+		let mut ts = buffer(16);
+		let mut i = 0;
+		while !ts.is_full()
+		{
+			ts.push(i, i as u64 * 1000);
+			i += 1;
+		}
+
+		ts.push(i, TimestampBuffer::MAX);
+		ts.push(i + 1, TimestampBuffer::MAX);
+		assert_eq!(ts.get(i + 1), TimestampBuffer::MAX);
+	}
+
+	#[test]
+	fn test_ring()
+	{
+		let mut ts = buffer(64);
+		let (mut head, mut len) = (0, 0);
+		for i in 0..100
+		{
+			let dropped = ts.ring_push(head, len, i * 10);
+			head = (head + dropped) % 64;
+			len = len + 1 - dropped;
+		}
+
+		/* Full blocks are dropped, the newest entries are kept */
+		assert_eq!((head, len), (48, 52));
+		ts.linearize(head);
+		assert_eq!(ts.get(0), 480);
+		assert_eq!(ts.get(51), 990);
+
+		/* Long gaps use up the delta bytes before the entries */
+		let mut ts = buffer(64);
+		let (mut head, mut len) = (0, 0);
+		for i in 0..100
+		{
+			let dropped = ts.ring_push(head, len, i << 40);
+			head = (head + dropped) % 64;
+			len = len + 1 - dropped;
+		}
+
+		assert!(len < 48);
+		ts.linearize(head);
+		assert_eq!(ts.get(len - 1), 99 << 40);
+		assert_eq!(ts.get(0), (100 - len as u64) << 40);
+	}
+}
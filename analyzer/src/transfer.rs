@@ -0,0 +1,270 @@
+use crate::sample::{SampleBuffer, Timestamp, CHANNELS};
+use crate::decoder::{SectionBuffer, SectionContent};
+
+/*
+ * Binary transfer of a finished capture over the serial port, decoded on the
+ * host by dumper/src/capture.rs with the constants from here. All numbers are
+ * little endian.
+ *
+ *   magic       "ITSB"
+ *   version     u8
+ *   clock_rate  u32, ticks per second of the timebase
+ *   channels    u8, every sample takes channels / 8 bytes
+ *   count       u32, number of samples
+ *   payload     count times: ticks since the previous sample as varint, sample
+ *   sections    u32, number of decoded sections (since version 2)
+ *   decoded     sections times: start in ticks and length in ticks as varint,
+ *               kind u8, value (length u8 and the text for Err, else a varint)
+ *   crc         u32, CRC-32 (IEEE) of everything from magic to the end of the sections
+ *
+ * Varints hold 7 bits per byte starting with the lowest, the top bit is set
+ * on all bytes but the last.
+ */
+
+pub const MAGIC: [u8; 4] = *b"ITSB";
+pub const VERSION: u8 = 2;
+
+// CRC-32 as used by zlib and Ethernet, bitwise to save the 1 KiB table
+pub struct Crc32
+{
+	crc: u32
+}
+
+impl Crc32
+{
+	pub fn new() -> Self
+	{
+		Crc32 { crc: 0xFFFF_FFFF }
+	}
+
+	pub fn update(&mut self, b: u8)
+	{
+		self.crc ^= b as u32;
+		for _ in 0..8
+		{
+			let mask = (self.crc & 1).wrapping_neg();
+			self.crc = (self.crc >> 1) ^ (0xEDB8_8320 & mask);
+		}
+	}
+
+	pub fn finish(&self) -> u32
+	{
+		!self.crc
+	}
+}
+
+// Sends bytes and keeps the checksum of everything sent
+struct Writer<'a, F: FnMut(u8)>
+{
+	crc: Crc32,
+	out: &'a mut F
+}
+
+impl<F: FnMut(u8)> Writer<'_, F>
+{
+	fn put(&mut self, bytes: &[u8])
+	{
+		for &b in bytes
+		{
+			self.crc.update(b);
+			(self.out)(b);
+		}
+	}
+
+	fn varint(&mut self, mut v: Timestamp)
+	{
+		while v >= 0x80
+		{
+			self.put(&[v as u8 | 0x80]);
+			v >>= 7;
+		}
+
+		self.put(&[v as u8]);
+	}
+}
+
+// Name of each kind as in SectionContent and whether it has a value,
+// indexed by the number kind() sends
+pub const KINDS: [(&str, bool); 29] = [
+	("Empty", false), ("Byte", true), ("TxByte", true), ("RxByte", true),
+	("Bit", true), ("StartBit", false), ("StopBit", false), ("RepeatedStart", false),
+	("Ack", false), ("Nak", false), ("I2cWrite", false), ("I2cRead", false),
+	("I2cAddress", true), ("ParityBit", true), ("Err", true), ("Reset", false),
+	("CRC", true), ("ResetResponse", true), ("Data", true), ("ResetRecovery", false),
+	("FunctionCmd", true), ("FamilyCode", true), ("SensorID", true), ("ROMCmd", true),
+	("ChipSelect", true), ("TxWord", true), ("RxWord", true), ("Transaction", true),
+	("WordGap", false)
+];
+
+// Errors carry a text instead of a number
+pub const KIND_ERR: u8 = 14;
+
+// Sends its number of words, the words follow as their own sections
+pub const KIND_TRANSACTION: u8 = 27;
+
+// Kind of a section on the wire, numbered in the order of SectionContent,
+// and its value. Flags are 0 or 1, sections without a value send 0.
+fn kind(content: &SectionContent) -> (u8, u64)
+{
+	match *content
+	{
+		SectionContent::Empty => (0, 0),
+		SectionContent::Byte(v) => (1, v as u64),
+		SectionContent::TxByte(v) => (2, v as u64),
+		SectionContent::RxByte(v) => (3, v as u64),
+		SectionContent::Bit(v) => (4, v as u64),
+		SectionContent::StartBit => (5, 0),
+		SectionContent::StopBit => (6, 0),
+		SectionContent::RepeatedStart => (7, 0),
+		SectionContent::Ack => (8, 0),
+		SectionContent::Nak => (9, 0),
+		SectionContent::I2cWrite => (10, 0),
+		SectionContent::I2cRead => (11, 0),
+		SectionContent::I2cAddress(v) => (12, v as u64),
+		SectionContent::ParityBit(v) => (13, v as u64),
+		SectionContent::Err(_) => (KIND_ERR, 0),
+		SectionContent::Reset => (15, 0),
+		SectionContent::CRC(v) => (16, v as u64),
+		SectionContent::ResetResponse(v) => (17, v as u64),
+		SectionContent::Data(v) => (18, v),
+		SectionContent::ResetRecovery => (19, 0),
+		SectionContent::FunctionCmd(v) => (20, v as u64),
+		SectionContent::FamilyCode(v) => (21, v as u64),
+		SectionContent::SensorID(v) => (22, v),
+		SectionContent::ROMCmd(v) => (23, v.code() as u64),
+		SectionContent::ChipSelect(v) => (24, v as u64),
+		SectionContent::TxWord(v) => (25, v as u64),
+		SectionContent::RxWord(v) => (26, v as u64),
+		SectionContent::Transaction(v) => (KIND_TRANSACTION, v as u64),
+		SectionContent::WordGap => (28, 0)
+	}
+}
+
+// Encode the capture and its decoded sections into out byte by byte,
+// timestamps stay in ticks of its timebase
+pub fn encode(buf: &SampleBuffer, sections: &SectionBuffer, out: &mut impl FnMut(u8))
+{
+	let mut w = Writer { crc: Crc32::new(), out };
+	w.put(&MAGIC);
+	w.put(&[VERSION]);
+	w.put(&buf.timebase.clock_rate().to_le_bytes());
+	w.put(&[CHANNELS as u8]);
+	w.put(&(buf.len as u32).to_le_bytes());
+
+	let mut last = 0;
+	for i in 0..buf.len
+	{
+		let ts = buf.timestamp(i);
+		w.varint(ts - last);
+		w.put(&buf.samples[i].to_le_bytes());
+		last = ts;
+	}
+
+	w.put(&(sections.len as u32).to_le_bytes());
+	for sec in sections.iter()
+	{
+		let (kind, value) = kind(&sec.content);
+		w.varint(sec.start);
+		w.varint(sec.end.saturating_sub(sec.start));
+		w.put(&[kind]);
+		match sec.content
+		{
+			SectionContent::Err(text) =>
+			{
+				let text = &text.as_bytes()[..text.len().min(u8::MAX as usize)];
+				w.put(&[text.len() as u8]);
+				w.put(text);
+			}
+			_ => w.varint(value)
+		}
+	}
+
+	let crc = w.crc.finish();
+	for b in crc.to_le_bytes()
+	{
+		(w.out)(b);
+	}
+}
+
+#[cfg(test)]
+mod tests
+{
+	use crate::transfer::{encode, kind, Crc32, KINDS};
+	use crate::sample::{SampleBuffer, Timebase};
+	use crate::decoder::{Section, SectionBuffer, SectionContent};
+	use crate::decoder_onewire::rom_cmd::ROMCmd;
+
+	fn crc(bytes: &[u8]) -> u32
+	{
+		let mut crc = Crc32::new();
+		for &b in bytes
+		{
+			crc.update(b);
+		}
+
+		crc.finish()
+	}
+
+	#[test]
+	fn test_crc()
+	{
+		assert_eq!(crc(b"123456789"), 0xCBF4_3926);
+		assert_eq!(crc(b""), 0);
+	}
+
+	#[test]
+	fn test_encode()
+	{
+		let mut buf = SampleBuffer::new();
+		buf.push(0x0001, 0);
+		buf.push(0x8000, 100);
+		buf.push(0x8000, 300);
+		buf.timebase = Timebase::Mhz10;
+
+		let mut sections = SectionBuffer::new();
+		sections.push(Section { start: 100, end: 300, content: SectionContent::Byte(0x48) });
+		sections.push(Section { start: 300, end: 300, content: SectionContent::Err("Bad") });
+
+		let mut out = Vec::new();
+		encode(&buf, &sections, &mut |b| out.push(b));
+
+		let (body, tail) = out.split_at(out.len() - 4);
+		assert_eq!(body, [
+			b'I', b'T', b'S', b'B', 2,
+			0x80, 0x96, 0x98, 0x00,
+			16,
+			3, 0, 0, 0,
+			0, 0x01, 0x00,
+			100, 0x00, 0x80,
+			0xC8, 0x01, 0x00, 0x80,
+			2, 0, 0, 0,
+			100, 0xC8, 0x01, 1, 0x48,
+			0xAC, 0x02, 0, 14, 3, b'B', b'a', b'd'
+		]);
+		assert_eq!(tail, crc(body).to_le_bytes());
+	}
+
+	#[test]
+	fn test_kinds()
+	{
+		/* The table the host decodes with names every kind in order */
+		let contents = [
+			SectionContent::Empty, SectionContent::Byte(1), SectionContent::TxByte(1), SectionContent::RxByte(1),
+			SectionContent::Bit(true), SectionContent::StartBit, SectionContent::StopBit, SectionContent::RepeatedStart,
+			SectionContent::Ack, SectionContent::Nak, SectionContent::I2cWrite, SectionContent::I2cRead,
+			SectionContent::I2cAddress(1), SectionContent::ParityBit(true), SectionContent::Err("Bad"), SectionContent::Reset,
+			SectionContent::CRC(1), SectionContent::ResetResponse(true), SectionContent::Data(1), SectionContent::ResetRecovery,
+			SectionContent::FunctionCmd(1), SectionContent::FamilyCode(1), SectionContent::SensorID(1),
+			SectionContent::ROMCmd(ROMCmd::ReadROM), SectionContent::ChipSelect(true), SectionContent::TxWord(1),
+			SectionContent::RxWord(1), SectionContent::Transaction(1), SectionContent::WordGap
+		];
+		assert_eq!(contents.len(), KINDS.len());
+
+		for (i, content) in contents.iter().enumerate()
+		{
+			let debug = format!("{:?}", content);
+			assert_eq!(kind(content).0 as usize, i);
+			assert_eq!(KINDS[i], (debug.split('(').next().unwrap(), debug.contains('(')));
+		}
+	}
+}
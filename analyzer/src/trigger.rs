@@ -0,0 +1,219 @@
+use crate::decoder::DecoderPin;
+use crate::sample::{Sample, SampleBuffer, CHANNELS};
+
+#[derive(serde::Serialize, serde::Deserialize, Clone, Copy, PartialEq, Eq, Debug, Default)]
+pub enum TriggerKind
+{
+	// Start recording immediately
+	#[default]
+	Off,
+	Rising,
+	Falling,
+	AnyEdge,
+	// All channels in mask have the level given in value
+	Match,
+	// At least one channel in mask differs from value
+	Mismatch
+}
+
+#[derive(serde::Serialize, serde::Deserialize, Clone, Copy, PartialEq, Eq, Debug)]
+pub struct Trigger
+{
+	pub kind: TriggerKind,
+
+	// Channel for the edge triggers
+	pub channel: DecoderPin,
+
+	// Channels taking part in the pattern, the others are don't care
+	pub mask: Sample,
+
+	// Expected levels of the channels in mask
+	pub value: Sample,
+
+	// Percentage of the buffer recorded after the event,
+	// below 100 the sampler records into a ring until the event occurs
+	pub post_trigger: u8
+}
+
+impl Default for Trigger
+{
+	fn default() -> Self
+	{
+		Trigger
+		{
+			kind: TriggerKind::Off,
+			channel: 0,
+			mask: 0,
+			value: 0,
+			post_trigger: 100
+		}
+	}
+}
+
+impl Trigger
+{
+	pub fn is_armed(&self) -> bool
+	{
+		self.kind != TriggerKind::Off
+	}
+
+	// Keep the history before the event
+	pub fn has_pre_trigger(&self) -> bool
+	{
+		self.post_trigger < 100
+	}
+
+	// Number of entries recorded after the event for a ring of size cap,
+	// at least one entry before the event and the final sample are kept
+	pub fn post_len(&self, cap: usize) -> usize
+	{
+		(cap * self.post_trigger as usize / 100).clamp(1, cap - 1)
+	}
+
+	pub fn is_valid(&self) -> bool
+	{
+		match self.kind
+		{
+			TriggerKind::Match | TriggerKind::Mismatch => self.mask != 0,
+			_ => self.channel < CHANNELS
+		}
+	}
+
+	// Channels the trigger looks at
+	pub fn channels(&self) -> Sample
+	{
+		match self.kind
+		{
+			TriggerKind::Off => 0,
+			TriggerKind::Rising | TriggerKind::Falling | TriggerKind::AnyEdge => 1 << self.channel,
+			TriggerKind::Match | TriggerKind::Mismatch => self.mask
+		}
+	}
+
+	// Does the transition from prev to cur fire the trigger?
+	pub fn is_met(&self, prev: Sample, cur: Sample) -> bool
+	{
+		let bit = 1 << self.channel;
+		let was_high = prev & bit != 0;
+		let is_high = cur & bit != 0;
+		match self.kind
+		{
+			TriggerKind::Off => true,
+			TriggerKind::Rising => !was_high && is_high,
+			TriggerKind::Falling => was_high && !is_high,
+			TriggerKind::AnyEdge => was_high != is_high,
+			TriggerKind::Match => (cur & self.mask) == (self.value & self.mask),
+			TriggerKind::Mismatch => (cur & self.mask) != (self.value & self.mask)
+		}
+	}
+
+	// Index of the first sample that fires the trigger
+	pub fn find(&self, buf: &SampleBuffer) -> Option<usize>
+	{
+		if buf.len == 0
+		{
+			return None;
+		}
+
+		let mut prev = buf.samples[0];
+		if matches!(self.kind, TriggerKind::Off | TriggerKind::Match | TriggerKind::Mismatch)
+			&& self.is_met(prev, prev)
+		{
+			return Some(0);
+		}
+
+		for i in 1..buf.len
+		{
+			let cur = buf.samples[i];
+			if self.is_met(prev, cur)
+			{
+				return Some(i);
+			}
+
+			prev = cur;
+		}
+
+		None
+	}
+}
+
+#[cfg(test)]
+mod tests
+{
+	use crate::trigger::{Trigger, TriggerKind};
+	use crate::test_utils::load_sample_buffer;
+
+	fn edge(kind: TriggerKind, channel: u32) -> Trigger
+	{
+		Trigger { kind, channel, ..Default::default() }
+	}
+
+	fn pattern(kind: TriggerKind, mask: u8, value: u8) -> Trigger
+	{
+		Trigger { kind, mask: mask.into(), value: value.into(), ..Default::default() }
+	}
+
+	#[test]
+	fn test_edges()
+	{
+		assert!(edge(TriggerKind::Rising, 2).is_met(0b000, 0b100));
+		assert!(!edge(TriggerKind::Rising, 2).is_met(0b100, 0b000));
+		assert!(!edge(TriggerKind::Rising, 2).is_met(0b000, 0b010));
+		assert!(edge(TriggerKind::Falling, 0).is_met(0b1, 0b0));
+		assert!(!edge(TriggerKind::Falling, 0).is_met(0b0, 0b1));
+		assert!(edge(TriggerKind::AnyEdge, 1).is_met(0b10, 0b00));
+		assert!(edge(TriggerKind::AnyEdge, 1).is_met(0b00, 0b10));
+		assert!(!edge(TriggerKind::AnyEdge, 1).is_met(0b10, 0b11));
+	}
+
+	#[test]
+	fn test_channels()
+	{
+		assert_eq!(edge(TriggerKind::Off, 3).channels(), 0);
+		assert_eq!(edge(TriggerKind::Falling, 3).channels(), 0b1000);
+		assert_eq!(pattern(TriggerKind::Match, 0b101, 0b001).channels(), 0b101);
+		assert!(edge(TriggerKind::AnyEdge, 15).is_valid());
+		assert!(!edge(TriggerKind::AnyEdge, 16).is_valid());
+	}
+
+	#[test]
+	fn test_pattern()
+	{
+		let t = pattern(TriggerKind::Match, 0b0101, 0b0001);
+		assert!(t.is_met(0, 0b0001));
+		assert!(t.is_met(0, 0b1011));
+		assert!(!t.is_met(0, 0b0101));
+
+		let t = pattern(TriggerKind::Mismatch, 0b0101, 0b0001);
+		assert!(!t.is_met(0, 0b1011));
+		assert!(t.is_met(0, 0b0000));
+
+		assert!(!pattern(TriggerKind::Mismatch, 0, 0).is_valid());
+		assert!(pattern(TriggerKind::Match, 1, 0).is_valid());
+	}
+
+	#[test]
+	fn test_post_len()
+	{
+		let mut t = Trigger::default();
+		assert!(!t.has_pre_trigger());
+		t.post_trigger = 25;
+		assert!(t.has_pre_trigger());
+		assert_eq!(t.post_len(1000), 250);
+		t.post_trigger = 0;
+		assert_eq!(t.post_len(1000), 1);
+		t.post_trigger = 100;
+		assert_eq!(t.post_len(1000), 999);
+	}
+
+	#[test]
+	fn test_find()
+	{
+		let buf = load_sample_buffer("UART/UART_8N1_300_H.csv");
+		assert_eq!(Trigger::default().find(&buf), Some(0));
+		assert_eq!(edge(TriggerKind::Falling, 0).find(&buf), Some(1));
+		assert_eq!(edge(TriggerKind::Rising, 0).find(&buf), Some(2));
+		assert_eq!(pattern(TriggerKind::Match, 1, 0).find(&buf), Some(1));
+		assert_eq!(edge(TriggerKind::Rising, 3).find(&buf), None);
+	}
+}
@@ -0,0 +1,404 @@
+use core::fmt;
+
+/*
+ * Value Change Dump (IEEE 1364) as read by GTKWave and PulseView. Every channel
+ * is a 1 bit wire, samples are 16 bit with channel n in bit n.
+ *
+ * Only depends on core. The dumper and decode use it through the library.
+ */
+
+pub const CHANNEL_NAMES: [&str; 16] = [
+	"CH0", "CH1", "CH2", "CH3", "CH4", "CH5", "CH6", "CH7",
+	"CH8", "CH9", "CH10", "CH11", "CH12", "CH13", "CH14", "CH15"
+];
+
+const PS_PER_S: u128 = 1_000_000_000_000;
+
+// Timescale units in ps, fs is only read
+const UNITS: [(&str, u128); 6] = [
+	("s", PS_PER_S), ("ms", 1_000_000_000), ("us", 1_000_000),
+	("ns", 1_000), ("ps", 1), ("fs", 0)
+];
+
+fn channel_id(ch: usize) -> char
+{
+	(b'!' + ch as u8) as char
+}
+
+// Writes samples as value changes, the last sample of a buffer only
+// repeats the level and marks the end of the capture
+pub struct VcdWriter
+{
+	clock_rate: u32,
+
+	// Length of a time step in ps
+	unit: u64,
+
+	// Channels written, the others are left out
+	mask: u16,
+	level: Option<u16>
+}
+
+impl VcdWriter
+{
+	// Time steps are as long as possible while each tick is a whole
+	// number of them, otherwise 1 ps and rounded (e.g. 90 MHz)
+	pub fn new(clock_rate: u32, mask: u16) -> Self
+	{
+		let clock_rate = clock_rate.max(1);
+		let mut unit = 1;
+		if PS_PER_S.is_multiple_of(clock_rate as u128)
+		{
+			let period = (PS_PER_S / clock_rate as u128) as u64;
+			while period.is_multiple_of(unit * 10) && unit * 10 <= PS_PER_S as u64
+			{
+				unit *= 10;
+			}
+		}
+
+		VcdWriter { clock_rate, unit, mask, level: None }
+	}
+
+	pub fn header(&self, out: &mut impl fmt::Write, names: &[&str]) -> fmt::Result
+	{
+		/* Largest unit the step is a multiple of, e.g. 100 ns */
+		let (name, ps) = UNITS.iter()
+			.find(|(_, ps)| *ps > 0 && (self.unit as u128).is_multiple_of(*ps))
+			.unwrap();
+
+		writeln!(out, "$version ITS-Board Logic Analyzer $end")?;
+		writeln!(out, "$timescale {} {} $end", self.unit as u128 / ps, name)?;
+		writeln!(out, "$scope module logic $end")?;
+		for (ch, name) in names.iter().enumerate().take(16)
+		{
+			if self.mask & (1 << ch) != 0
+			{
+				writeln!(out, "$var wire 1 {} {} $end", channel_id(ch), name)?;
+			}
+		}
+
+		writeln!(out, "$upscope $end")?;
+		writeln!(out, "$enddefinitions $end")
+	}
+
+	pub fn sample(&mut self, out: &mut impl fmt::Write, ts: u64, sample: u16) -> fmt::Result
+	{
+		let div = self.clock_rate as u128 * self.unit as u128;
+		let time = (ts as u128 * PS_PER_S + div / 2) / div;
+		writeln!(out, "#{}", time)?;
+
+		let changed = match self.level
+		{
+			None =>
+			{
+				writeln!(out, "$dumpvars")?;
+				self.mask
+			}
+			Some(level) => (level ^ sample) & self.mask
+		};
+
+		for ch in (0..16).filter(|ch| changed & (1 << ch) != 0)
+		{
+			writeln!(out, "{}{}", (sample >> ch) & 1, channel_id(ch))?;
+		}
+
+		if self.level.is_none()
+		{
+			writeln!(out, "$end")?;
+		}
+
+		self.level = Some(sample);
+		Ok(())
+	}
+}
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum VcdError
+{
+	Timescale,
+	Definitions,
+	Time,
+	Value
+}
+
+// 1 bit variables mapped to channels, by name if called CHn,
+// the others take the free channels in declaration order
+struct Vars<'a>
+{
+	vars: [(&'a str, &'a str); 16],
+	len: usize,
+	channels: [Option<&'a str>; 16]
+}
+
+impl<'a> Vars<'a>
+{
+	fn assign(&mut self)
+	{
+		for &(id, name) in &self.vars[..self.len]
+		{
+			let ch = name.strip_prefix("CH").and_then(|n| n.parse::<usize>().ok());
+			if let Some(ch) = ch.filter(|&ch| ch < 16 && self.channels[ch].is_none())
+			{
+				self.channels[ch] = Some(id);
+			}
+		}
+
+		for &(id, _) in &self.vars[..self.len]
+		{
+			if self.channels.contains(&Some(id))
+			{
+				continue;
+			}
+
+			if let Some(free) = self.channels.iter_mut().find(|c| c.is_none())
+			{
+				*free = Some(id);
+			}
+		}
+	}
+
+	fn mask(&self, id: &str) -> u16
+	{
+		self.channels.iter().enumerate()
+			.filter(|(_, c)| **c == Some(id))
+			.fold(0, |m, (ch, _)| m | 1 << ch)
+	}
+}
+
+fn parse_timescale(text: &str) -> Option<u128>
+{
+	let digits = text.find(|c: char| !c.is_ascii_digit())?;
+	let (n, unit) = text.split_at(digits);
+	let n: u128 = n.parse().ok()?;
+	let &(_, ps) = UNITS.iter().find(|(name, _)| *name == unit.trim())?;
+
+	/* Femtoseconds: 1, 10 or 100 fs, kept in units of 1/1000 ps */
+	Some(if ps == 0 { n } else { n * ps * 1000 })
+}
+
+// Reads a dump into samples with timestamps in ticks of clock_rate, one sample
+// per time with changes and one at the last time as the end.
+// Returns the channels found.
+pub fn read(text: &str, clock_rate: u32, out: &mut impl FnMut(u64, u16)) -> Result<u16, VcdError>
+{
+	let mut tokens = text.split_whitespace();
+	let mut vars = Vars { vars: [("", ""); 16], len: 0, channels: [None; 16] };
+
+	/* Time step in fs, 1 ns if not given */
+	let mut unit: u128 = 1_000_000;
+	while let Some(token) = tokens.next()
+	{
+		match token
+		{
+			"$timescale" =>
+			{
+				let mut text = [0u8; 16];
+				let mut len = 0;
+				for t in tokens.by_ref().take_while(|&t| t != "$end")
+				{
+					for &b in t.as_bytes()
+					{
+						*text.get_mut(len).ok_or(VcdError::Timescale)? = b;
+						len += 1;
+					}
+				}
+
+				let text = core::str::from_utf8(&text[..len]).map_err(|_| VcdError::Timescale)?;
+				unit = parse_timescale(text).ok_or(VcdError::Timescale)?;
+			}
+			"$var" =>
+			{
+				let (_, size, id, name) = (tokens.next(), tokens.next(), tokens.next(), tokens.next());
+				let (Some(size), Some(id), Some(name)) = (size, id, name) else
+				{
+					return Err(VcdError::Definitions);
+				};
+
+				if tokens.by_ref().all(|t| t != "$end")
+				{
+					return Err(VcdError::Definitions);
+				}
+
+				if size == "1" && vars.len < vars.vars.len()
+				{
+					vars.vars[vars.len] = (id, name);
+					vars.len += 1;
+				}
+			}
+			"$enddefinitions" =>
+			{
+				tokens.by_ref().take_while(|&t| t != "$end").for_each(drop);
+				break;
+			}
+			t if t.starts_with('$') =>
+			{
+				tokens.by_ref().take_while(|&t| t != "$end").for_each(drop);
+			}
+			_ => return Err(VcdError::Definitions)
+		}
+	}
+
+	vars.assign();
+
+	let div = 1_000 * PS_PER_S;
+	let to_ticks = |time: u128| ((time * unit * clock_rate as u128 + div / 2) / div) as u64;
+
+	let mut level: u16 = 0;
+	let mut time = 0;
+	let mut last: Option<(u128, u16)> = None;
+	let mut flush = |time: u128, level: u16, end: bool|
+	{
+		let emit = match last
+		{
+			None => true,
+			Some((t, l)) => l != level || (end && t < time)
+		};
+
+		if emit
+		{
+			out(to_ticks(time), level);
+			last = Some((time, level));
+		}
+	};
+
+	while let Some(token) = tokens.next()
+	{
+		let (value, id) = token.split_at(token.chars().next().map_or(1, char::len_utf8));
+		match value
+		{
+			"#" =>
+			{
+				let t: u128 = id.parse().map_err(|_| VcdError::Time)?;
+				if t != time
+				{
+					flush(time, level, false);
+					time = t;
+				}
+			}
+			"0" | "1" | "x" | "X" | "z" | "Z" =>
+			{
+				let mask = vars.mask(id);
+				level = if value == "1" { level | mask } else { level & !mask };
+			}
+			"b" | "B" =>
+			{
+				/* Vector value of a 1 bit variable, the lowest bit counts */
+				let bits = id;
+				let mask = vars.mask(tokens.next().ok_or(VcdError::Value)?);
+				level = if bits.ends_with('1') { level | mask } else { level & !mask };
+			}
+			"r" | "R" =>
+			{
+				tokens.next().ok_or(VcdError::Value)?;
+			}
+			"$" if token == "$comment" =>
+			{
+				tokens.by_ref().take_while(|&t| t != "$end").for_each(drop);
+			}
+			"$" => {}
+			_ => return Err(VcdError::Value)
+		}
+	}
+
+	flush(time, level, true);
+	Ok(vars.channels.iter().enumerate()
+		.filter(|(_, c)| c.is_some())
+		.fold(0, |m, (ch, _)| m | 1 << ch))
+}
+
+#[cfg(test)]
+mod tests
+{
+	use crate::vcd::{read, VcdError, VcdWriter, CHANNEL_NAMES};
+	use crate::test_utils::load_sample_buffer;
+	use crate::capture_file::write_vcd;
+
+	fn write(clock_rate: u32, mask: u16, samples: &[(u64, u16)]) -> String
+	{
+		let mut out = String::new();
+		let mut w = VcdWriter::new(clock_rate, mask);
+		w.header(&mut out, &CHANNEL_NAMES).unwrap();
+		for &(ts, sample) in samples
+		{
+			w.sample(&mut out, ts, sample).unwrap();
+		}
+
+		out
+	}
+
+	fn read_all(text: &str, clock_rate: u32) -> Result<(u16, Vec<(u64, u16)>), VcdError>
+	{
+		let mut samples = Vec::new();
+		let mask = read(text, clock_rate, &mut |ts, s| samples.push((ts, s)))?;
+		Ok((mask, samples))
+	}
+
+	#[test]
+	fn test_write()
+	{
+		let out = write(10_000_000, 0x0003, &[(0, 0x0001), (5, 0x0002), (7, 0x0002)]);
+		assert_eq!(out, "$version ITS-Board Logic Analyzer $end\n\
+			$timescale 100 ns $end\n\
+			$scope module logic $end\n\
+			$var wire 1 ! CH0 $end\n\
+			$var wire 1 \" CH1 $end\n\
+			$upscope $end\n\
+			$enddefinitions $end\n\
+			#0\n$dumpvars\n1!\n0\"\n$end\n\
+			#5\n0!\n1\"\n\
+			#7\n");
+	}
+
+	#[test]
+	fn test_roundtrip()
+	{
+		let samples = [(0, 0x8001), (90, 0x8000), (1000, 0x0000), (123_456_789, 0x0000)];
+		for clock_rate in [90_000_000, 10_000_000, 1_000_000]
+		{
+			let out = write(clock_rate, 0xFFFF, &samples);
+			assert_eq!(read_all(&out, clock_rate), Ok((0xFFFF, samples.to_vec())));
+		}
+
+		/* Disabled channels keep their number */
+		let out = write(1_000_000, 0x8001, &samples);
+		assert_eq!(read_all(&out, 1_000_000), Ok((0x8001, samples.to_vec())));
+	}
+
+	#[test]
+	fn test_foreign()
+	{
+		/* As exported by PulseView, names in declaration order, several changes per time */
+		let text = "$date today $end\n$timescale 1ns $end\n\
+			$comment Acquisition with 2/8 channels at 1 MHz $end\n\
+			$scope module libsigrok $end\n\
+			$var wire 1 ! D0 $end\n$var wire 1 \" D1 $end\n$var wire 8 # bus $end\n\
+			$upscope $end\n$enddefinitions $end\n\
+			#0 1! 0\" b00001111 #\n#2000 0! #2000 1\" #3000 x\"\n#5000";
+
+		assert_eq!(read_all(text, 90_000_000),
+			Ok((0x0003, vec![(0, 0x0001), (180, 0x0002), (270, 0x0000), (450, 0x0000)])));
+	}
+
+	#[test]
+	fn test_errors()
+	{
+		assert_eq!(read_all("$timescale 3 hours $end", 1_000_000), Err(VcdError::Timescale));
+		assert_eq!(read_all("$var wire 1 ! CH0", 1_000_000), Err(VcdError::Definitions));
+		assert_eq!(read_all("$enddefinitions $end #x", 1_000_000), Err(VcdError::Time));
+		assert_eq!(read_all("$enddefinitions $end #0 q!", 1_000_000), Err(VcdError::Value));
+	}
+
+	#[test]
+	fn test_sample_data()
+	{
+		/* Same capture as CSV and as written by the simulator */
+		let csv = load_sample_buffer("UART/UART_8N1_300_H.csv");
+		let vcd = load_sample_buffer("UART/UART_8N1_300_H.vcd");
+		assert!(csv.into_iter().eq(&vcd));
+
+		let mut out = String::new();
+		write_vcd(&csv, &mut out).unwrap();
+		let text = std::fs::read_to_string("../sample_data/UART/UART_8N1_300_H.vcd").unwrap();
+		assert_eq!(out, text);
+	}
+}
@@ -0,0 +1,130 @@
+use std::env;
+use std::error::Error;
+use std::fs;
+use std::io;
+use std::mem::size_of;
+
+use itsboard_rust::arena::Arena;
+use itsboard_rust::decoder::{DecoderUnion, Section, SectionBuffer};
+use itsboard_rust::sample::{Sample, SampleBuffer, Timebase, Timestamp};
+use itsboard_rust::capture_file::read_csv;
+use itsboard_rust::vcd;
+
+mod output;
+
+use output::{write_sections, Format};
+
+// Room for every bit of a UART byte that has only two edges
+const SECTIONS_PER_EDGE: usize = 16;
+
+// The decoder settings as saved by the board, in TOML or JSON:
+// [Uart]
+// rx_pin = 0
+// ...
+fn load_decoder(filename: &str) -> Result<DecoderUnion, Box<dyn Error>>
+{
+	let text = fs::read_to_string(filename)?;
+	parse_decoder(&text, filename.ends_with(".json"))
+}
+
+// Settings the menu would not accept, like pins beyond the channels, are refused
+fn parse_decoder(text: &str, json: bool) -> Result<DecoderUnion, Box<dyn Error>>
+{
+	let config: DecoderUnion = if json
+	{
+		serde_json::from_str(text)?
+	}
+	else
+	{
+		toml::from_str(text)?
+	};
+
+	if config.decoder().is_some_and(|d| !d.is_valid())
+	{
+		return Err("invalid decoder settings".into());
+	}
+
+	Ok(config)
+}
+
+// A CSV as written by the dumper or a VCD, in a buffer of exactly its size
+fn load_capture(filename: &str) -> Result<SampleBuffer, Box<dyn Error>>
+{
+	let timebase = Timebase::default();
+	let mut entries: Vec<(Timestamp, Sample)> = Vec::new();
+	if filename.ends_with(".vcd")
+	{
+		let text = fs::read_to_string(filename)?;
+		vcd::read(&text, timebase.clock_rate(), &mut |ts, data| entries.push((ts, data)))
+			.map_err(|e| format!("invalid VCD: {:?}", e))?;
+	}
+	else
+	{
+		read_csv(filename, &mut |ts, data| entries.push((ts, data)))?;
+	}
+
+	let len = entries.len().max(1);
+	let mut mem = Arena::leak(len * SampleBuffer::ENTRY_SIZE + 16);
+	let mut buf = SampleBuffer::alloc(&mut mem, len).ok_or("capture too large")?;
+	buf.timebase = timebase;
+	for (ts, data) in entries
+	{
+		buf.push(data, ts);
+	}
+
+	Ok(buf)
+}
+
+fn main() -> Result<(), Box<dyn Error>>
+{
+	let mut args: Vec<String> = env::args().collect();
+
+	let mut format = Format::Text;
+	if args.len() > 2 && args[1] == "--format"
+	{
+		format = Format::parse(&args[2]).ok_or("format must be text, csv or json")?;
+		args.drain(1..3);
+	}
+
+	if args.len() != 3
+	{
+		eprintln!("Usage: ./decode [--format text|csv|json] `capture.csv|capture.vcd` `decoder.toml|decoder.json`");
+		return Ok(());
+	}
+
+	let buf = load_capture(&args[1])?;
+	let config = load_decoder(&args[2])?;
+	let decoder = config.decoder().ok_or("no decoder configured")?;
+
+	let len = buf.len * SECTIONS_PER_EDGE;
+	let mut mem = Arena::leak((len + 1) * size_of::<Section>());
+	let mut sections = SectionBuffer::alloc(&mut mem, len).ok_or("capture too large")?;
+
+	/* Whatever was decoded is written, even if the decoder gave up */
+	let result = decoder.decode(&buf, &mut sections);
+	write_sections(&mut io::stdout().lock(), format, &sections, buf.timebase.clock_rate())?;
+	if sections.is_full()
+	{
+		eprintln!("Section buffer full, only the first {} sections are written", len);
+	}
+
+	result.map_err(|_| "decoder failed, the settings do not fit the capture".into())
+}
+
+#[cfg(test)]
+mod tests
+{
+	use crate::parse_decoder;
+
+	#[test]
+	fn test_invalid_pins()
+	{
+		let spi = "[SPI]\nmosi_pin = 0\nmiso_pin = 1\nsck_pin = 2\ncs_pin = 20\ncs_polarity = \"ActiveLow\"\n\
+			mode = 0\nbitorder = \"MsbFirst\"\nword_size = 8\n";
+		assert!(parse_decoder(spi, false).is_err());
+		assert!(parse_decoder(&spi.replace("cs_pin = 20", "cs_pin = 3"), false).is_ok());
+
+		let i2c = r#"{"I2C": {"sda_pin": 0, "scl_pin": 40}}"#;
+		assert!(parse_decoder(i2c, true).is_err());
+	}
+}
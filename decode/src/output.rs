@@ -0,0 +1,178 @@
+use std::io::{self, Write};
+use serde::Serialize;
+
+use itsboard_rust::decoder::{Section, SectionBuffer, SectionContent};
+use itsboard_rust::decoder_spi::{transaction_words, write_transaction};
+use itsboard_rust::sample::Timestamp;
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Format
+{
+	Text,
+	Csv,
+	Json
+}
+
+impl Format
+{
+	pub fn parse(name: &str) -> Option<Format>
+	{
+		match name
+		{
+			"text" => Some(Format::Text),
+			"csv" => Some(Format::Csv),
+			"json" => Some(Format::Json),
+			_ => None
+		}
+	}
+}
+
+// One section as written to CSV and JSON, times in ticks of the capture clock
+#[derive(Serialize)]
+struct Annotation
+{
+	start: Timestamp,
+	end: Timestamp,
+	row: &'static str,
+	text: String
+}
+
+impl Annotation
+{
+	fn new(sections: &SectionBuffer, s: &Section) -> Self
+	{
+		Annotation
+		{
+			start: s.start,
+			end: s.end,
+			row: s.content.row(),
+			text: label(sections, s)
+		}
+	}
+}
+
+#[derive(Serialize)]
+struct Document
+{
+	clock_rate: u32,
+	sections: Vec<Annotation>
+}
+
+fn micros(ts: Timestamp, clock_rate: u32) -> f64
+{
+	ts as f64 * 1_000_000.0 / clock_rate as f64
+}
+
+// The label like on the screen, SPI transactions with their words
+fn label(sections: &SectionBuffer, s: &Section) -> String
+{
+	match s.content
+	{
+		SectionContent::Transaction(_) =>
+		{
+			let mut text = String::new();
+			write_transaction(&mut text, transaction_words(sections, s)).unwrap();
+			text
+		}
+		_ => s.content.to_string()
+	}
+}
+
+pub fn write_sections(out: &mut impl Write, format: Format, sections: &SectionBuffer,
+	clock_rate: u32) -> io::Result<()>
+{
+	match format
+	{
+		/* Start and end in µs, the label like on the screen */
+		Format::Text =>
+		{
+			for s in sections.iter()
+			{
+				writeln!(out, "{:>14.3} {:>14.3}  {:<4}  {}", micros(s.start, clock_rate),
+					micros(s.end, clock_rate), s.content.row(), label(sections, s))?;
+			}
+		}
+		Format::Csv =>
+		{
+			let mut w = csv::Writer::from_writer(out);
+			for s in sections.iter()
+			{
+				w.serialize(Annotation::new(sections, s))?;
+			}
+
+			w.flush()?;
+		}
+		Format::Json =>
+		{
+			let doc = Document
+			{
+				clock_rate,
+				sections: sections.iter().map(|s| Annotation::new(sections, s)).collect()
+			};
+			serde_json::to_writer_pretty(&mut *out, &doc)?;
+			writeln!(out)?;
+		}
+	}
+
+	Ok(())
+}
+
+#[cfg(test)]
+mod tests
+{
+	use itsboard_rust::decoder::{Section, SectionBuffer, SectionContent};
+	use crate::output::{write_sections, Format};
+
+	fn sections() -> SectionBuffer
+	{
+		let mut sections = SectionBuffer::new();
+		sections.push(Section { start: 0, end: 900, content: SectionContent::StartBit });
+		sections.push(Section { start: 900, end: 8100, content: SectionContent::Byte(b',') });
+		sections
+	}
+
+	fn write(format: Format) -> String
+	{
+		let mut out = Vec::new();
+		write_sections(&mut out, format, &sections(), 90_000_000).unwrap();
+		String::from_utf8(out).unwrap()
+	}
+
+	#[test]
+	fn test_text()
+	{
+		assert_eq!(write(Format::Text),
+			"         0.000         10.000  data  Start\n\
+			 \x20       10.000         90.000  data  $2C ,\n");
+	}
+
+	#[test]
+	fn test_transaction()
+	{
+		let mut sections = SectionBuffer::new();
+		sections.push(Section { start: 0, end: 900, content: SectionContent::RxWord(0x555) });
+		sections.push(Section { start: 0, end: 900, content: SectionContent::TxWord(0xABC) });
+		sections.push(Section { start: 0, end: 900, content: SectionContent::Transaction(1) });
+
+		let mut out = Vec::new();
+		write_sections(&mut out, Format::Text, &sections, 90_000_000).unwrap();
+		let text = String::from_utf8(out).unwrap();
+		assert!(text.ends_with("transactions  1 words, MOSI: ABC, MISO: 555\n"));
+	}
+
+	#[test]
+	fn test_csv()
+	{
+		assert_eq!(write(Format::Csv), "start,end,row,text\n0,900,data,Start\n900,8100,data,\"$2C ,\"\n");
+	}
+
+	#[test]
+	fn test_json()
+	{
+		let doc: serde_json::Value = serde_json::from_str(&write(Format::Json)).unwrap();
+		assert_eq!(doc["clock_rate"], 90_000_000);
+		assert_eq!(doc["sections"][1]["start"], 900);
+		assert_eq!(doc["sections"][1]["text"], "$2C ,");
+		assert_eq!(Format::parse("xml"), None);
+	}
+}
@@ -0,0 +1,717 @@
+use std::fmt;
+use std::io;
+use std::io::{Seek, Write};
+
+use itsboard_rust::arena::Arena;
+use itsboard_rust::sample::{SampleBuffer, Timebase};
+use itsboard_rust::sigrok;
+use itsboard_rust::decoder_spi::write_transaction;
+use itsboard_rust::transfer::{Crc32, KINDS, KIND_ERR, KIND_TRANSACTION, MAGIC, VERSION};
+use itsboard_rust::vcd::{VcdWriter, CHANNEL_NAMES};
+
+// version, clock_rate, channels, count
+const HEADER_LEN: usize = 10;
+
+// More than the board can hold, anything above is a corrupted header
+const MAX_SAMPLES: u32 = 1 << 24;
+const MAX_SECTIONS: u32 = 1 << 24;
+
+// Timestamps are written in cycles of the 90 MHz timer clock,
+// like the stream decoder
+const TIMER_CLOCK_RATE: u32 = 90_000_000;
+
+#[derive(Debug, PartialEq)]
+pub enum TransferError
+{
+	Version(u8),
+	Header,
+	Varint,
+	Section,
+	Incomplete { received: u32, count: u32 },
+	Crc { expected: u32, actual: u32 }
+}
+
+impl fmt::Display for TransferError
+{
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result
+	{
+		match self
+		{
+			TransferError::Version(v) => write!(f, "unsupported transfer version {}", v),
+			TransferError::Header => write!(f, "invalid header"),
+			TransferError::Varint => write!(f, "invalid timestamp"),
+			TransferError::Section => write!(f, "invalid decoded section"),
+			TransferError::Incomplete { received, count } =>
+				write!(f, "transfer stopped after {} of {} samples", received, count),
+			TransferError::Crc { expected, actual } =>
+				write!(f, "CRC mismatch, expected {:08X}, got {:08X}", expected, actual)
+		}
+	}
+}
+
+impl std::error::Error for TransferError {}
+
+#[derive(Debug, PartialEq, Clone)]
+pub enum SectionValue
+{
+	Number(u64),
+	Text(String)
+}
+
+// A section found by the decoder on the board
+#[derive(Debug, PartialEq)]
+pub struct Section
+{
+	pub start: u64,
+	pub end: u64,
+	pub kind: u8,
+	pub value: SectionValue
+}
+
+impl Section
+{
+	fn empty() -> Self
+	{
+		Section { start: 0, end: 0, kind: 0, value: SectionValue::Number(0) }
+	}
+}
+
+// A capture as sent by the board, timestamps in ticks of clock_rate
+#[derive(Debug, PartialEq)]
+pub struct Capture
+{
+	pub clock_rate: u32,
+	pub channels: u8,
+	pub samples: Vec<(u64, u16)>,
+	pub sections: Vec<Section>
+}
+
+impl Capture
+{
+	fn empty() -> Self
+	{
+		Capture { clock_rate: 0, channels: 0, samples: Vec::new(), sections: Vec::new() }
+	}
+
+	fn divider(&self) -> u64
+	{
+		(TIMER_CLOCK_RATE / self.clock_rate.max(1)).max(1) as u64
+	}
+
+	// Value as written to the files, a transaction lists its words
+	// like the annotations the board writes
+	fn value(&self, s: &Section) -> SectionValue
+	{
+		if s.kind != KIND_TRANSACTION
+		{
+			return s.value.clone();
+		}
+
+		let words = self.sections.iter()
+			.filter(|w| w.start >= s.start && w.end <= s.end)
+			.filter_map(|w| match (KINDS[w.kind as usize].0, &w.value)
+			{
+				("TxByte" | "TxWord", SectionValue::Number(v)) => Some((true, *v as u32)),
+				("RxByte" | "RxWord", SectionValue::Number(v)) => Some((false, *v as u32)),
+				_ => None
+			});
+
+		let mut text = String::new();
+		write_transaction(&mut text, words).unwrap();
+		SectionValue::Text(text)
+	}
+
+	// Same CSV format as the stream decoder
+	pub fn write_csv(&self, out: &mut impl Write) -> io::Result<()>
+	{
+		let divider = self.divider();
+		writeln!(out, "Timestamp,Data")?;
+		for &(ts, sample) in &self.samples
+		{
+			writeln!(out, "{},{}", ts * divider, sample)?;
+		}
+
+		Ok(())
+	}
+
+	// Times like the samples, the kind as named in SectionContent,
+	// numbers in decimal, flags as 0 and 1
+	pub fn write_sections(&self, out: &mut impl Write) -> io::Result<()>
+	{
+		let divider = self.divider();
+		writeln!(out, "Start,End,Kind,Value")?;
+		for s in &self.sections
+		{
+			let (name, has_value) = KINDS[s.kind as usize];
+			write!(out, "{},{},{},", s.start * divider, s.end * divider, name)?;
+			match &self.value(s)
+			{
+				SectionValue::Text(text) => writeln!(out, "\"{}\"", text.replace('"', "\"\""))?,
+				SectionValue::Number(v) if has_value => writeln!(out, "{}", v)?,
+				SectionValue::Number(_) => writeln!(out)?
+			}
+		}
+
+		Ok(())
+	}
+
+	// One line per section for reading along, times in µs
+	pub fn write_sections_text(&self, out: &mut impl Write) -> io::Result<()>
+	{
+		let micros = |ts: u64| ts as f64 * 1_000_000.0 / self.clock_rate.max(1) as f64;
+		for s in &self.sections
+		{
+			let (name, has_value) = KINDS[s.kind as usize];
+			write!(out, "{:>14.3} {:>14.3}  {:<13}", micros(s.start), micros(s.end), name)?;
+			match &self.value(s)
+			{
+				SectionValue::Text(text) => writeln!(out, " {}", text)?,
+				SectionValue::Number(v) if has_value => writeln!(out, " {}", v)?,
+				SectionValue::Number(_) => writeln!(out)?
+			}
+		}
+
+		Ok(())
+	}
+
+	pub fn write_vcd(&self, out: &mut impl Write) -> io::Result<()>
+	{
+		let mut text = String::new();
+		let mut w = VcdWriter::new(self.clock_rate, u16::MAX);
+		w.header(&mut text, &CHANNEL_NAMES).map_err(io::Error::other)?;
+		for &(ts, sample) in &self.samples
+		{
+			w.sample(&mut text, ts, sample).map_err(io::Error::other)?;
+		}
+
+		out.write_all(text.as_bytes())
+	}
+
+	// The samples in a buffer of the library, for the file formats it writes
+	fn sample_buffer(&self) -> io::Result<SampleBuffer>
+	{
+		let timebase = [Timebase::Mhz90, Timebase::Mhz10, Timebase::Mhz1].into_iter()
+			.find(|t| t.clock_rate() == self.clock_rate)
+			.ok_or_else(|| io::Error::new(io::ErrorKind::InvalidData, "unknown clock rate"))?;
+
+		let len = self.samples.len().max(1);
+		let mut mem = Arena::leak(len * SampleBuffer::ENTRY_SIZE + 16);
+		let mut buf = SampleBuffer::alloc(&mut mem, len)
+			.ok_or_else(|| io::Error::new(io::ErrorKind::OutOfMemory, "capture too large"))?;
+		buf.timebase = timebase;
+		buf.resolution = timebase.sample_period();
+		for &(ts, sample) in &self.samples
+		{
+			buf.push(sample, ts);
+		}
+
+		Ok(buf)
+	}
+
+	// sigrok session for PulseView, the sections in its annotations file
+	// by their kind name as in write_sections
+	pub fn write_sr<W: Write + Seek>(&self, out: W) -> io::Result<()>
+	{
+		let buf = self.sample_buffer()?;
+		let (_, ticks) = sigrok::samplerate(&buf);
+		let mut text = String::new();
+		for s in &self.sections
+		{
+			let (name, has_value) = KINDS[s.kind as usize];
+			text += &match &self.value(s)
+			{
+				SectionValue::Text(value) => format!("{}-{} {}: {}\n", s.start / ticks, s.end / ticks, name, value),
+				SectionValue::Number(v) if has_value => format!("{}-{} {}: {}\n", s.start / ticks, s.end / ticks, name, v),
+				SectionValue::Number(_) => format!("{}-{} {}\n", s.start / ticks, s.end / ticks, name)
+			};
+		}
+
+		sigrok::write_sr_annotations(out, &buf, &text)
+	}
+}
+
+// What a varint is read for
+#[derive(Clone, Copy)]
+enum Field
+{
+	Timestamp,
+	Start,
+	Length,
+	Value
+}
+
+enum State
+{
+	// Looking for the magic, number of bytes matched so far
+	Sync(usize),
+	Header,
+	Varint { shift: u32, field: Field },
+	Sample,
+	SectionCount,
+	Kind,
+	TextLen,
+	Text(usize),
+	Crc
+}
+
+// Picks binary captures out of the serial data, anything between them is skipped
+pub struct CaptureDecoder
+{
+	state: State,
+	bytes: Vec<u8>,
+	crc: Crc32,
+	version: u8,
+	count: u32,
+	section_count: u32,
+	ts: u64,
+	value: u64,
+	section: Section,
+	capture: Capture
+}
+
+impl CaptureDecoder
+{
+	pub fn new() -> Self
+	{
+		CaptureDecoder
+		{
+			state: State::Sync(0),
+			bytes: Vec::new(),
+			crc: Crc32::new(),
+			version: 0,
+			count: 0,
+			section_count: 0,
+			ts: 0,
+			value: 0,
+			section: Section::empty(),
+			capture: Capture::empty()
+		}
+	}
+
+	// True while a transfer has started but is not finished
+	pub fn busy(&self) -> bool
+	{
+		!matches!(self.state, State::Sync(_))
+	}
+
+	// The link went quiet, a started transfer will not be completed
+	pub fn timeout(&mut self) -> Option<TransferError>
+	{
+		if !self.busy()
+		{
+			return None;
+		}
+
+		let received = self.capture.samples.len() as u32;
+		self.state = State::Sync(0);
+		Some(TransferError::Incomplete { received, count: self.count })
+	}
+
+	pub fn feed(&mut self, bytes: &[u8]) -> Option<Result<Capture, TransferError>>
+	{
+		bytes.iter().find_map(|&b| self.push(b))
+	}
+
+	pub fn push(&mut self, b: u8) -> Option<Result<Capture, TransferError>>
+	{
+		if let State::Crc = self.state
+		{
+			return self.push_crc(b);
+		}
+
+		self.crc.update(b);
+		let result = match self.state
+		{
+			State::Sync(matched) => self.push_sync(matched, b),
+			State::Header => self.push_header(b),
+			State::Varint { shift, field } => self.push_varint(shift, field, b),
+			State::Sample => self.push_sample(b),
+			State::SectionCount => self.push_section_count(b),
+			State::Kind => self.push_kind(b),
+			State::TextLen => self.push_text_len(b),
+			State::Text(len) => self.push_text(len, b),
+			State::Crc => unreachable!()
+		};
+
+		/* Resync on errors, the magic may follow right away */
+		if result.is_err()
+		{
+			self.state = State::Sync(0);
+		}
+
+		result.err().map(Err)
+	}
+
+	fn push_sync(&mut self, matched: usize, b: u8) -> Result<(), TransferError>
+	{
+		let matched = if b == MAGIC[matched] { matched + 1 } else if b == MAGIC[0] { 1 } else { 0 };
+		if matched == 1
+		{
+			self.crc = Crc32::new();
+			self.crc.update(b);
+		}
+
+		if matched < MAGIC.len()
+		{
+			self.state = State::Sync(matched);
+			return Ok(());
+		}
+
+		self.bytes.clear();
+		self.state = State::Header;
+		Ok(())
+	}
+
+	fn push_header(&mut self, b: u8) -> Result<(), TransferError>
+	{
+		self.bytes.push(b);
+		if self.bytes.len() < HEADER_LEN
+		{
+			return Ok(());
+		}
+
+		let h = &self.bytes;
+		if h[0] == 0 || h[0] > VERSION
+		{
+			return Err(TransferError::Version(h[0]));
+		}
+
+		let clock_rate = u32::from_le_bytes([h[1], h[2], h[3], h[4]]);
+		let channels = h[5];
+		let count = u32::from_le_bytes([h[6], h[7], h[8], h[9]]);
+		if clock_rate == 0 || (channels != 8 && channels != 16) || count > MAX_SAMPLES
+		{
+			return Err(TransferError::Header);
+		}
+
+		self.capture = Capture
+		{
+			clock_rate,
+			channels,
+			samples: Vec::with_capacity(count as usize),
+			sections: Vec::new()
+		};
+		self.version = h[0];
+		self.count = count;
+		self.ts = 0;
+		self.start_sample();
+		Ok(())
+	}
+
+	fn start_varint(&mut self, field: Field)
+	{
+		self.value = 0;
+		self.state = State::Varint { shift: 0, field };
+	}
+
+	fn start_sample(&mut self)
+	{
+		self.bytes.clear();
+		if (self.capture.samples.len() as u32) < self.count
+		{
+			self.start_varint(Field::Timestamp);
+		}
+		/* Version 1 has no sections */
+		else if self.version >= 2
+		{
+			self.state = State::SectionCount;
+		}
+		else
+		{
+			self.state = State::Crc;
+		}
+	}
+
+	fn start_section(&mut self)
+	{
+		self.bytes.clear();
+		if (self.capture.sections.len() as u32) < self.section_count
+		{
+			self.start_varint(Field::Start);
+		}
+		else
+		{
+			self.state = State::Crc;
+		}
+	}
+
+	fn finish_section(&mut self, value: SectionValue)
+	{
+		self.section.value = value;
+		let s = std::mem::replace(&mut self.section, Section::empty());
+		self.capture.sections.push(s);
+		self.start_section();
+	}
+
+	fn push_varint(&mut self, shift: u32, field: Field, b: u8) -> Result<(), TransferError>
+	{
+		if shift > 63
+		{
+			return Err(TransferError::Varint);
+		}
+
+		self.value |= ((b & 0x7F) as u64) << shift;
+		if b & 0x80 != 0
+		{
+			self.state = State::Varint { shift: shift + 7, field };
+			return Ok(());
+		}
+
+		match field
+		{
+			Field::Timestamp => self.state = State::Sample,
+			Field::Start =>
+			{
+				self.section.start = self.value;
+				self.start_varint(Field::Length);
+			}
+			Field::Length =>
+			{
+				self.section.end = self.section.start.saturating_add(self.value);
+				self.state = State::Kind;
+			}
+			Field::Value => self.finish_section(SectionValue::Number(self.value))
+		}
+
+		Ok(())
+	}
+
+	fn push_sample(&mut self, b: u8) -> Result<(), TransferError>
+	{
+		self.bytes.push(b);
+		if self.bytes.len() < self.capture.channels as usize / 8
+		{
+			return Ok(());
+		}
+
+		let sample = self.bytes.iter().rev().fold(0u16, |s, &b| s << 8 | b as u16);
+		self.ts += self.value;
+		self.capture.samples.push((self.ts, sample));
+		self.start_sample();
+		Ok(())
+	}
+
+	fn push_section_count(&mut self, b: u8) -> Result<(), TransferError>
+	{
+		self.bytes.push(b);
+		if self.bytes.len() < 4
+		{
+			return Ok(());
+		}
+
+		self.section_count = u32::from_le_bytes([self.bytes[0], self.bytes[1], self.bytes[2], self.bytes[3]]);
+		if self.section_count > MAX_SECTIONS
+		{
+			return Err(TransferError::Section);
+		}
+
+		self.start_section();
+		Ok(())
+	}
+
+	fn push_kind(&mut self, b: u8) -> Result<(), TransferError>
+	{
+		if b as usize >= KINDS.len()
+		{
+			return Err(TransferError::Section);
+		}
+
+		self.section.kind = b;
+		if b == KIND_ERR
+		{
+			self.state = State::TextLen;
+		}
+		else
+		{
+			self.start_varint(Field::Value);
+		}
+
+		Ok(())
+	}
+
+	fn push_text_len(&mut self, b: u8) -> Result<(), TransferError>
+	{
+		self.bytes.clear();
+		if b == 0
+		{
+			self.finish_section(SectionValue::Text(String::new()));
+		}
+		else
+		{
+			self.state = State::Text(b as usize);
+		}
+
+		Ok(())
+	}
+
+	fn push_text(&mut self, len: usize, b: u8) -> Result<(), TransferError>
+	{
+		self.bytes.push(b);
+		if self.bytes.len() == len
+		{
+			let text = String::from_utf8_lossy(&self.bytes).into_owned();
+			self.finish_section(SectionValue::Text(text));
+		}
+
+		Ok(())
+	}
+
+	fn push_crc(&mut self, b: u8) -> Option<Result<Capture, TransferError>>
+	{
+		self.bytes.push(b);
+		if self.bytes.len() < 4
+		{
+			return None;
+		}
+
+		self.state = State::Sync(0);
+		let expected = u32::from_le_bytes([self.bytes[0], self.bytes[1], self.bytes[2], self.bytes[3]]);
+		let actual = self.crc.finish();
+		if expected != actual
+		{
+			return Some(Err(TransferError::Crc { expected, actual }));
+		}
+
+		Some(Ok(std::mem::replace(&mut self.capture, Capture::empty())))
+	}
+}
+
+#[cfg(test)]
+mod tests
+{
+	use std::io::Cursor;
+
+	use itsboard_rust::transfer::Crc32;
+
+	use crate::capture::{Capture, CaptureDecoder, Section, SectionValue, TransferError};
+
+	// Transfer of three samples at 10 MHz and two sections, as encoded by the board
+	fn transfer_version(version: u8) -> Vec<u8>
+	{
+		let mut bytes = vec![
+			b'I', b'T', b'S', b'B', version,
+			0x80, 0x96, 0x98, 0x00,
+			16,
+			3, 0, 0, 0,
+			0, 0x01, 0x00,
+			100, 0x00, 0x80,
+			0xC8, 0x01, 0x00, 0x80
+		];
+		if version >= 2
+		{
+			bytes.extend_from_slice(&[
+				2, 0, 0, 0,
+				100, 0xC8, 0x01, 1, 0x48,
+				0xAC, 0x02, 0, 14, 3, b'B', b'a', b'd'
+			]);
+		}
+
+		let mut crc = Crc32::new();
+		bytes.iter().for_each(|&b| crc.update(b));
+		bytes.extend_from_slice(&crc.finish().to_le_bytes());
+		bytes
+	}
+
+	fn transfer() -> Vec<u8>
+	{
+		transfer_version(2)
+	}
+
+	#[test]
+	fn test_decode()
+	{
+		/* Text printed by the board in front of the transfer is skipped */
+		let mut bytes = b"ITS-Board ready\r\nIT".to_vec();
+		bytes.extend(transfer());
+
+		let capture = CaptureDecoder::new().feed(&bytes).unwrap().unwrap();
+		assert_eq!(capture.clock_rate, 10_000_000);
+		assert_eq!(capture.samples, [(0, 0x0001), (100, 0x8000), (300, 0x8000)]);
+
+		let mut csv = Vec::new();
+		capture.write_csv(&mut csv).unwrap();
+		assert_eq!(csv, b"Timestamp,Data\n0,1\n900,32768\n2700,32768\n");
+	}
+
+	#[test]
+	fn test_sections()
+	{
+		let capture = CaptureDecoder::new().feed(&transfer()).unwrap().unwrap();
+		assert_eq!(capture.sections.len(), 2);
+		assert_eq!((capture.sections[0].start, capture.sections[0].end), (100, 300));
+		assert_eq!(capture.sections[1].value, SectionValue::Text("Bad".into()));
+
+		let mut csv = Vec::new();
+		capture.write_sections(&mut csv).unwrap();
+		assert_eq!(String::from_utf8(csv).unwrap(),
+			"Start,End,Kind,Value\n900,2700,Byte,72\n2700,2700,Err,\"Bad\"\n");
+
+		let mut text = Vec::new();
+		capture.write_sections_text(&mut text).unwrap();
+		assert_eq!(String::from_utf8(text).unwrap(),
+			"        10.000         30.000  Byte          72\n\
+			 \x20       30.000         30.000  Err           Bad\n");
+
+		/* Older firmware sends no sections */
+		let capture = CaptureDecoder::new().feed(&transfer_version(1)).unwrap().unwrap();
+		assert_eq!(capture.samples.len(), 3);
+		assert!(capture.sections.is_empty());
+
+		/* Unknown kinds are rejected */
+		let mut bytes = transfer();
+		bytes[31] = 29;
+		assert_eq!(CaptureDecoder::new().feed(&bytes), Some(Err(TransferError::Section)));
+	}
+
+	#[test]
+	fn test_transaction()
+	{
+		/* Transaction, ChipSelect, RxWord and TxWord of one 12 bit word */
+		let section = |start, end, kind, v| Section { start, end, kind, value: SectionValue::Number(v) };
+		let capture = Capture
+		{
+			clock_rate: 90_000_000,
+			channels: 16,
+			samples: Vec::new(),
+			sections: vec![section(0, 900, 27, 1), section(0, 0, 24, 1),
+				section(90, 810, 26, 0x555), section(90, 810, 25, 0xABC)]
+		};
+
+		let mut csv = Vec::new();
+		capture.write_sections(&mut csv).unwrap();
+		assert!(String::from_utf8(csv).unwrap()
+			.contains("\n0,900,Transaction,\"1 words, MOSI: ABC, MISO: 555\"\n"));
+	}
+
+	#[test]
+	fn test_sr()
+	{
+		let mut capture = CaptureDecoder::new().feed(&transfer()).unwrap().unwrap();
+		let mut sr = Cursor::new(Vec::new());
+		capture.write_sr(&mut sr).unwrap();
+		assert!(sr.into_inner().starts_with(b"PK"));
+
+		capture.clock_rate = 12_345;
+		assert!(capture.write_sr(Cursor::new(Vec::new())).is_err());
+	}
+
+	#[test]
+	fn test_corrupted()
+	{
+		let mut bytes = transfer();
+		bytes[18] ^= 0x04;
+		let mut decoder = CaptureDecoder::new();
+		assert!(matches!(decoder.feed(&bytes), Some(Err(TransferError::Crc { .. }))));
+
+		/* The next transfer is decoded again */
+		assert!(decoder.feed(&transfer()).unwrap().is_ok());
+
+		/* A lost byte stalls the transfer until the link times out */
+		let mut bytes = transfer_version(1);
+		bytes.remove(21);
+		bytes.truncate(bytes.len() - 4);
+		assert!(decoder.feed(&bytes).is_none());
+		assert_eq!(decoder.timeout(), Some(TransferError::Incomplete { received: 2, count: 3 }));
+		assert_eq!(decoder.timeout(), None);
+	}
+}
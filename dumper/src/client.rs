@@ -0,0 +1,261 @@
+use std::fmt;
+use std::io;
+use std::io::{Read, Write};
+
+use itsboard_rust::remote::{Command, Reply, COMMAND_MAX};
+
+use crate::capture::{Capture, CaptureDecoder, TransferError};
+
+// Read timeouts in a row until a command counts as unanswered,
+// a Run waits as long as the trigger does
+const MAX_IDLE: u32 = 3;
+
+#[derive(Debug)]
+pub enum ClientError
+{
+	Io(io::Error),
+	NoReply
+}
+
+impl fmt::Display for ClientError
+{
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result
+	{
+		match self
+		{
+			ClientError::Io(e) => write!(f, "serial port: {}", e),
+			ClientError::NoReply => write!(f, "the board does not answer, is it on the main screen?")
+		}
+	}
+}
+
+impl std::error::Error for ClientError {}
+
+impl From<io::Error> for ClientError
+{
+	fn from(e: io::Error) -> Self
+	{
+		ClientError::Io(e)
+	}
+}
+
+// The answer of the board and the capture it sent in front of it
+pub struct Response
+{
+	pub capture: Option<Result<Capture, TransferError>>,
+	pub reply: Reply
+}
+
+// Sends remote commands (analyzer/src/remote.rs) and waits for the replies,
+// captures that come with them are decoded on the way
+pub struct Client<P: Read + Write>
+{
+	port: P,
+	capture: CaptureDecoder,
+	frame: Vec<u8>
+}
+
+impl<P: Read + Write> Client<P>
+{
+	pub fn new(port: P) -> Self
+	{
+		Client
+		{
+			port,
+			capture: CaptureDecoder::new(),
+			frame: Vec::new()
+		}
+	}
+
+	pub fn request(&mut self, cmd: &Command) -> Result<Response, ClientError>
+	{
+		let mut tmp = [0; COMMAND_MAX];
+		let frame = postcard::to_slice_cobs(cmd, &mut tmp)
+			.map_err(|_| io::Error::from(io::ErrorKind::InvalidInput))?;
+		self.port.write_all(frame)?;
+		self.port.flush()?;
+
+		self.receive(matches!(cmd, Command::Run))
+	}
+
+	fn receive(&mut self, patient: bool) -> Result<Response, ClientError>
+	{
+		let mut capture = None;
+		let mut idle = 0;
+		let mut serial_buf = [0; 1024];
+		loop
+		{
+			let n = match self.port.read(&mut serial_buf)
+			{
+				Ok(0) => return Err(io::Error::from(io::ErrorKind::UnexpectedEof).into()),
+				Ok(n) => n,
+				Err(ref e) if e.kind() == io::ErrorKind::TimedOut =>
+				{
+					if let Some(e) = self.capture.timeout()
+					{
+						capture = Some(Err(e));
+					}
+
+					idle += 1;
+					if !patient && idle >= MAX_IDLE
+					{
+						return Err(ClientError::NoReply);
+					}
+
+					continue;
+				}
+				Err(e) => return Err(e.into())
+			};
+
+			idle = 0;
+			for &b in &serial_buf[..n]
+			{
+				/* Bytes of a capture are no reply, even if they look like one */
+				let busy = self.capture.busy();
+				if let Some(result) = self.capture.push(b)
+				{
+					capture = Some(result);
+					continue;
+				}
+
+				if busy || self.capture.busy()
+				{
+					continue;
+				}
+
+				if let Some(reply) = self.push_reply(b)
+				{
+					return Ok(Response { capture, reply });
+				}
+			}
+		}
+	}
+
+	// The reply once its frame is complete, the zero in front of it
+	// ends whatever was collected since the last one
+	fn push_reply(&mut self, b: u8) -> Option<Reply>
+	{
+		if b != 0
+		{
+			self.frame.push(b);
+			return None;
+		}
+
+		if self.frame.is_empty()
+		{
+			return None;
+		}
+
+		let reply = postcard::from_bytes_cobs(&mut self.frame).ok();
+		self.frame.clear();
+		reply
+	}
+}
+
+#[cfg(test)]
+mod tests
+{
+	use std::io::{Read, Write};
+	use std::thread;
+	use std::time::Duration;
+	use serialport::{SerialPort, TTYPort};
+
+	use itsboard_rust::decoder::{DecoderUnion, Section, SectionBuffer, SectionContent};
+	use itsboard_rust::decoder_i2c::DecoderI2C;
+	use itsboard_rust::remote::{encode_reply, Command, CommandParser, RemoteError, Reply, Status};
+	use itsboard_rust::sample::SampleBuffer;
+	use itsboard_rust::transfer;
+
+	use crate::client::{Client, ClientError};
+
+	// Stands in for the board on the other end of a pseudo-terminal,
+	// answers a single command with the bytes from answer
+	fn board(answer: impl FnOnce(Command) -> Vec<u8> + Send + 'static) -> Client<TTYPort>
+	{
+		let (mut host, mut port) = TTYPort::pair().unwrap();
+		host.set_timeout(Duration::from_millis(50)).unwrap();
+		port.set_timeout(Duration::from_secs(5)).unwrap();
+		thread::spawn(move ||
+		{
+			let mut parser = CommandParser::new();
+			let mut b = [0];
+			while port.read_exact(&mut b).is_ok()
+			{
+				if let Some(Ok(cmd)) = parser.push(b[0])
+				{
+					let bytes = answer(cmd);
+					port.write_all(&bytes).unwrap();
+					port.flush().unwrap();
+
+					/* Keep the port open until the client has read everything */
+					let _ = port.read_exact(&mut b);
+					return;
+				}
+			}
+		});
+
+		Client::new(host)
+	}
+
+	fn reply(reply: Reply) -> Vec<u8>
+	{
+		let mut bytes = Vec::new();
+		encode_reply(&reply, &mut |b| bytes.push(b));
+		bytes
+	}
+
+	#[test]
+	fn test_capture()
+	{
+		let mut client = board(|cmd|
+		{
+			assert!(matches!(cmd, Command::Run));
+			let mut buf = SampleBuffer::new();
+			buf.push(0x0001, 0);
+			buf.push(0x0000, 90);
+			let mut sections = SectionBuffer::new();
+			sections.push(Section { start: 0, end: 90, content: SectionContent::Bit(true) });
+
+			let mut bytes = Vec::new();
+			transfer::encode(&buf, &sections, &mut |b| bytes.push(b));
+			bytes.extend(reply(Reply::Ok));
+			bytes
+		});
+
+		let response = client.request(&Command::Run).unwrap();
+		assert_eq!(response.reply, Reply::Ok);
+		let capture = response.capture.unwrap().unwrap();
+		assert_eq!(capture.samples, [(0, 0x0001), (90, 0x0000)]);
+		assert_eq!(capture.sections.len(), 1);
+	}
+
+	#[test]
+	fn test_config()
+	{
+		let mut client = board(|cmd|
+		{
+			assert!(matches!(cmd, Command::SetDecoder(DecoderUnion::I2C(DecoderI2C { sda_pin: 0, scl_pin: 0 }))));
+			reply(Reply::Error(RemoteError::Argument))
+		});
+
+		let decoder = DecoderUnion::I2C(DecoderI2C { sda_pin: 0, scl_pin: 0 });
+		let response = client.request(&Command::SetDecoder(decoder)).unwrap();
+		assert_eq!(response.reply, Reply::Error(RemoteError::Argument));
+		assert!(response.capture.is_none());
+	}
+
+	#[test]
+	fn test_status()
+	{
+		let status = Status { ready: true, samples: 1000, ..Default::default() };
+		let mut client = board(move |_| reply(Reply::Status(status)));
+		assert_eq!(client.request(&Command::Status).unwrap().reply, Reply::Status(status));
+	}
+
+	#[test]
+	fn test_no_reply()
+	{
+		let mut client = board(|_| Vec::new());
+		assert!(matches!(client.request(&Command::Fetch), Err(ClientError::NoReply)));
+	}
+}
@@ -1,52 +1,164 @@
 use std::env;
 use std::time::Duration;
 use std::io;
-use std::io::Write;
+use std::fs;
 use std::fs::File;
+use std::path::Path;
 use std::error::Error;
 
-fn main() -> Result<(), Box<dyn Error>>
+use serialport::SerialPort;
+
+use itsboard_rust::decoder::DecoderUnion;
+use itsboard_rust::remote::{Command, Reply};
+
+mod stream;
+mod capture;
+mod client;
+
+use stream::StreamDecoder;
+use capture::{Capture, CaptureDecoder};
+use client::Client;
+
+fn usage()
 {
-	let args: Vec<String> = env::args().collect();
-	if args.len() != 3 && args.len() != 4
+	eprintln!("Usage: ./serial-dump list-ports");
+	eprintln!("       ./serial-dump dump [--stream] `port` `baudrate` [`file`]");
+	eprintln!("       ./serial-dump capture `port` `baudrate` [`file`]");
+	eprintln!("       ./serial-dump config `port` `baudrate` `decoder.toml|decoder.json`");
+	eprintln!("       ./serial-dump monitor `port` `baudrate`");
+}
+
+fn open_port(name: &str, baudrate: &str) -> Result<Box<dyn SerialPort>, Box<dyn Error>>
+{
+	Ok(serialport::new(name, baudrate.parse()?)
+		.timeout(Duration::from_millis(1000))
+		.open()?)
+}
+
+// CSV, VCD or a sigrok session by the file extension, the decoded sections
+// next to it in aufnahme.sections.csv, everything to stdout without a file
+fn save_capture(buf: &Capture, filename: Option<&String>) -> io::Result<()>
+{
+	match filename
 	{
-		eprintln!("Usage: ./serial-dump `port` `baudrate` [`file`]");
-		let ports = serialport::available_ports()?;
-		for p in ports
+		Some(filename) if filename.ends_with(".vcd") =>
+			buf.write_vcd(&mut File::create(filename)?)?,
+		Some(filename) if filename.ends_with(".sr") =>
+			buf.write_sr(File::create(filename)?)?,
+		Some(filename) => buf.write_csv(&mut File::create(filename)?)?,
+		None => buf.write_csv(&mut io::stdout())?
+	}
+
+	if !buf.sections.is_empty()
+	{
+		match filename
 		{
-			println!("{}", p.port_name);
+			Some(filename) => buf.write_sections(
+				&mut File::create(Path::new(filename).with_extension("sections.csv"))?)?,
+			None =>
+			{
+				println!();
+				buf.write_sections(&mut io::stdout())?;
+			}
 		}
+	}
+
+	eprintln!("Received {} samples and {} sections, CRC ok",
+		buf.samples.len(), buf.sections.len());
+	Ok(())
+}
+
+// The decoder settings as saved by the board, in TOML or JSON like for decode
+fn load_decoder(filename: &str) -> Result<DecoderUnion, Box<dyn Error>>
+{
+	let text = fs::read_to_string(filename)?;
+	if filename.ends_with(".json")
+	{
+		Ok(serde_json::from_str(&text)?)
+	}
+	else
+	{
+		Ok(toml::from_str(&text)?)
+	}
+}
+
+fn list_ports() -> Result<(), Box<dyn Error>>
+{
+	for p in serialport::available_ports()?
+	{
+		println!("{}", p.port_name);
+	}
+
+	Ok(())
+}
+
+// Waits for whatever the board sends after Run was pressed on it
+fn dump(mut args: &[String]) -> Result<(), Box<dyn Error>>
+{
+	/* Decode the binary edge stream of a streaming capture into CSV */
+	let streaming = args.first().is_some_and(|a| a == "--stream");
+	if streaming
+	{
+		args = &args[1..];
+	}
 
+	if args.len() != 2 && args.len() != 3
+	{
+		usage();
 		return Ok(());
 	}
 
-	let mut file = if args.len() == 4
+	/* A captured buffer is only written once it arrived intact */
+	let mut file = if args.len() == 3 && streaming
 	{
-		let filename = &args[3];
+		let filename = &args[2];
 		Some(File::create(filename)?)
 	}
 	else { None };
 
-	let mut port = serialport::new(&args[1], args[2].parse()?)
-		.timeout(Duration::from_millis(1000))
-		.open()?;
+	let mut port = open_port(&args[0], &args[1])?;
 
+	let mut decoder = StreamDecoder::new();
+	let mut capture = CaptureDecoder::new();
 	loop
 	{
 		let mut serial_buf: Vec<u8> = vec![0; 1024];
 		match port.read(serial_buf.as_mut_slice())
 		{
-			Ok(n) =>
+			Ok(n) if streaming =>
 			{
-				if let Some(ref mut file) = file
+				match file
 				{
-					file.write_all(&serial_buf[0..n])?;
+					Some(ref mut file) => decoder.feed(&serial_buf[0..n], file)?,
+					None => decoder.feed(&serial_buf[0..n], &mut io::stdout())?
 				}
 
-				io::stdout().write_all(&serial_buf[0..n])?;
-				io::stdout().flush()?;
+				if decoder.done
+				{
+					break;
+				}
+			}
+			Ok(n) =>
+			{
+				/* A corrupted capture is reported, the next one is waited for */
+				match capture.feed(&serial_buf[0..n])
+				{
+					Some(Ok(buf)) =>
+					{
+						save_capture(&buf, args.get(2))?;
+						break;
+					}
+					Some(Err(e)) => eprintln!("Capture corrupted: {}", e),
+					None => ()
+				}
+			}
+			Err(ref e) if e.kind() == io::ErrorKind::TimedOut =>
+			{
+				if let Some(e) = capture.timeout()
+				{
+					eprintln!("Capture corrupted: {}", e);
+				}
 			}
-			Err(ref e) if e.kind() == io::ErrorKind::TimedOut => (),
 			Err(e) =>
 			{
 				eprintln!("{:?}", e);
@@ -57,3 +169,99 @@ fn main() -> Result<(), Box<dyn Error>>
 
 	Ok(())
 }
+
+// Runs one capture over the remote commands and downloads it
+fn capture(args: &[String]) -> Result<(), Box<dyn Error>>
+{
+	if args.len() != 2 && args.len() != 3
+	{
+		usage();
+		return Ok(());
+	}
+
+	let mut client = Client::new(open_port(&args[0], &args[1])?);
+	let response = client.request(&Command::Run)?;
+	if let Reply::Error(e) = response.reply
+	{
+		return Err(format!("board refused the capture: {:?}", e).into());
+	}
+
+	match response.capture
+	{
+		Some(Ok(buf)) => Ok(save_capture(&buf, args.get(2))?),
+		Some(Err(e)) => Err(format!("capture corrupted: {}", e).into()),
+		None => Err("no capture received, is Capture set to Buffer?".into())
+	}
+}
+
+fn config(args: &[String]) -> Result<(), Box<dyn Error>>
+{
+	if args.len() != 3
+	{
+		usage();
+		return Ok(());
+	}
+
+	let decoder = load_decoder(&args[2])?;
+	let mut client = Client::new(open_port(&args[0], &args[1])?);
+	match client.request(&Command::SetDecoder(decoder))?.reply
+	{
+		Reply::Error(e) => Err(format!("board refused the decoder: {:?}", e).into()),
+		_ =>
+		{
+			eprintln!("Decoder saved");
+			Ok(())
+		}
+	}
+}
+
+// Captures again and again and prints the decoded sections until interrupted
+fn monitor(args: &[String]) -> Result<(), Box<dyn Error>>
+{
+	if args.len() != 2
+	{
+		usage();
+		return Ok(());
+	}
+
+	let mut client = Client::new(open_port(&args[0], &args[1])?);
+	for n in 1..
+	{
+		let response = client.request(&Command::Run)?;
+		if let Reply::Error(e) = response.reply
+		{
+			return Err(format!("board refused the capture: {:?}", e).into());
+		}
+
+		match response.capture
+		{
+			Some(Ok(buf)) =>
+			{
+				eprintln!("Capture {}: {} samples", n, buf.samples.len());
+				buf.write_sections_text(&mut io::stdout())?;
+			}
+			Some(Err(e)) => eprintln!("Capture corrupted: {}", e),
+			None => return Err("no capture received, is Capture set to Buffer?".into())
+		}
+	}
+
+	Ok(())
+}
+
+fn main() -> Result<(), Box<dyn Error>>
+{
+	let args: Vec<String> = env::args().collect();
+	match args.get(1).map(String::as_str)
+	{
+		Some("list-ports") => list_ports(),
+		Some("dump") => dump(&args[2..]),
+		Some("capture") => capture(&args[2..]),
+		Some("config") => config(&args[2..]),
+		Some("monitor") => monitor(&args[2..]),
+		_ =>
+		{
+			usage();
+			list_ports()
+		}
+	}
+}
@@ -0,0 +1,188 @@
+use std::io;
+use std::io::Write;
+
+use itsboard_rust::stream::StreamFrame;
+
+// Timestamps are written in cycles of the 90 MHz timer clock,
+// like the dump after a buffered capture
+const TIMER_CLOCK_RATE: u32 = 90_000_000;
+
+// Turns the framed edge stream of the board into CSV lines,
+// in the same format as the dump after a buffered capture
+pub struct StreamDecoder
+{
+	frame: Vec<u8>,
+	ts: u64,
+	divider: u64,
+	level: u16,
+	pub lost: u64,
+	pub done: bool
+}
+
+impl StreamDecoder
+{
+	pub fn new() -> Self
+	{
+		StreamDecoder
+		{
+			frame: Vec::new(),
+			ts: 0,
+			divider: 1,
+			level: 0,
+			lost: 0,
+			done: false
+		}
+	}
+
+	pub fn feed(&mut self, bytes: &[u8], out: &mut impl Write) -> io::Result<()>
+	{
+		for &b in bytes
+		{
+			if b != 0
+			{
+				self.frame.push(b);
+				continue;
+			}
+
+			if self.frame.is_empty()
+			{
+				continue;
+			}
+
+			let decoded = postcard::from_bytes_cobs::<StreamFrame>(&mut self.frame);
+			self.frame.clear();
+			match decoded
+			{
+				Ok(frame) => self.frame_done(frame, out)?,
+				Err(_) => eprintln!("Skipping invalid frame"),
+			}
+		}
+
+		Ok(())
+	}
+
+	fn frame_done(&mut self, frame: StreamFrame, out: &mut impl Write) -> io::Result<()>
+	{
+		match frame
+		{
+			StreamFrame::Start { clock_rate, resolution, level } =>
+			{
+				self.ts = 0;
+				self.divider = (TIMER_CLOCK_RATE / clock_rate.max(1)).max(1) as u64;
+				self.level = level;
+				self.lost = 0;
+				self.done = false;
+				eprintln!("Streaming, resolution {} ns",
+					resolution as u64 * 1_000_000_000 / clock_rate.max(1) as u64);
+				writeln!(out, "Timestamp,Data")?;
+				writeln!(out, "0,{}", level)?;
+			}
+			StreamFrame::Edge { delta, level } =>
+			{
+				self.ts += delta * self.divider;
+				self.level = level;
+				writeln!(out, "{},{}", self.ts, level)?;
+			}
+			StreamFrame::Lost { count } =>
+			{
+				self.lost += count as u64;
+				eprintln!("Link too slow, {} edges lost before t={}", count, self.ts);
+			}
+			StreamFrame::End { delta, overrun } =>
+			{
+				/* Like a buffered capture, the last entry marks the end */
+				self.ts += delta * self.divider;
+				self.done = true;
+				writeln!(out, "{},{}", self.ts, self.level)?;
+				if overrun
+				{
+					eprintln!("Capture overrun, samples were lost");
+				}
+
+				eprintln!("Stream ended at t={}, {} edges lost", self.ts, self.lost);
+			}
+		}
+
+		Ok(())
+	}
+}
+
+#[cfg(test)]
+mod tests
+{
+	use itsboard_rust::stream::StreamFrame;
+
+	use crate::stream::StreamDecoder;
+
+	fn frame(frame: StreamFrame) -> Vec<u8>
+	{
+		let mut tmp = [0; 32];
+		postcard::to_slice_cobs(&frame, &mut tmp).unwrap().to_vec()
+	}
+
+	fn start() -> Vec<u8>
+	{
+		frame(StreamFrame::Start { clock_rate: 10_000_000, resolution: 1, level: 0x0000 })
+	}
+
+	fn feed(decoder: &mut StreamDecoder, bytes: &[u8]) -> String
+	{
+		let mut out = Vec::new();
+		decoder.feed(bytes, &mut out).unwrap();
+		String::from_utf8(out).unwrap()
+	}
+
+	#[test]
+	fn test_stream()
+	{
+		/* Ticks of 10 MHz are written in cycles of 90 MHz */
+		let mut bytes = start();
+		bytes.extend(frame(StreamFrame::Edge { delta: 100, level: 0x0001 }));
+		bytes.extend(frame(StreamFrame::End { delta: 50, overrun: false }));
+
+		let mut decoder = StreamDecoder::new();
+		assert_eq!(feed(&mut decoder, &bytes), "Timestamp,Data\n0,0\n900,1\n1350,1\n");
+		assert!(decoder.done);
+
+		/* A new stream starts over */
+		assert_eq!(feed(&mut decoder, &start()), "Timestamp,Data\n0,0\n");
+		assert!(!decoder.done);
+	}
+
+	#[test]
+	fn test_resync()
+	{
+		let mut decoder = StreamDecoder::new();
+		feed(&mut decoder, &start());
+
+		/* The tail of a frame, a COBS block longer than its frame and empty frames */
+		let mut bytes = frame(StreamFrame::Edge { delta: 5, level: 0x0003 })[3..].to_vec();
+		bytes.extend([0x05, 0x11, 0x22, 0x00, 0x00, 0x00]);
+		bytes.extend(frame(StreamFrame::Edge { delta: 10, level: 0x0002 }));
+		assert_eq!(feed(&mut decoder, &bytes), "90,2\n");
+
+		/* A frame split over two reads */
+		let bytes = frame(StreamFrame::Edge { delta: 10, level: 0x0000 });
+		assert_eq!(feed(&mut decoder, &bytes[..2]), "");
+		assert_eq!(feed(&mut decoder, &bytes[2..]), "180,0\n");
+	}
+
+	#[test]
+	fn test_lost()
+	{
+		let mut decoder = StreamDecoder::new();
+		let mut bytes = start();
+		bytes.extend(frame(StreamFrame::Lost { count: 3 }));
+		bytes.extend(frame(StreamFrame::Edge { delta: 20, level: 0x0004 }));
+		bytes.extend(frame(StreamFrame::Lost { count: 2 }));
+		bytes.extend(frame(StreamFrame::End { delta: 0, overrun: true }));
+
+		/* Lost edges leave no line, the next edge carries the level */
+		assert_eq!(feed(&mut decoder, &bytes), "Timestamp,Data\n0,0\n180,4\n180,4\n");
+		assert_eq!(decoder.lost, 5);
+		assert!(decoder.done);
+
+		feed(&mut decoder, &start());
+		assert_eq!(decoder.lost, 0);
+	}
+}